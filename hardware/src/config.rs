@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, de};
 
 const DEFAULT_RAM_BASE: u64 = 0x8000_0000;
 const DEFAULT_RAM_SIZE: usize = 128 * 1024 * 1024;
@@ -8,6 +8,10 @@ const UART_BASE: u64 = 0x1000_0000;
 const DISK_BASE: u64 = 0x9000_0000;
 const CLINT_BASE: u64 = 0x0200_0000;
 
+/// Smallest and largest VLEN accepted from the config.
+const MIN_VLEN: usize = 128;
+const MAX_VLEN: usize = 65536;
+
 const STACK_SIZE: usize = 0x800_000;
 const BUS_WIDTH: u64 = 8;
 const BUS_LATENCY: u64 = 4;
@@ -112,6 +116,9 @@ pub struct MemoryConfig {
 
     #[serde(default = "default_tlb_size")]
     pub tlb_size: usize,
+
+    #[serde(default = "default_pmp_entries")]
+    pub pmp_entries: usize,
 }
 
 impl MemoryConfig {
@@ -186,6 +193,10 @@ fn default_tlb_size() -> usize {
     32
 }
 
+fn default_pmp_entries() -> usize {
+    16
+}
+
 fn default_t_cas() -> u64 {
     14
 }
@@ -277,6 +288,27 @@ pub struct PipelineConfig {
     pub ras_size: usize,
     pub misa_override: Option<String>,
 
+    #[serde(default = "default_vlen", deserialize_with = "deserialize_vlen")]
+    pub vlen: usize,
+
+    #[serde(default)]
+    pub tval_reporting: TvalReporting,
+
+    #[serde(default = "default_triggers")]
+    pub triggers: usize,
+
+    #[serde(default)]
+    pub model: PipelineModel,
+
+    #[serde(default)]
+    pub latencies: LatencyConfig,
+
+    #[serde(default)]
+    pub ports: PortConfig,
+
+    #[serde(default)]
+    pub ooo: OooConfig,
+
     #[serde(default)]
     pub tage: TageConfig,
 
@@ -291,6 +323,328 @@ fn default_width() -> usize {
     1
 }
 
+fn default_vlen() -> usize {
+    128
+}
+
+/// VLEN must be a power of two between `MIN_VLEN` and `MAX_VLEN`.
+fn deserialize_vlen<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    let vlen = usize::deserialize(deserializer)?;
+    if !vlen.is_power_of_two() || !(MIN_VLEN..=MAX_VLEN).contains(&vlen) {
+        return Err(de::Error::custom(format!(
+            "vlen must be a power of two from {} to {}, got {}",
+            MIN_VLEN, MAX_VLEN, vlen
+        )));
+    }
+    Ok(vlen)
+}
+
+fn default_triggers() -> usize {
+    4
+}
+
+/// What `mtval`/`stval` receive when an exception is taken.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TvalReporting {
+    /// The faulting address or instruction bits
+    #[default]
+    Full,
+    /// Always zero, like cores that do not report them
+    Zero,
+}
+
+/// The back-end behind the shared fetch stage.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PipelineModel {
+    /// The five-stage in-order pipeline
+    #[default]
+    Inorder,
+    /// The out-of-order back-end
+    Ooo,
+}
+
+/// Latency and unit count for one class of functional unit.
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct UnitConfig {
+    pub latency: u64,
+
+    /// A pipelined unit accepts a new operation every cycle, an iterative
+    /// one only once the previous operation has finished
+    #[serde(default = "d_u_pipelined")]
+    pub pipelined: bool,
+
+    #[serde(default = "d_u_units")]
+    pub units: usize,
+}
+
+fn unit(latency: u64, pipelined: bool, units: usize) -> UnitConfig {
+    UnitConfig {
+        latency,
+        pipelined,
+        units,
+    }
+}
+
+fn d_u_pipelined() -> bool {
+    true
+}
+
+fn d_u_units() -> usize {
+    1
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LatencyConfig {
+    /// Integer ALU, bit manipulation, branches and jumps
+    #[serde(default = "d_l_alu")]
+    pub alu: UnitConfig,
+
+    /// Integer and carry-less multiply
+    #[serde(default = "d_l_mul")]
+    pub mul: UnitConfig,
+
+    /// Integer divide and remainder
+    #[serde(default = "d_l_div")]
+    pub div: UnitConfig,
+
+    /// AES and SHA
+    #[serde(default = "d_l_crypto")]
+    pub crypto: UnitConfig,
+
+    #[serde(default = "d_l_fp_add")]
+    pub fp_add: UnitConfig,
+
+    #[serde(default = "d_l_fp_mul")]
+    pub fp_mul: UnitConfig,
+
+    #[serde(default = "d_l_fp_fma")]
+    pub fp_fma: UnitConfig,
+
+    #[serde(default = "d_l_fp_div")]
+    pub fp_div: UnitConfig,
+
+    #[serde(default = "d_l_fp_sqrt")]
+    pub fp_sqrt: UnitConfig,
+
+    /// Conversions between integer and floating-point formats
+    #[serde(default = "d_l_fp_cvt")]
+    pub fp_cvt: UnitConfig,
+
+    /// Sign injection, comparisons, min/max, classify and moves
+    #[serde(default = "d_l_fp_misc")]
+    pub fp_misc: UnitConfig,
+}
+
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        Self {
+            alu: d_l_alu(),
+            mul: d_l_mul(),
+            div: d_l_div(),
+            crypto: d_l_crypto(),
+            fp_add: d_l_fp_add(),
+            fp_mul: d_l_fp_mul(),
+            fp_fma: d_l_fp_fma(),
+            fp_div: d_l_fp_div(),
+            fp_sqrt: d_l_fp_sqrt(),
+            fp_cvt: d_l_fp_cvt(),
+            fp_misc: d_l_fp_misc(),
+        }
+    }
+}
+
+fn d_l_alu() -> UnitConfig {
+    unit(1, true, 4)
+}
+
+fn d_l_mul() -> UnitConfig {
+    unit(3, true, 1)
+}
+
+fn d_l_div() -> UnitConfig {
+    unit(20, false, 1)
+}
+
+fn d_l_crypto() -> UnitConfig {
+    unit(1, true, 1)
+}
+
+fn d_l_fp_add() -> UnitConfig {
+    unit(4, true, 2)
+}
+
+fn d_l_fp_mul() -> UnitConfig {
+    unit(4, true, 2)
+}
+
+fn d_l_fp_fma() -> UnitConfig {
+    unit(5, true, 2)
+}
+
+fn d_l_fp_div() -> UnitConfig {
+    unit(12, false, 1)
+}
+
+fn d_l_fp_sqrt() -> UnitConfig {
+    unit(16, false, 1)
+}
+
+fn d_l_fp_cvt() -> UnitConfig {
+    unit(3, true, 1)
+}
+
+fn d_l_fp_misc() -> UnitConfig {
+    unit(1, true, 2)
+}
+
+/// Instructions of each kind that can issue in one cycle.
+#[derive(Debug, Deserialize, Clone)]
+pub struct PortConfig {
+    #[serde(default = "d_p_alu")]
+    pub alu: usize,
+
+    /// Branches and jumps
+    #[serde(default = "d_p_one")]
+    pub branch: usize,
+
+    #[serde(default = "d_p_load")]
+    pub load: usize,
+
+    #[serde(default = "d_p_one")]
+    pub store: usize,
+
+    #[serde(default = "d_p_fp")]
+    pub fp: usize,
+
+    #[serde(default = "d_p_one")]
+    pub muldiv: usize,
+}
+
+impl Default for PortConfig {
+    fn default() -> Self {
+        Self {
+            alu: d_p_alu(),
+            branch: d_p_one(),
+            load: d_p_load(),
+            store: d_p_one(),
+            fp: d_p_fp(),
+            muldiv: d_p_one(),
+        }
+    }
+}
+
+fn d_p_alu() -> usize {
+    4
+}
+
+fn d_p_one() -> usize {
+    1
+}
+
+fn d_p_load() -> usize {
+    2
+}
+
+fn d_p_fp() -> usize {
+    2
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct OooConfig {
+    #[serde(default = "d_o_rob")]
+    pub rob_size: usize,
+
+    #[serde(default = "d_o_int_regs")]
+    pub int_phys_regs: usize,
+
+    #[serde(default = "d_o_fp_regs")]
+    pub fp_phys_regs: usize,
+
+    #[serde(default)]
+    pub issue_queue: IssueQueue,
+
+    #[serde(default = "d_o_iq_size")]
+    pub iq_size: usize,
+
+    #[serde(default = "d_o_lq")]
+    pub load_queue: usize,
+
+    #[serde(default = "d_o_sq")]
+    pub store_queue: usize,
+
+    #[serde(default = "d_o_width")]
+    pub issue_width: usize,
+
+    #[serde(default = "d_o_width")]
+    pub commit_width: usize,
+
+    /// Let loads issue ahead of older stores whose address is not yet known
+    #[serde(default = "d_o_spec")]
+    pub speculative_loads: bool,
+}
+
+impl Default for OooConfig {
+    fn default() -> Self {
+        Self {
+            rob_size: d_o_rob(),
+            int_phys_regs: d_o_int_regs(),
+            fp_phys_regs: d_o_fp_regs(),
+            issue_queue: IssueQueue::default(),
+            iq_size: d_o_iq_size(),
+            load_queue: d_o_lq(),
+            store_queue: d_o_sq(),
+            issue_width: d_o_width(),
+            commit_width: d_o_width(),
+            speculative_loads: d_o_spec(),
+        }
+    }
+}
+
+fn d_o_rob() -> usize {
+    128
+}
+
+fn d_o_int_regs() -> usize {
+    128
+}
+
+fn d_o_fp_regs() -> usize {
+    96
+}
+
+/// How the out-of-order back-end organises its issue queues.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueQueue {
+    /// One queue of `iq_size` entries
+    #[default]
+    Unified,
+    /// Integer, memory and floating-point queues of `iq_size` entries each
+    Distributed,
+}
+
+fn d_o_iq_size() -> usize {
+    48
+}
+
+fn d_o_lq() -> usize {
+    32
+}
+
+fn d_o_sq() -> usize {
+    32
+}
+
+fn d_o_width() -> usize {
+    4
+}
+
+fn d_o_spec() -> bool {
+    true
+}
+
 #[derive(Debug, Deserialize, Clone, Default)]
 pub struct TageConfig {
     #[serde(default = "d_t_b")]
@@ -71,22 +71,87 @@ impl CacheSim {
         }
     }
 
+    pub fn line_bytes(&self) -> usize {
+        self.line_bytes
+    }
+
     pub fn contains(&self, addr: u64) -> bool {
+        self.lookup(addr).is_some()
+    }
+
+    /// Returns the index into `lines` of the valid line holding `addr`.
+    fn lookup(&self, addr: u64) -> Option<usize> {
         if !self.enabled {
-            return false;
+            return None;
         }
 
         let set_index = ((addr as usize) / self.line_bytes) % self.num_sets;
         let tag = addr / (self.line_bytes * self.num_sets) as u64;
         let base_idx = set_index * self.ways;
 
-        for i in 0..self.ways {
-            let idx = base_idx + i;
-            if self.lines[idx].valid && self.lines[idx].tag == tag {
-                return true;
+        (base_idx..base_idx + self.ways)
+            .find(|&idx| self.lines[idx].valid && self.lines[idx].tag == tag)
+    }
+
+    /// Drops the line holding `addr` without writing back dirty data.
+    pub fn invalidate_line(&mut self, addr: u64) {
+        if let Some(idx) = self.lookup(addr) {
+            self.lines[idx].valid = false;
+            self.lines[idx].dirty = false;
+        }
+    }
+
+    /// Writes back the line holding `addr` if it is dirty and keeps it resident.
+    /// Returns the write-back penalty.
+    pub fn clean_line(&mut self, addr: u64, next_level_latency: u64) -> u64 {
+        match self.lookup(addr) {
+            Some(idx) if self.lines[idx].dirty => {
+                self.lines[idx].dirty = false;
+                next_level_latency
             }
+            _ => 0,
+        }
+    }
+
+    /// Writes back the line holding `addr` if it is dirty, then drops it.
+    /// Returns the write-back penalty.
+    pub fn flush_line(&mut self, addr: u64, next_level_latency: u64) -> u64 {
+        let penalty = self.clean_line(addr, next_level_latency);
+        self.invalidate_line(addr);
+        penalty
+    }
+
+    /// Allocates the line holding `addr` as dirty without a fill from the
+    /// next level, as for a block zeroed in place. Returns the write-back
+    /// penalty of the evicted victim, if any.
+    pub fn zero_line(&mut self, addr: u64, next_level_latency: u64) -> u64 {
+        if !self.enabled {
+            return 0;
+        }
+
+        if let Some(idx) = self.lookup(addr) {
+            self.policy.update(idx / self.ways, idx % self.ways);
+            self.lines[idx].dirty = true;
+            0
+        } else {
+            self.install_line(addr, true, next_level_latency)
+        }
+    }
+
+    /// Installs the line holding `addr` if it is absent, the same way the
+    /// hardware prefetchers do. Evictions are not charged.
+    pub fn prefetch_line(&mut self, addr: u64, next_level_latency: u64) {
+        if self.enabled && !self.contains(addr) {
+            self.install_line(addr, false, next_level_latency);
+        }
+    }
+
+    /// Drops every line without writing back dirty data.
+    pub fn invalidate_all(&mut self) {
+        for line in &mut self.lines {
+            line.valid = false;
+            line.dirty = false;
         }
-        false
     }
 
     fn install_line(&mut self, addr: u64, is_write: bool, next_level_latency: u64) -> u64 {
@@ -152,9 +217,7 @@ impl CacheSim {
         }
 
         for target in prefetches {
-            if !self.contains(target) {
-                self.install_line(target, false, next_level_latency);
-            }
+            self.prefetch_line(target, next_level_latency);
         }
 
         (hit, penalty)
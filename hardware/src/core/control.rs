@@ -1,4 +1,8 @@
-use super::pipeline::{ExMem, IdEx, IdExEntry, IfId, MemWb};
+use super::fpu::FpFormat;
+use super::pipeline::{ExMem, IdExEntry, MemWb};
+use super::vpu::VecOp;
+use crate::isa::instruction::InstructionBits;
+use crate::isa::{abi, funct3, opcodes, sys_ops};
 
 #[derive(Clone, Copy, Debug, Default)]
 pub enum AluOp {
@@ -21,6 +25,57 @@ pub enum AluOp {
     Divu,
     Rem,
     Remu,
+    Sh1Add,
+    Sh2Add,
+    Sh3Add,
+    AddUw,
+    Sh1AddUw,
+    Sh2AddUw,
+    Sh3AddUw,
+    SllUw,
+    Andn,
+    Orn,
+    Xnor,
+    Clz,
+    Ctz,
+    Cpop,
+    Max,
+    Maxu,
+    Min,
+    Minu,
+    SextB,
+    SextH,
+    ZextH,
+    Rol,
+    Ror,
+    OrcB,
+    Rev8,
+    Clmul,
+    Clmulh,
+    Clmulr,
+    Bclr,
+    Bext,
+    Binv,
+    Bset,
+    Pack,
+    Packh,
+    Packw,
+    Brev8,
+    Aes64Es,
+    Aes64Esm,
+    Aes64Ds,
+    Aes64Dsm,
+    Aes64Ks1i,
+    Aes64Ks2,
+    Aes64Im,
+    Sha256Sig0,
+    Sha256Sig1,
+    Sha256Sum0,
+    Sha256Sum1,
+    Sha512Sig0,
+    Sha512Sig1,
+    Sha512Sum0,
+    Sha512Sum1,
     FAdd,
     FSub,
     FMul,
@@ -36,8 +91,16 @@ pub enum AluOp {
     FCvtLS,
     FCvtSW,
     FCvtSL,
+    FCvtWuS,
+    FCvtLuS,
+    FCvtSWu,
+    FCvtSLu,
     FCvtSD,
     FCvtDS,
+    FCvtSH,
+    FCvtHS,
+    FCvtDH,
+    FCvtHD,
     FSgnJ,
     FSgnJN,
     FSgnJX,
@@ -49,6 +112,65 @@ pub enum AluOp {
     FMvToF,
 }
 
+impl AluOp {
+    /// AES and SHA operations, which take the configured crypto latency.
+    pub fn is_crypto(self) -> bool {
+        matches!(
+            self,
+            Self::Aes64Es
+                | Self::Aes64Esm
+                | Self::Aes64Ds
+                | Self::Aes64Dsm
+                | Self::Aes64Ks1i
+                | Self::Aes64Ks2
+                | Self::Aes64Im
+                | Self::Sha256Sig0
+                | Self::Sha256Sig1
+                | Self::Sha256Sum0
+                | Self::Sha256Sum1
+                | Self::Sha512Sig0
+                | Self::Sha512Sig1
+                | Self::Sha512Sum0
+                | Self::Sha512Sum1
+        )
+    }
+
+    /// FP operations that can accrue exception flags in fflags.
+    pub fn accrues_fflags(self) -> bool {
+        matches!(
+            self,
+            Self::FAdd
+                | Self::FSub
+                | Self::FMul
+                | Self::FDiv
+                | Self::FSqrt
+                | Self::FMin
+                | Self::FMax
+                | Self::FMAdd
+                | Self::FMSub
+                | Self::FNMAdd
+                | Self::FNMSub
+                | Self::FCvtWS
+                | Self::FCvtLS
+                | Self::FCvtSW
+                | Self::FCvtSL
+                | Self::FCvtWuS
+                | Self::FCvtLuS
+                | Self::FCvtSWu
+                | Self::FCvtSLu
+                | Self::FCvtSD
+                | Self::FCvtDS
+                | Self::FCvtSH
+                | Self::FCvtHS
+                | Self::FCvtDH
+                | Self::FCvtHD
+                | Self::FEq
+                | Self::FLt
+                | Self::FLe
+        )
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 pub enum AtomicOp {
     #[default]
@@ -66,6 +188,26 @@ pub enum AtomicOp {
     Maxu,
 }
 
+/// Zicbom/Zicboz cache-block operations and Zicbop prefetch hints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CacheOp {
+    #[default]
+    None,
+    Inval,
+    Clean,
+    Flush,
+    Zero,
+    PrefetchI,
+    PrefetchR,
+    PrefetchW,
+}
+
+impl CacheOp {
+    pub fn is_prefetch(self) -> bool {
+        matches!(self, Self::PrefetchI | Self::PrefetchR | Self::PrefetchW)
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default)]
 pub enum MemWidth {
     #[default]
@@ -76,6 +218,18 @@ pub enum MemWidth {
     Double,
 }
 
+impl MemWidth {
+    pub fn bytes(self) -> u64 {
+        match self {
+            Self::Nop => 0,
+            Self::Byte => 1,
+            Self::Half => 2,
+            Self::Word => 4,
+            Self::Double => 8,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default)]
 pub enum OpASrc {
     #[default]
@@ -113,6 +267,7 @@ pub struct ControlSignals {
     pub branch: bool,
     pub jump: bool,
     pub is_rv32: bool,
+    pub fp_fmt: FpFormat,
     pub width: MemWidth,
     pub signed_load: bool,
     pub alu: AluOp,
@@ -123,34 +278,82 @@ pub struct ControlSignals {
     pub is_mret: bool,
     pub is_sret: bool,
     pub csr_op: CsrOp,
+    pub is_fence: bool,
+    pub is_fence_i: bool,
     pub rs1_fp: bool,
     pub rs2_fp: bool,
     pub rs3_fp: bool,
     pub atomic_op: AtomicOp,
+    pub cache_op: CacheOp,
+    pub vec_op: VecOp,
+    /// HLV/HSV: access memory as the guest named by hstatus.SPVP
+    pub hyper_mem: bool,
+    /// HLVX: the guest access needs execute rather than read permission
+    pub hlvx: bool,
 }
 
-pub fn need_stall_load_use(id_ex: &IdEx, if_id: &IfId) -> bool {
-    for ex_inst in &id_ex.entries {
-        if !ex_inst.ctrl.mem_read {
-            continue;
-        }
-
-        if !ex_inst.ctrl.fp_reg_write && ex_inst.rd == 0 {
-            continue;
+/// The registers an instruction reads, as (register, is FP) pairs, leaving
+/// out x0 and register fields that hold immediates or function selectors.
+pub fn source_regs(inst: u32, ctrl: &ControlSignals) -> Vec<(usize, bool)> {
+    let rs1 = (inst.rs1(), ctrl.rs1_fp);
+    let rs2 = (inst.rs2(), ctrl.rs2_fp);
+    let mut regs = match inst.opcode() {
+        opcodes::OP_LUI | opcodes::OP_AUIPC | opcodes::OP_JAL => vec![],
+        opcodes::OP_JALR | opcodes::OP_LOAD | opcodes::OP_IMM | opcodes::OP_IMM_32 => vec![rs1],
+        opcodes::OP_BRANCH | opcodes::OP_STORE | opcodes::OP_REG | opcodes::OP_REG_32 => {
+            vec![rs1, rs2]
         }
-
-        for id_inst in &if_id.entries {
-            let inst = id_inst.inst;
-            let next_rs1 = ((inst >> 15) & 0x1f) as usize;
-            let next_rs2 = ((inst >> 20) & 0x1f) as usize;
-            let next_rs3 = ((inst >> 27) & 0x1f) as usize;
-
-            if ex_inst.rd == next_rs1 || ex_inst.rd == next_rs2 || ex_inst.rd == next_rs3 {
-                return true;
+        opcodes::OP_AMO if ctrl.atomic_op == AtomicOp::Lr => vec![rs1],
+        opcodes::OP_AMO => vec![rs1, rs2],
+        opcodes::OP_LOAD_FP | opcodes::OP_STORE_FP => match ctrl.vec_op {
+            VecOp::Strided => vec![rs1, rs2],
+            VecOp::None if ctrl.mem_write => vec![rs1, rs2],
+            _ => vec![rs1],
+        },
+        opcodes::OP_MISC_MEM if ctrl.cache_op != CacheOp::None => vec![rs1],
+        opcodes::OP_SYSTEM if ctrl.hyper_mem && ctrl.mem_write => vec![rs1, rs2],
+        opcodes::OP_SYSTEM if ctrl.hyper_mem => vec![rs1],
+        opcodes::OP_SYSTEM => match ctrl.csr_op {
+            CsrOp::Rw | CsrOp::Rs | CsrOp::Rc => vec![rs1],
+            CsrOp::None => match inst {
+                // The syscall number and arguments are read without being named
+                sys_ops::ECALL => vec![
+                    (abi::REG_A0, false),
+                    (abi::REG_A1, false),
+                    (abi::REG_A7, false),
+                ],
+                _ if matches!(
+                    inst & sys_ops::FENCE_VMA_MASK,
+                    sys_ops::SFENCE_VMA | sys_ops::HFENCE_VVMA | sys_ops::HFENCE_GVMA
+                ) =>
+                {
+                    vec![rs1, rs2]
+                }
+                // EBREAK, WFI, MRET and SRET hold selectors in their register fields
+                _ => vec![],
+            },
+            _ => vec![],
+        },
+        opcodes::OP_V => match ctrl.vec_op {
+            VecOp::Vsetivli => vec![],
+            VecOp::Vsetvli => vec![rs1],
+            VecOp::Vsetvl => vec![rs1, rs2],
+            _ if matches!(inst.funct3(), funct3::OPIVX | funct3::OPMVX | funct3::OPFVF) => {
+                vec![rs1]
             }
+            _ => vec![],
+        },
+        // Single-operand FP instructions (square root, conversions, moves, classify)
+        // clear rs2_fp, since their rs2 field selects the operation
+        opcodes::OP_FP if ctrl.rs2_fp => vec![rs1, rs2],
+        opcodes::OP_FP => vec![rs1],
+        opcodes::OP_FMADD | opcodes::OP_FMSUB | opcodes::OP_FNMADD | opcodes::OP_FNMSUB => {
+            vec![rs1, rs2, (inst.rs3(), true)]
         }
-    }
-    false
+        _ => vec![],
+    };
+    regs.retain(|&(reg, fp)| fp || reg != 0);
+    regs
 }
 
 pub fn forward_rs(id_entry: &IdExEntry, ex_mem: &ExMem, mem_wb: &MemWb) -> (u64, u64, u64) {
@@ -176,7 +379,7 @@ pub fn forward_rs(id_entry: &IdExEntry, ex_mem: &ExMem, mem_wb: &MemWb) -> (u64,
             let wb_val = if wb_entry.ctrl.mem_read {
                 wb_entry.load_data
             } else if wb_entry.ctrl.jump {
-                wb_entry.pc.wrapping_add(4)
+                wb_entry.pc.wrapping_add(wb_entry.inst_size)
             } else {
                 wb_entry.alu
             };
@@ -198,7 +401,7 @@ pub fn forward_rs(id_entry: &IdExEntry, ex_mem: &ExMem, mem_wb: &MemWb) -> (u64,
     for mem_entry in ex_mem.entries.iter() {
         if (mem_entry.ctrl.reg_write || mem_entry.ctrl.fp_reg_write) && !mem_entry.ctrl.mem_read {
             let ex_val = if mem_entry.ctrl.jump {
-                mem_entry.pc.wrapping_add(4)
+                mem_entry.pc.wrapping_add(mem_entry.inst_size)
             } else {
                 mem_entry.alu
             };
@@ -1,13 +1,20 @@
 use super::bp;
 use super::bp::BranchPredictor;
 use super::cache::CacheSim;
-use super::control;
-use super::mmu::Mmu;
+use super::control::CacheOp;
+use super::hpm::Hpm;
+use super::mmu::pmp::Pmp;
+use super::mmu::{self, AccessContext, Mmu};
+use super::ooo::{self, OooCore};
 use super::pipeline::{ExMem, IdEx, IfId, MemWb};
 use super::register_file::RegisterFile;
+use super::scoreboard::Scoreboard;
 use super::stages;
+use super::trigger::Triggers;
 use super::types::{AccessType, PhysAddr, TranslationResult, Trap, VirtAddr};
-use crate::config::Config;
+use super::units::{FunctionalUnits, IssuePorts};
+use super::vpu::{VTYPE_VILL, VectorRegisterFile};
+use crate::config::{Config, PipelineModel, TvalReporting};
 use crate::isa::{abi, csr, sys_ops};
 use crate::stats::SimStats;
 use crate::system::System;
@@ -15,7 +22,12 @@ use crate::system::System;
 #[derive(Default)]
 pub struct Csrs {
     pub mstatus: u64,
-    pub sstatus: u64,
+    pub medeleg: u64,
+    pub mideleg: u64,
+    pub mie: u64,
+    pub mip: u64,
+    pub mcounteren: u64,
+    pub scounteren: u64,
     pub mepc: u64,
     pub sepc: u64,
     pub mtvec: u64,
@@ -28,10 +40,51 @@ pub struct Csrs {
     pub mtval: u64,
     pub stval: u64,
     pub misa: u64,
+    pub fflags: u64,
+    pub frm: u64,
+    pub vstart: u64,
+    pub vxsat: u64,
+    pub vxrm: u64,
+    pub vl: u64,
+    pub vtype: u64,
+    pub pmp: Pmp,
+    pub hpm: Hpm,
+    pub triggers: Triggers,
+
+    // Environment configuration and Sstc timer compares
+    pub menvcfg: u64,
+    pub senvcfg: u64,
+    pub henvcfg: u64,
+    pub stimecmp: u64,
+    pub vstimecmp: u64,
+    pub htimedelta: u64,
+
+    // Hypervisor extension
+    pub hstatus: u64,
+    pub hedeleg: u64,
+    pub hideleg: u64,
+    pub hcounteren: u64,
+    pub htval: u64,
+    pub htinst: u64,
+    pub hgatp: u64,
+    /// hvip.VSTIP; the other bits hvip injects are kept in mip directly
+    pub hvip: u64,
+    pub mtval2: u64,
+    pub mtinst: u64,
+
+    // Virtual supervisor state, which stands in for the S-mode CSRs while V=1
+    pub vsstatus: u64,
+    pub vstvec: u64,
+    pub vsscratch: u64,
+    pub vsepc: u64,
+    pub vscause: u64,
+    pub vstval: u64,
+    pub vsatp: u64,
 }
 
 pub struct Cpu {
     pub regs: RegisterFile,
+    pub vregs: VectorRegisterFile,
     pub pc: u64,
     pub trace: bool,
     pub bus: System,
@@ -39,6 +92,8 @@ pub struct Cpu {
 
     pub csrs: Csrs,
     pub privilege: u8, // 0=User, 1=Supervisor, 3=Machine
+    /// Virtualization mode: with `privilege` 1 or 0 the hart is in VS- or VU-mode
+    pub virt: bool,
 
     pub direct_mode: bool,
     pub mmio_base: u64,
@@ -59,11 +114,19 @@ pub struct Cpu {
 
     pub stall_cycles: u64,
     pub alu_timer: u64,
+    /// Set by WFI; fetch stops and the hart sleeps until an interrupt is pending
+    pub wfi: bool,
+    pub units: FunctionalUnits,
+    pub ports: IssuePorts,
+    pub scoreboard: Scoreboard,
+    pub report_tval: bool,
 
     pub mmu: Mmu,
 
     pub load_reservation: Option<u64>,
     pub pipeline_width: usize,
+    /// The out-of-order back-end, when `[pipeline] model = "ooo"`
+    pub ooo: Option<OooCore>,
 }
 
 impl Cpu {
@@ -72,23 +135,39 @@ impl Cpu {
             let s = override_str.trim_start_matches("0x");
             u64::from_str_radix(s, 16).unwrap_or(0x8000_0000_0014_1101)
         } else {
-            // Default RV64IMAFD:
+            // Default RV64IMAFDCBHSUV:
             // 63:62 = 2 (RV64)
-            // Extensions: A(0), D(3), F(5), I(8), M(12)
+            // Extensions: A(0), B(1), C(2), D(3), F(5), H(7), I(8), M(12), S(18), U(20), V(21)
             let mut val: u64 = 2 << 62;
             val |= 1 << 0; // A - Atomic
+            val |= 1 << 1; // B - Bit Manipulation (Zba/Zbb/Zbs)
+            val |= 1 << 2; // C - Compressed
             val |= 1 << 3; // D - Double Float
             val |= 1 << 5; // F - Single Float
+            val |= 1 << 7; // H - Hypervisor
             val |= 1 << 8; // I - Integer
             val |= 1 << 12; // M - Multiply/Divide
+            val |= 1 << 18; // S - Supervisor Mode
+            val |= 1 << 20; // U - User Mode
+            val |= 1 << 21; // V - Vector
             val
         };
 
         // Initialize CSRs including the configured MISA
         let csrs = Csrs {
-            mstatus: csr::MSTATUS_FS_INIT,
-            sstatus: csr::MSTATUS_FS_INIT,
+            mstatus: csr::MSTATUS_FS_INIT
+                | csr::MSTATUS_VS_INIT
+                | csr::MSTATUS_UXL_64
+                | csr::MSTATUS_SXL_64,
             misa: configured_misa,
+            vtype: VTYPE_VILL,
+            pmp: Pmp::new(config.memory.pmp_entries),
+            triggers: Triggers::new(config.pipeline.triggers),
+            mideleg: csr::MIP_VS_MASK,
+            hstatus: csr::HSTATUS_VSXL_64,
+            vsstatus: csr::MSTATUS_FS_INIT | csr::MSTATUS_VS_INIT | csr::MSTATUS_UXL_64,
+            stimecmp: u64::MAX,
+            vstimecmp: u64::MAX,
             ..Default::default()
         };
 
@@ -121,12 +200,14 @@ impl Cpu {
 
         Self {
             regs: RegisterFile::new(),
+            vregs: VectorRegisterFile::new(config.pipeline.vlen),
             pc: config.general.start_pc_val(),
             trace: config.general.trace_instructions,
             bus: system,
             exit_code: None,
             csrs,
             privilege: 3,
+            virt: false,
             direct_mode: false,
             mmio_base: config.system.disk_base_val(),
             if_id: IfId::default(),
@@ -142,9 +223,16 @@ impl Cpu {
             l3_cache: CacheSim::new(&config.cache.l3),
             stall_cycles: 0,
             alu_timer: 0,
+            wfi: false,
+            units: FunctionalUnits::new(&config.pipeline.latencies),
+            ports: IssuePorts::new(&config.pipeline.ports),
+            scoreboard: Scoreboard::new(),
+            report_tval: config.pipeline.tval_reporting == TvalReporting::Full,
             mmu: Mmu::new(config.memory.tlb_size),
             load_reservation: None,
             pipeline_width: config.pipeline.width,
+            ooo: (config.pipeline.model == PipelineModel::Ooo)
+                .then(|| OooCore::new(&config.pipeline.ooo)),
         }
     }
 
@@ -154,76 +242,149 @@ impl Cpu {
             return Ok(());
         }
 
-        // Timer & Interrupt Handling
-        let timer_irq = self.bus.tick();
+        // Timer & Interrupt Handling: device lines drive the read-only MIP bits,
+        // and with Sstc enabled the stimecmp/vstimecmp comparators drive STIP/VSTIP.
+        // VSTIP is also raised by hvip.VSTIP, with or without the comparator.
+        let sstc_mask = self.sstc_mask();
+        let mut irq_lines = self.bus.tick() | self.csrs.hvip;
+        if sstc_mask != 0 {
+            irq_lines |= self.sstc_lines() & sstc_mask;
+        }
+        let hw_mask = csr::MIP_HW_MASK | csr::MIP_VSTIP | sstc_mask;
+        self.csrs.mip = (self.csrs.mip & !hw_mask) | (irq_lines & hw_mask);
 
-        // Update MIP for Timer
-        let mut mip = self.csr_read(csr::MIP);
-        if timer_irq {
-            mip |= csr::MIP_MTIP;
-        } else {
-            mip &= !csr::MIP_MTIP;
+        // Counters catch up with last cycle's events; an overflow raises LCOFIP
+        if self.csrs.hpm.update(&self.stats, self.privilege, self.virt) {
+            self.csrs.mip |= csr::MIP_LCOFIP;
         }
-        self.csr_write(csr::MIP, mip);
 
-        let mie = self.csr_read(csr::MIE);
+        let mip = self.csrs.mip;
+        let mie = self.csrs.mie;
         let mstatus = self.csrs.mstatus;
 
         // Extract Global Interrupt Enables
         let m_global_ie = (mstatus & csr::MSTATUS_MIE) != 0;
         let s_global_ie = (mstatus & csr::MSTATUS_SIE) != 0;
-        let u_global_ie = (mstatus & csr::MSTATUS_UIE) != 0;
+        let vs_global_ie = (self.csrs.vsstatus & csr::MSTATUS_SIE) != 0;
+
+        // Interrupts preempt lower levels, ranked U/VU < VS < HS < M
+        let level = match (self.privilege, self.virt) {
+            (0, _) => 0,
+            (1, true) => 1,
+            (1, false) => 2,
+            _ => 3,
+        };
+
+        // Supervisor-level interrupts are taken in M-mode unless delegated through mideleg
+        let mideleg = self.csrs.mideleg;
+        let s_target = |bit: u64| {
+            if mideleg & bit != 0 {
+                (2, s_global_ie)
+            } else {
+                (3, m_global_ie)
+            }
+        };
 
-        let check_irq = |pending_bit: u64, enable_bit: u64, mode: u8, global_ie: bool| -> bool {
+        // VS-level interrupts go on to VS-mode through hideleg, but only while V=1;
+        // a disabled level-0 target keeps them pending everywhere else
+        let hideleg = self.csrs.hideleg;
+        let virt = self.virt;
+        let vs_target = |bit: u64| match (hideleg & bit != 0, virt) {
+            (false, _) => s_target(bit),
+            (true, true) => (1, vs_global_ie),
+            (true, false) => (0, false),
+        };
+
+        let check_irq = |pending_bit: u64, enable_bit: u64, target: (u8, bool)| -> bool {
             let pending = (mip & pending_bit) != 0;
             let enabled = (mie & enable_bit) != 0;
             if !pending || !enabled {
                 return false;
             }
 
-            if self.privilege < mode {
+            let (target_level, global_ie) = target;
+            if level < target_level {
                 return true;
             }
-            if self.privilege == mode {
+            if level == target_level {
                 return global_ie;
             }
             false
         };
 
-        // Priority Order (External > Software > Timer, M > S > U)
-        let trap_cause = if check_irq(csr::MIP_MEIP, csr::MIE_MEIP, 3, m_global_ie) {
-            Some(Trap::ExternalInterrupt)
-        } else if check_irq(csr::MIP_MSIP, csr::MIE_MSIP, 3, m_global_ie) {
+        // Priority Order (External > Software > Timer, M > S > VS > U)
+        let trap_cause = if check_irq(csr::MIP_MEIP, csr::MIE_MEIP, (3, m_global_ie)) {
+            Some(Trap::MachineExternalInterrupt)
+        } else if check_irq(csr::MIP_MSIP, csr::MIE_MSIP, (3, m_global_ie)) {
             Some(Trap::MachineSoftwareInterrupt)
-        } else if check_irq(csr::MIP_MTIP, csr::MIE_MTIE, 3, m_global_ie) {
+        } else if check_irq(csr::MIP_MTIP, csr::MIE_MTIE, (3, m_global_ie)) {
             Some(Trap::MachineTimerInterrupt)
-        } else if check_irq(csr::MIP_SEIP, csr::MIE_SEIP, 1, s_global_ie) {
-            Some(Trap::ExternalInterrupt)
-        } else if check_irq(csr::MIP_SSIP, csr::MIE_SSIP, 1, s_global_ie) {
+        } else if check_irq(csr::MIP_SEIP, csr::MIE_SEIP, s_target(csr::MIP_SEIP)) {
+            Some(Trap::SupervisorExternalInterrupt)
+        } else if check_irq(csr::MIP_SSIP, csr::MIE_SSIP, s_target(csr::MIP_SSIP)) {
             Some(Trap::SupervisorSoftwareInterrupt)
-        } else if check_irq(csr::MIP_STIP, csr::MIE_STIE, 1, s_global_ie) {
+        } else if check_irq(csr::MIP_STIP, csr::MIE_STIE, s_target(csr::MIP_STIP)) {
             Some(Trap::SupervisorTimerInterrupt)
-        } else if check_irq(csr::MIP_USIP, csr::MIE_USIP, 0, u_global_ie) {
-            Some(Trap::UserSoftwareInterrupt)
+        } else if check_irq(csr::MIP_VSEIP, csr::MIP_VSEIP, vs_target(csr::MIP_VSEIP)) {
+            Some(Trap::VirtualSupervisorExternalInterrupt)
+        } else if check_irq(csr::MIP_VSSIP, csr::MIP_VSSIP, vs_target(csr::MIP_VSSIP)) {
+            Some(Trap::VirtualSupervisorSoftwareInterrupt)
+        } else if check_irq(csr::MIP_VSTIP, csr::MIP_VSTIP, vs_target(csr::MIP_VSTIP)) {
+            Some(Trap::VirtualSupervisorTimerInterrupt)
+        } else if check_irq(csr::MIP_LCOFIP, csr::MIP_LCOFIP, s_target(csr::MIP_LCOFIP)) {
+            Some(Trap::LocalCounterOverflowInterrupt)
         } else {
             None
         };
 
-        if let Some(trap) = trap_cause {
-            self.trap(trap, self.pc);
-            return Ok(());
+        // An exception on its way to write-back is taken first; everything
+        // younger has already been squashed
+        let mut draining = false;
+        if let Some(trap) = trap_cause
+            && !self.trap_in_flight()
+        {
+            // Accesses already in MEM translate under the privilege they
+            // executed in, so hold issue until they have left it
+            if !self.ex_mem.entries.is_empty() {
+                draining = true;
+            } else {
+                // Resume at the oldest instruction that has not executed yet
+                let epc = self
+                    .id_ex
+                    .entries
+                    .first()
+                    .map(|e| e.pc)
+                    .or_else(|| self.if_id.entries.first().map(|e| e.pc))
+                    .unwrap_or(self.pc);
+                self.trap(trap, epc);
+                return Ok(());
+            }
+        }
+
+        // WFI wakes on any interrupt pending in mie, even one that is globally disabled
+        if self.wfi {
+            if self.csrs.mip & self.csrs.mie != 0 {
+                self.wfi = false;
+            } else if self.is_drained() {
+                self.idle();
+                return Ok(());
+            }
         }
 
         if self.trace {
             self.print_pipeline_diagram();
         }
 
+        if self.ooo.is_some() {
+            return ooo::tick(self);
+        }
+
         // Handle stalls (Memory)
         if self.stall_cycles > 0 {
             self.stall_cycles -= 1;
             self.stats.cycles += 1;
             self.stats.stalls_mem += 1;
-            self.track_mode_cycles();
+            self.track_mode_cycles(1);
             return Ok(());
         }
 
@@ -231,18 +392,16 @@ impl Cpu {
         if self.alu_timer > 0 {
             self.alu_timer -= 1;
             self.stats.cycles += 1;
-            self.track_mode_cycles();
+            self.track_mode_cycles(1);
             return Ok(());
         }
 
         // Advance cycle & stats
         self.stats.cycles += 1;
-        self.track_mode_cycles();
+        self.track_mode_cycles(1);
 
         // Write Back Stage
-        if let Err(trap) = stages::write_back::wb_stage(self) {
-            return Err(format!("{:?}", trap));
-        }
+        stages::write_back::wb_stage(self)?;
 
         // Check for program exit
         if self.exit_code.is_some() {
@@ -254,21 +413,18 @@ impl Cpu {
 
         // Memory & Execute Stages
         stages::memory_access::mem_stage(self)?;
-        stages::execute::execute_stage(self)?;
-
-        // Hazard Detection (Load-Use)
-        let is_load_use_hazard = control::need_stall_load_use(&self.id_ex, &self.if_id);
-
-        if is_load_use_hazard {
-            // Stall: Inject Bubble into ID/EX, do not fetch new instruction
-            self.id_ex = IdEx::bubble();
-            self.stats.stalls_data += 1;
+        if draining {
+            // Nothing issues behind the interrupt; it is taken next cycle
+            self.ex_mem = ExMem::default();
         } else {
-            // Normal operation: Decode & Fetch
+            stages::execute::execute_stage(self)?;
+
+            // Decode & Fetch; decode holds back instructions whose operands the
+            // scoreboard does not have ready yet
             stages::decode::decode_stage(self)?;
 
             // Only fetch if IF/ID is empty (simplified in-order logic)
-            if self.if_id.entries.is_empty() {
+            if self.if_id.entries.is_empty() && !self.wfi && !self.trap_in_flight() {
                 stages::fetch::fetch_stage(self)?;
             }
         }
@@ -279,7 +435,73 @@ impl Cpu {
         Ok(())
     }
 
-    pub fn translate(&mut self, vaddr: VirtAddr, access: AccessType) -> TranslationResult {
+    /// Whether an instruction in the pipeline will trap when it reaches write-back.
+    fn trap_in_flight(&self) -> bool {
+        self.id_ex.entries.iter().any(|e| e.trap.is_some())
+            || self.ex_mem.entries.iter().any(|e| e.trap.is_some())
+            || self.mem_wb.entries.iter().any(|e| e.trap.is_some())
+    }
+
+    /// The mode breakpoint triggers match in, or `None` while they are held off:
+    /// in M-mode while mstatus.MIE is clear, and in S-mode while sstatus.SIE is
+    /// clear if breakpoints are delegated there, so handlers cannot retrigger.
+    pub(crate) fn trigger_mode(&self) -> Option<(u8, bool)> {
+        let held_off = match (self.privilege, self.virt) {
+            (3, _) => self.csrs.mstatus & csr::MSTATUS_MIE == 0,
+            (1, false) => {
+                self.csrs.medeleg & (1 << 3) != 0 && self.csrs.mstatus & csr::MSTATUS_SIE == 0
+            }
+            _ => false,
+        };
+        (!held_off).then_some((self.privilege, self.virt))
+    }
+
+    /// The mode ordinary loads, stores and fetches are performed in.
+    pub fn access_context(&self) -> AccessContext {
+        // With mstatus.MPRV set, M-mode loads and stores use the mode in MPP and MPV
+        let mstatus = self.csrs.mstatus;
+        if self.privilege == 3 && mstatus & csr::MSTATUS_MPRV != 0 {
+            let mpp = ((mstatus & csr::MSTATUS_MPP) >> csr::MSTATUS_MPP_SHIFT) as u8;
+            return AccessContext {
+                privilege: mpp,
+                virt: mpp != 3 && mstatus & csr::MSTATUS_MPV != 0,
+                hlvx: false,
+            };
+        }
+        AccessContext {
+            privilege: self.privilege,
+            virt: self.virt,
+            hlvx: false,
+        }
+    }
+
+    /// Translates an access of `size` bytes and checks it against the bus map and PMP.
+    pub fn translate(
+        &mut self,
+        vaddr: VirtAddr,
+        access: AccessType,
+        size: u64,
+    ) -> TranslationResult {
+        // Instruction fetch always runs in the current mode
+        let ctx = match access {
+            AccessType::Fetch => AccessContext {
+                privilege: self.privilege,
+                virt: self.virt,
+                hlvx: false,
+            },
+            _ => self.access_context(),
+        };
+        self.translate_in(vaddr, access, size, ctx)
+    }
+
+    /// Like `translate`, for an access made on behalf of another mode (HLV/HSV).
+    pub fn translate_in(
+        &mut self,
+        vaddr: VirtAddr,
+        access: AccessType,
+        size: u64,
+        ctx: AccessContext,
+    ) -> TranslationResult {
         if self.direct_mode {
             let paddr = vaddr.val();
             if !self.bus.bus.is_valid_address(paddr) {
@@ -293,19 +515,25 @@ impl Cpu {
             return TranslationResult::success(PhysAddr::new(paddr), 0);
         }
 
-        // Standard MMU Translation (SV39)
+        // Standard MMU Translation (Sv39/Sv48/Sv57, nested under Sv39x4 for guests)
         let res = self
             .mmu
-            .translate(vaddr, access, self.privilege, &self.csrs, &mut self.bus.bus);
+            .translate(vaddr, access, ctx, &self.csrs, &mut self.bus.bus);
+
+        if res.tlb_miss {
+            match access {
+                AccessType::Fetch => self.stats.itlb_misses += 1,
+                _ => self.stats.dtlb_misses += 1,
+            }
+        }
 
         if res.trap.is_none() {
+            // Access faults report the virtual address, like page faults
             let paddr = res.paddr.val();
-            if !self.bus.bus.is_valid_address(paddr) {
-                let trap = match access {
-                    AccessType::Fetch => Trap::InstructionAccessFault(paddr),
-                    AccessType::Read => Trap::LoadAccessFault(paddr),
-                    AccessType::Write => Trap::StoreAccessFault(paddr),
-                };
+            if !self.bus.bus.is_valid_address(paddr)
+                || !self.csrs.pmp.check(paddr, size, access, ctx.privilege)
+            {
+                let trap = mmu::access_fault(access, vaddr.val());
                 return TranslationResult::fault(trap, res.cycles);
             }
         }
@@ -381,6 +609,42 @@ impl Cpu {
         total_penalty
     }
 
+    /// Applies a cache-block operation or software prefetch to the cache
+    /// hierarchy and returns the stall it causes. `prefetch.i` targets L1-I,
+    /// everything else the data side.
+    pub fn simulate_cache_op(&mut self, addr: PhysAddr, op: CacheOp) -> u64 {
+        let raw_addr = addr.val();
+        let wb_latency = self.bus.mem_controller.access_latency(raw_addr);
+
+        let l1 = if op == CacheOp::PrefetchI {
+            &mut self.l1_i_cache
+        } else {
+            &mut self.l1_d_cache
+        };
+
+        let mut total_penalty = 0;
+        for cache in [l1, &mut self.l2_cache, &mut self.l3_cache] {
+            if !cache.enabled {
+                continue;
+            }
+            match op {
+                CacheOp::Inval => cache.invalidate_line(raw_addr),
+                CacheOp::Clean => total_penalty += cache.clean_line(raw_addr, wb_latency),
+                CacheOp::Flush => total_penalty += cache.flush_line(raw_addr, wb_latency),
+                CacheOp::Zero => {
+                    // The zeroed block lives only in the closest enabled level
+                    total_penalty += cache.zero_line(raw_addr, wb_latency);
+                    break;
+                }
+                CacheOp::PrefetchI | CacheOp::PrefetchR | CacheOp::PrefetchW => {
+                    cache.prefetch_line(raw_addr, wb_latency)
+                }
+                CacheOp::None => {}
+            }
+        }
+        total_penalty
+    }
+
     pub fn trap(&mut self, cause: Trap, epc: u64) {
         let code = match cause {
             Trap::InstructionAddressMisaligned(_) => 0,
@@ -393,12 +657,26 @@ impl Cpu {
             Trap::StoreAccessFault(_) => 7,
             Trap::EnvironmentCallFromUMode => 8,
             Trap::EnvironmentCallFromSMode => 9,
+            Trap::EnvironmentCallFromVSMode => 10,
             Trap::EnvironmentCallFromMMode => 11,
             Trap::InstructionPageFault(_) => 12,
             Trap::LoadPageFault(_) => 13,
             Trap::StorePageFault(_) => 15,
-            Trap::MachineTimerInterrupt => 0x8000_0000_0000_0007,
-            _ => 0,
+            Trap::InstructionGuestPageFault(..) => 20,
+            Trap::LoadGuestPageFault(..) => 21,
+            Trap::VirtualInstruction(_) => 22,
+            Trap::StoreGuestPageFault(..) => 23,
+            Trap::SupervisorSoftwareInterrupt => csr::CAUSE_INTERRUPT | 1,
+            Trap::VirtualSupervisorSoftwareInterrupt => csr::CAUSE_INTERRUPT | 2,
+            Trap::MachineSoftwareInterrupt => csr::CAUSE_INTERRUPT | 3,
+            Trap::SupervisorTimerInterrupt => csr::CAUSE_INTERRUPT | 5,
+            Trap::VirtualSupervisorTimerInterrupt => csr::CAUSE_INTERRUPT | 6,
+            Trap::MachineTimerInterrupt => csr::CAUSE_INTERRUPT | 7,
+            Trap::SupervisorExternalInterrupt => csr::CAUSE_INTERRUPT | 9,
+            Trap::VirtualSupervisorExternalInterrupt => csr::CAUSE_INTERRUPT | 10,
+            Trap::MachineExternalInterrupt => csr::CAUSE_INTERRUPT | 11,
+            Trap::LocalCounterOverflowInterrupt => csr::CAUSE_INTERRUPT | 13,
+            Trap::RequestedTrap(_) => 0,
         };
 
         if self.direct_mode {
@@ -442,27 +720,152 @@ impl Cpu {
         }
 
         self.stats.traps_taken += 1;
-        self.csrs.sepc = epc;
-        self.csrs.scause = code;
+        self.wfi = false;
+
+        // Faulting address or instruction bits, unless the core is configured to report zero
+        let tval = match cause {
+            _ if !self.report_tval => 0,
+            Trap::InstructionAddressMisaligned(addr)
+            | Trap::InstructionAccessFault(addr)
+            | Trap::Breakpoint(addr)
+            | Trap::LoadAddressMisaligned(addr)
+            | Trap::LoadAccessFault(addr)
+            | Trap::StoreAddressMisaligned(addr)
+            | Trap::StoreAccessFault(addr)
+            | Trap::InstructionPageFault(addr)
+            | Trap::LoadPageFault(addr)
+            | Trap::StorePageFault(addr)
+            | Trap::InstructionGuestPageFault(addr, _)
+            | Trap::LoadGuestPageFault(addr, _)
+            | Trap::StoreGuestPageFault(addr, _) => addr,
+            Trap::IllegalInstruction(inst) | Trap::VirtualInstruction(inst) => inst as u64,
+            _ => 0,
+        };
 
-        let mut sstatus = self.csrs.sstatus;
-        if self.privilege == 0 {
-            sstatus &= !csr::MSTATUS_SPP;
+        // Guest-page faults also report the guest physical address, shifted right by 2
+        let tval2 = match cause {
+            _ if !self.report_tval => 0,
+            Trap::InstructionGuestPageFault(_, gpa)
+            | Trap::LoadGuestPageFault(_, gpa)
+            | Trap::StoreGuestPageFault(_, gpa) => gpa >> 2,
+            _ => 0,
+        };
+
+        // Whether tval holds a guest virtual address; only address-reporting
+        // exceptions taken from a guest write one
+        let gva = self.report_tval
+            && match cause {
+                Trap::InstructionGuestPageFault(..)
+                | Trap::LoadGuestPageFault(..)
+                | Trap::StoreGuestPageFault(..) => true,
+                Trap::InstructionAddressMisaligned(_)
+                | Trap::InstructionAccessFault(_)
+                | Trap::Breakpoint(_)
+                | Trap::LoadAddressMisaligned(_)
+                | Trap::LoadAccessFault(_)
+                | Trap::StoreAddressMisaligned(_)
+                | Trap::StoreAccessFault(_)
+                | Trap::InstructionPageFault(_)
+                | Trap::LoadPageFault(_)
+                | Trap::StorePageFault(_) => self.virt,
+                _ => false,
+            };
+
+        // Traps from U/S-mode go to S-mode when delegated; M-mode traps never drop privilege.
+        // From V=1, hedeleg/hideleg pass S-mode traps on to VS-mode.
+        let is_interrupt = code & csr::CAUSE_INTERRUPT != 0;
+        let cause_bit = 1 << (code & !csr::CAUSE_INTERRUPT);
+        let (deleg, hdeleg) = if is_interrupt {
+            (self.csrs.mideleg, self.csrs.hideleg)
         } else {
-            sstatus |= csr::MSTATUS_SPP;
-        }
-        let sie = (sstatus & csr::MSTATUS_MIE) != 0;
-        if sie {
-            sstatus |= csr::MSTATUS_MPIE;
+            (self.csrs.medeleg, self.csrs.hedeleg)
+        };
+        let to_s = self.privilege <= 1 && deleg & cause_bit != 0;
+        let to_vs = to_s && self.virt && hdeleg & cause_bit != 0;
+
+        let (tvec, code) = if to_vs {
+            // VS-mode sees its interrupts under the S-level cause numbers
+            let code = if is_interrupt { code - 1 } else { code };
+            self.csrs.vsepc = epc;
+            self.csrs.vscause = code;
+            self.csrs.vstval = tval;
+
+            let mut vsstatus = self.csrs.vsstatus & !(csr::MSTATUS_SPP | csr::MSTATUS_SPIE);
+            if self.privilege == 1 {
+                vsstatus |= csr::MSTATUS_SPP;
+            }
+            if vsstatus & csr::MSTATUS_SIE != 0 {
+                vsstatus |= csr::MSTATUS_SPIE;
+            }
+            self.csrs.vsstatus = vsstatus & !csr::MSTATUS_SIE;
+
+            self.privilege = 1;
+            (self.csrs.vstvec, code)
+        } else if to_s {
+            self.csrs.sepc = epc;
+            self.csrs.scause = code;
+            self.csrs.stval = tval;
+            self.csrs.htval = tval2;
+            self.csrs.htinst = 0;
+
+            // SPVP is only updated when leaving a virtualized mode
+            let mut hstatus = self.csrs.hstatus & !(csr::HSTATUS_SPV | csr::HSTATUS_GVA);
+            if self.virt {
+                hstatus = (hstatus & !csr::HSTATUS_SPVP) | csr::HSTATUS_SPV;
+                if self.privilege == 1 {
+                    hstatus |= csr::HSTATUS_SPVP;
+                }
+            }
+            if gva {
+                hstatus |= csr::HSTATUS_GVA;
+            }
+            self.csrs.hstatus = hstatus;
+
+            let mut mstatus = self.csrs.mstatus & !(csr::MSTATUS_SPP | csr::MSTATUS_SPIE);
+            if self.privilege == 1 {
+                mstatus |= csr::MSTATUS_SPP;
+            }
+            if mstatus & csr::MSTATUS_SIE != 0 {
+                mstatus |= csr::MSTATUS_SPIE;
+            }
+            self.csrs.mstatus = mstatus & !csr::MSTATUS_SIE;
+
+            self.privilege = 1;
+            self.virt = false;
+            (self.csrs.stvec, code)
         } else {
-            sstatus &= !csr::MSTATUS_MPIE;
-        }
-        sstatus &= !csr::MSTATUS_MIE;
-        self.csrs.sstatus = sstatus;
+            self.csrs.mepc = epc;
+            self.csrs.mcause = code;
+            self.csrs.mtval = tval;
+            self.csrs.mtval2 = tval2;
+            self.csrs.mtinst = 0;
+
+            let mut mstatus = self.csrs.mstatus
+                & !(csr::MSTATUS_MPP | csr::MSTATUS_MPIE | csr::MSTATUS_MPV | csr::MSTATUS_GVA);
+            mstatus |= (self.privilege as u64) << csr::MSTATUS_MPP_SHIFT;
+            if mstatus & csr::MSTATUS_MIE != 0 {
+                mstatus |= csr::MSTATUS_MPIE;
+            }
+            if self.virt {
+                mstatus |= csr::MSTATUS_MPV;
+            }
+            if gva {
+                mstatus |= csr::MSTATUS_GVA;
+            }
+            self.csrs.mstatus = mstatus & !csr::MSTATUS_MIE;
 
-        let vector = self.csrs.stvec & !3;
-        self.pc = vector;
-        self.privilege = 1;
+            self.privilege = 3;
+            self.virt = false;
+            (self.csrs.mtvec, code)
+        };
+
+        // Vectored mode sends interrupts to BASE + 4 * cause
+        let base = tvec & !3;
+        self.pc = if is_interrupt && tvec & 3 == csr::TVEC_MODE_VECTORED {
+            base + 4 * (code & !csr::CAUSE_INTERRUPT)
+        } else {
+            base
+        };
 
         self.if_id = Default::default();
         self.id_ex = IdEx::default();
@@ -477,16 +880,94 @@ impl Cpu {
         self.regs.dump();
     }
 
-    fn track_mode_cycles(&mut self) {
-        match self.privilege {
-            0 => self.stats.cycles_user += 1,
-            1 => self.stats.cycles_kernel += 1,
-            3 => self.stats.cycles_machine += 1,
+    pub(crate) fn track_mode_cycles(&mut self, cycles: u64) {
+        match (self.privilege, self.virt) {
+            (0, true) => self.stats.cycles_vu += cycles,
+            (1, true) => self.stats.cycles_vs += cycles,
+            (0, _) => self.stats.cycles_user += cycles,
+            (1, _) => self.stats.cycles_kernel += cycles,
+            (3, _) => self.stats.cycles_machine += cycles,
             _ => {}
         }
     }
 
+    /// True once every instruction older than a WFI has left the pipeline.
+    fn is_drained(&self) -> bool {
+        self.if_id.entries.is_empty()
+            && self.id_ex.entries.is_empty()
+            && self.ex_mem.entries.is_empty()
+            && self.mem_wb.entries.is_empty()
+            && self.wb_latch.entries.is_empty()
+            && self.stall_cycles == 0
+            && self.alu_timer == 0
+            && self.ooo.as_ref().is_none_or(OooCore::is_empty)
+    }
+
+    /// Spends a cycle asleep in WFI. Nothing but a device can wake the hart, so
+    /// the devices are fast-forwarded to their next interrupt event in one step.
+    fn idle(&mut self) {
+        // This cycle's bus tick has already happened; the final one is left to
+        // the next call so that the wake-up goes through the usual interrupt check
+        let skipped = self
+            .next_timer_event()
+            .map_or(0, |ticks| ticks.saturating_sub(1));
+        self.bus.fast_forward(skipped);
+
+        let cycles = 1 + skipped;
+        self.stats.cycles += cycles;
+        self.stats.cycles_idle += cycles;
+        self.track_mode_cycles(cycles);
+    }
+
+    /// Returns the interrupt-pending bits that Sstc hands to the timer
+    /// comparators: STIP once `menvcfg.STCE` is set, VSTIP once `henvcfg.STCE` is too.
+    fn sstc_mask(&self) -> u64 {
+        if self.csrs.menvcfg & csr::ENVCFG_STCE == 0 {
+            return 0;
+        }
+        if self.csrs.henvcfg & csr::ENVCFG_STCE == 0 {
+            return csr::MIP_STIP;
+        }
+        csr::MIP_STIP | csr::MIP_VSTIP
+    }
+
+    /// Compares `time` against `stimecmp` and the guest's view of it against `vstimecmp`.
+    fn sstc_lines(&self) -> u64 {
+        let time = self.bus.time();
+        let mut lines = 0;
+        if time >= self.csrs.stimecmp {
+            lines |= csr::MIP_STIP;
+        }
+        if time.wrapping_add(self.csrs.htimedelta) >= self.csrs.vstimecmp {
+            lines |= csr::MIP_VSTIP;
+        }
+        lines
+    }
+
+    /// Ticks until the next device interrupt or Sstc comparator match, if any.
+    fn next_timer_event(&self) -> Option<u64> {
+        let sstc_mask = self.sstc_mask();
+        let stimer = (sstc_mask & csr::MIP_STIP != 0)
+            .then(|| self.bus.ticks_until(self.csrs.stimecmp))
+            .flatten();
+        let vstimer = (sstc_mask & csr::MIP_VSTIP != 0)
+            .then(|| {
+                let target = self.csrs.vstimecmp.wrapping_sub(self.csrs.htimedelta);
+                self.bus.ticks_until(target)
+            })
+            .flatten();
+        [self.bus.next_event(), stimer, vstimer]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
     pub fn print_pipeline_diagram(&self) {
+        if let Some(ooo) = &self.ooo {
+            eprint!("IF:{} -> ", self.if_id.entries.len());
+            ooo.print_occupancy();
+            return;
+        }
         eprintln!(
             "IF:{} -> ID:{} -> EX:{} -> MEM:{} -> WB:{}",
             self.if_id.entries.len(),
@@ -497,104 +978,583 @@ impl Cpu {
         );
     }
 
-    pub(crate) fn csr_read(&self, addr: u32) -> u64 {
+    /// Checks privilege, read-only and enable rules for a CSR access.
+    /// `write` is false only for CSRRS/CSRRC with a zero source.
+    pub(crate) fn csr_accessible(&self, addr: u32, write: bool) -> bool {
+        self.csr_permitted(addr, write, self.privilege, self.virt) && self.csr_enabled(addr)
+    }
+
+    /// True when an access denied at V=1 would have been allowed in HS-mode,
+    /// which makes it a virtual-instruction rather than an illegal-instruction fault.
+    pub(crate) fn csr_virtual_fault(&self, addr: u32, write: bool) -> bool {
+        self.virt
+            && self.csr_enabled(addr)
+            && self.csr_permitted(addr, write, 1, false)
+            && self.csr_read(addr).is_some()
+    }
+
+    fn csr_permitted(&self, addr: u32, write: bool, privilege: u8, virt: bool) -> bool {
+        // addr[9:8] is the lowest privilege allowed, addr[11:10] == 0b11 marks read-only.
+        // Hypervisor and VS CSRs (level 2) belong to HS-mode.
+        let min_privilege = match (addr >> 8) & 0x3 {
+            2 if virt => return false,
+            2 => 1,
+            level => level as u8,
+        };
+        let read_only = (addr >> 10) & 0x3 == 0x3;
+        if privilege < min_privilege || (write && read_only) {
+            return false;
+        }
+
         match addr {
+            // Below M-mode the counters need mcounteren, guests also hcounteren,
+            // and U-mode also scounteren
+            csr::CYCLE..=csr::HPMCOUNTER31 => {
+                let bit = 1 << (addr - csr::CYCLE);
+                (privilege == 3 || self.csrs.mcounteren & bit != 0)
+                    && (!virt || self.csrs.hcounteren & bit != 0)
+                    && (privilege != 0 || self.csrs.scounteren & bit != 0)
+            }
+            // TVM traps satp and hgatp in HS-mode; VTVM traps satp in VS-mode
+            csr::SATP if virt => self.csrs.hstatus & csr::HSTATUS_VTVM == 0,
+            csr::SATP | csr::HGATP => privilege == 3 || self.csrs.mstatus & csr::MSTATUS_TVM == 0,
+            // Sstc: below M-mode the timer compares need menvcfg.STCE and
+            // mcounteren.TM, and guests also henvcfg.STCE and hcounteren.TM
+            csr::STIMECMP | csr::VSTIMECMP => {
+                let stce = |envcfg: u64| envcfg & csr::ENVCFG_STCE != 0;
+                let tm = |counteren: u64| counteren & csr::COUNTEREN_TM != 0;
+                privilege == 3
+                    || (stce(self.csrs.menvcfg)
+                        && tm(self.csrs.mcounteren)
+                        && (!virt || (stce(self.csrs.henvcfg) && tm(self.csrs.hcounteren))))
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether the FP or vector state `field` (FS or VS) is not Off, in
+    /// vsstatus as well while V=1.
+    pub(crate) fn state_enabled(&self, field: u64) -> bool {
+        self.csrs.mstatus & field != 0 && (!self.virt || self.csrs.vsstatus & field != 0)
+    }
+
+    /// FP and vector CSRs need their state enabled.
+    fn csr_enabled(&self, addr: u32) -> bool {
+        let enabled = |field: u64| self.state_enabled(field);
+        match addr {
+            csr::FFLAGS | csr::FRM | csr::FCSR => enabled(csr::MSTATUS_FS),
+            csr::VSTART
+            | csr::VXSAT
+            | csr::VXRM
+            | csr::VCSR
+            | csr::VL
+            | csr::VTYPE
+            | csr::VLENB => enabled(csr::MSTATUS_VS),
+            _ => true,
+        }
+    }
+
+    /// While V=1 the supervisor CSR numbers name their VS counterparts.
+    fn csr_redirect(&self, addr: u32) -> u32 {
+        match addr {
+            csr::SSTATUS
+            | csr::SIE
+            | csr::STVEC
+            | csr::SSCRATCH
+            | csr::SEPC
+            | csr::SCAUSE
+            | csr::STVAL
+            | csr::SIP
+            | csr::SATP
+            | csr::STIMECMP
+                if self.virt =>
+            {
+                addr + (csr::VSSTATUS - csr::SSTATUS)
+            }
+            _ => addr,
+        }
+    }
+
+    /// Returns `None` for CSRs this hart does not implement.
+    pub(crate) fn csr_read(&self, addr: u32) -> Option<u64> {
+        // hie/hip/hvip hold the VS-level bits of mie/mip; vsie/vsip show the
+        // delegated ones at the S-level positions
+        let vs_mask = self.csrs.hideleg & csr::MIP_VS_MASK;
+        let s_mask = self.csrs.mideleg & !csr::MIP_VS_MASK;
+
+        let val = match self.csr_redirect(addr) {
             csr::MVENDORID => 0,
             csr::MARCHID => 0,
             csr::MIMPID => 0,
             csr::MHARTID => 0,
 
             csr::MSTATUS => self.csrs.mstatus,
-            csr::MEDELEG => 0,
-            csr::MIDELEG => 0,
-            csr::MIE => 0,
+            csr::MEDELEG => self.csrs.medeleg,
+            csr::MIDELEG => self.csrs.mideleg,
+            csr::MIE => self.csrs.mie,
             csr::MTVEC => self.csrs.mtvec,
-            csr::MCOUNTEREN => 0,
+            csr::MCOUNTEREN => self.csrs.mcounteren,
             csr::MISA => self.csrs.misa,
 
             csr::MSCRATCH => self.csrs.mscratch,
             csr::MEPC => self.csrs.mepc,
             csr::MCAUSE => self.csrs.mcause,
             csr::MTVAL => self.csrs.mtval,
-            csr::MIP => 0,
+            csr::MIP => self.csrs.mip,
+            csr::MTINST => self.csrs.mtinst,
+            csr::MTVAL2 => self.csrs.mtval2,
+            csr::MENVCFG => self.csrs.menvcfg,
 
-            csr::SSTATUS => self.csrs.sstatus,
-            csr::SIE => 0,
+            csr::SSTATUS => self.csrs.mstatus & csr::SSTATUS_MASK,
+            csr::SIE => self.csrs.mie & s_mask,
             csr::STVEC => self.csrs.stvec,
-            csr::SCOUNTEREN => 0,
+            csr::SCOUNTEREN => self.csrs.scounteren,
+            csr::SENVCFG => self.csrs.senvcfg,
 
             csr::SSCRATCH => self.csrs.sscratch,
             csr::SEPC => self.csrs.sepc,
             csr::SCAUSE => self.csrs.scause,
             csr::STVAL => self.csrs.stval,
-            csr::SIP => 0,
+            csr::SIP => self.csrs.mip & s_mask,
+            csr::STIMECMP => self.csrs.stimecmp,
 
             csr::SATP => self.csrs.satp,
 
-            csr::CYCLE | csr::MCYCLE | csr::TIME => self.stats.cycles,
-            csr::INSTRET | csr::MINSTRET => self.stats.instructions_retired,
+            csr::HSTATUS => self.csrs.hstatus,
+            csr::HEDELEG => self.csrs.hedeleg,
+            csr::HIDELEG => self.csrs.hideleg,
+            csr::HIE => self.csrs.mie & csr::MIP_VS_MASK,
+            csr::HCOUNTEREN => self.csrs.hcounteren,
+            csr::HTVAL => self.csrs.htval,
+            csr::HIP => self.csrs.mip & csr::MIP_VS_MASK,
+            csr::HVIP => (self.csrs.mip & (csr::MIP_VSSIP | csr::MIP_VSEIP)) | self.csrs.hvip,
+            csr::HTINST => self.csrs.htinst,
+            csr::HGATP => self.csrs.hgatp,
+            // henvcfg.STCE is read-only zero unless menvcfg.STCE is set
+            csr::HENVCFG => self.csrs.henvcfg & (self.csrs.menvcfg | !csr::ENVCFG_STCE),
+            csr::HTIMEDELTA => self.csrs.htimedelta,
+            // No guest external interrupt lines (GEILEN = 0)
+            csr::HGEIE | csr::HGEIP => 0,
+
+            csr::VSSTATUS => self.csrs.vsstatus & csr::SSTATUS_MASK,
+            csr::VSIE => (self.csrs.mie & vs_mask) >> 1,
+            csr::VSTVEC => self.csrs.vstvec,
+            csr::VSSCRATCH => self.csrs.vsscratch,
+            csr::VSEPC => self.csrs.vsepc,
+            csr::VSCAUSE => self.csrs.vscause,
+            csr::VSTVAL => self.csrs.vstval,
+            csr::VSIP => (self.csrs.mip & vs_mask) >> 1,
+            csr::VSATP => self.csrs.vsatp,
+            csr::VSTIMECMP => self.csrs.vstimecmp,
+
+            // RV64 has only the even-numbered pmpcfg registers
+            csr::PMPCFG0..=csr::PMPCFG15 if addr.is_multiple_of(2) => {
+                self.csrs.pmp.read_cfg((addr - csr::PMPCFG0) as usize)
+            }
+            csr::PMPADDR0..=csr::PMPADDR63 => {
+                self.csrs.pmp.read_addr((addr - csr::PMPADDR0) as usize)
+            }
 
-            _ => 0,
-        }
+            csr::FFLAGS => self.csrs.fflags,
+            csr::FRM => self.csrs.frm,
+            csr::FCSR => (self.csrs.frm << csr::FRM_SHIFT) | self.csrs.fflags,
+
+            csr::VSTART => self.csrs.vstart,
+            csr::VXSAT => self.csrs.vxsat,
+            csr::VXRM => self.csrs.vxrm,
+            csr::VCSR => (self.csrs.vxrm << csr::VXRM_SHIFT) | self.csrs.vxsat,
+            csr::VL => self.csrs.vl,
+            csr::VTYPE => self.csrs.vtype,
+            csr::VLENB => self.vregs.vlenb() as u64,
+
+            // time reads the CLINT's mtime; guests see it offset by htimedelta
+            csr::TIME if self.virt => self.bus.time().wrapping_add(self.csrs.htimedelta),
+            csr::TIME => self.bus.time(),
+            csr::CYCLE
+            | csr::INSTRET
+            | csr::HPMCOUNTER3..=csr::HPMCOUNTER31
+            | csr::MCYCLE
+            | csr::MINSTRET
+            | csr::MHPMCOUNTER3..=csr::MHPMCOUNTER31 => {
+                self.csrs.hpm.read_counter((addr & 0x1F) as usize)
+            }
+            csr::MHPMEVENT3..=csr::MHPMEVENT31 => self.csrs.hpm.read_event((addr & 0x1F) as usize),
+            csr::MCOUNTINHIBIT => self.csrs.hpm.read_inhibit(),
+
+            csr::TSELECT => self.csrs.triggers.read_select(),
+            csr::TDATA1 => self.csrs.triggers.read_tdata1(),
+            csr::TDATA2 => self.csrs.triggers.read_tdata2(),
+            csr::TDATA3 => 0,
+            csr::TINFO => self.csrs.triggers.read_tinfo(),
+            // scountovf shows the overflow flags of the counters S-mode (or the guest) may read
+            csr::SCOUNTOVF => {
+                let mut visible = if self.privilege == 3 {
+                    u64::MAX
+                } else {
+                    self.csrs.mcounteren
+                };
+                if self.virt {
+                    visible &= self.csrs.hcounteren;
+                }
+                self.csrs.hpm.overflow() & visible
+            }
+
+            csr::CSR_SIM_PANIC => 0,
+
+            _ => return None,
+        };
+        Some(val)
     }
 
     pub(crate) fn csr_write(&mut self, addr: u32, val: u64) {
-        match addr {
+        match self.csr_redirect(addr) {
             csr::CSR_SIM_PANIC => {
                 self.trap(Trap::RequestedTrap(val), self.pc);
             }
 
-            csr::MSTATUS => self.csrs.mstatus = val,
-            csr::MEDELEG => {}
-            csr::MIDELEG => {}
-            csr::MIE => {}
-            csr::MTVEC => self.csrs.mtvec = val,
-            csr::MCOUNTEREN => {}
+            // MPP is WARL: the reserved privilege level 2 is stored as U
+            csr::MSTATUS => {
+                let mut status = Self::write_status(self.csrs.mstatus, val, csr::MSTATUS_WRITABLE);
+                if status & csr::MSTATUS_MPP == 2 << csr::MSTATUS_MPP_SHIFT {
+                    status &= !csr::MSTATUS_MPP;
+                }
+                self.csrs.mstatus = status;
+            }
+            csr::MEDELEG => self.csrs.medeleg = val & csr::MEDELEG_MASK,
+            csr::MIDELEG => self.csrs.mideleg = (val & csr::MIDELEG_MASK) | csr::MIP_VS_MASK,
+            csr::MIE => self.csrs.mie = val & csr::MIE_WRITABLE,
+            csr::MTVEC => self.csrs.mtvec = val & !csr::TVEC_MODE_RESERVED,
+            csr::MCOUNTEREN => self.csrs.mcounteren = val & csr::COUNTEREN_MASK,
             csr::MISA => self.csrs.misa = val,
 
             csr::MSCRATCH => self.csrs.mscratch = val,
             csr::MEPC => self.csrs.mepc = val & !1,
             csr::MCAUSE => self.csrs.mcause = val,
             csr::MTVAL => self.csrs.mtval = val,
-            csr::MIP => {}
+            csr::MTINST => self.csrs.mtinst = val,
+            csr::MTVAL2 => self.csrs.mtval2 = val,
+            csr::MCYCLE | csr::MINSTRET | csr::MHPMCOUNTER3..=csr::MHPMCOUNTER31 => {
+                self.csrs.hpm.write_counter((addr & 0x1F) as usize, val)
+            }
+            csr::MHPMEVENT3..=csr::MHPMEVENT31 => {
+                let idx = (addr & 0x1F) as usize;
+                self.csrs.hpm.write_event(idx, val, &self.stats);
+            }
+            csr::MCOUNTINHIBIT => self.csrs.hpm.write_inhibit(val),
+
+            csr::TSELECT => self.csrs.triggers.write_select(val),
+            csr::TDATA1 => self.csrs.triggers.write_tdata1(val),
+            csr::TDATA2 => self.csrs.triggers.write_tdata2(val),
+            // No trigger uses tdata3, and tinfo is read-only
+            csr::TDATA3 | csr::TINFO => {}
+            // Bits driven by the Sstc comparators ignore writes
+            csr::MIP => {
+                let mask = csr::MIP_WRITABLE & !self.sstc_mask();
+                self.csrs.mip = (self.csrs.mip & !mask) | (val & mask);
+            }
+            csr::MENVCFG => {
+                self.csrs.menvcfg = Self::legal_envcfg(val, csr::ENVCFG_STCE | csr::ENVCFG_CBO)
+            }
 
-            csr::SSTATUS => self.csrs.sstatus = val,
-            csr::SIE => {}
-            csr::STVEC => self.csrs.stvec = val,
-            csr::SCOUNTEREN => {}
+            csr::SSTATUS => {
+                self.csrs.mstatus =
+                    Self::write_status(self.csrs.mstatus, val, csr::SSTATUS_WRITABLE)
+            }
+            // sie/sip expose only the delegated interrupts; SSIP and LCOFIP are the
+            // S-writable pending bits
+            csr::SIE => {
+                let mask = self.csrs.mideleg & !csr::MIP_VS_MASK;
+                self.csrs.mie = (self.csrs.mie & !mask) | (val & mask);
+            }
+            csr::STVEC => self.csrs.stvec = val & !csr::TVEC_MODE_RESERVED,
+            csr::SCOUNTEREN => self.csrs.scounteren = val & csr::COUNTEREN_MASK,
+            csr::SENVCFG => self.csrs.senvcfg = Self::legal_envcfg(val, csr::ENVCFG_CBO),
 
             csr::SSCRATCH => self.csrs.sscratch = val,
             csr::SEPC => self.csrs.sepc = val & !1,
             csr::SCAUSE => self.csrs.scause = val,
             csr::STVAL => self.csrs.stval = val,
-            csr::SIP => {}
+            csr::SIP => {
+                let mask = self.csrs.mideleg & (csr::MIP_SSIP | csr::MIP_LCOFIP);
+                self.csrs.mip = (self.csrs.mip & !mask) | (val & mask);
+            }
+            csr::STIMECMP => self.csrs.stimecmp = val,
+
+            // Writes selecting an unsupported translation mode have no effect
+            csr::SATP | csr::VSATP => {
+                let mode = val >> csr::SATP_MODE_SHIFT;
+                if matches!(
+                    mode,
+                    csr::SATP_MODE_BARE
+                        | csr::SATP_MODE_SV39
+                        | csr::SATP_MODE_SV48
+                        | csr::SATP_MODE_SV57
+                ) {
+                    if addr == csr::SATP && !self.virt {
+                        self.csrs.satp = val;
+                    } else {
+                        self.csrs.vsatp = val;
+                    }
+                }
+            }
+
+            csr::HSTATUS => {
+                self.csrs.hstatus =
+                    (self.csrs.hstatus & !csr::HSTATUS_WRITABLE) | (val & csr::HSTATUS_WRITABLE)
+            }
+            csr::HEDELEG => self.csrs.hedeleg = val & csr::HEDELEG_MASK,
+            csr::HIDELEG => self.csrs.hideleg = val & csr::HIDELEG_MASK,
+            csr::HIE => {
+                let mask = csr::MIP_VS_MASK;
+                self.csrs.mie = (self.csrs.mie & !mask) | (val & mask);
+            }
+            csr::HCOUNTEREN => self.csrs.hcounteren = val & csr::COUNTEREN_MASK,
+            csr::HTVAL => self.csrs.htval = val,
+            // hip.VSSIP is an alias of hvip.VSSIP
+            csr::HIP => {
+                let mask = csr::MIP_VSSIP;
+                self.csrs.mip = (self.csrs.mip & !mask) | (val & mask);
+            }
+            // hvip injects all three; hip.VSTIP is hvip.VSTIP ORed with the
+            // vstimecmp comparator
+            csr::HVIP => {
+                let mask = csr::MIP_VSSIP | csr::MIP_VSEIP;
+                self.csrs.hvip = val & csr::MIP_VSTIP;
+                let timer = self.sstc_lines() & self.sstc_mask() & csr::MIP_VSTIP;
+                self.csrs.mip =
+                    (self.csrs.mip & !csr::MIP_VS_MASK) | (val & mask) | self.csrs.hvip | timer;
+            }
+            csr::HTINST => self.csrs.htinst = val,
+            csr::HENVCFG => {
+                self.csrs.henvcfg = Self::legal_envcfg(val, csr::ENVCFG_STCE | csr::ENVCFG_CBO)
+            }
+            csr::HTIMEDELTA => self.csrs.htimedelta = val,
+            // VMIDs are not implemented; the root table of Sv39x4 is 16 KiB aligned
+            csr::HGATP => {
+                let mode = val >> csr::SATP_MODE_SHIFT;
+                if matches!(mode, csr::SATP_MODE_BARE | csr::HGATP_MODE_SV39X4) {
+                    self.csrs.hgatp = (mode << csr::SATP_MODE_SHIFT) | (val & csr::HGATP_PPN_MASK);
+                }
+            }
+
+            csr::VSSTATUS => {
+                self.csrs.vsstatus =
+                    Self::write_status(self.csrs.vsstatus, val, csr::SSTATUS_WRITABLE)
+            }
+            csr::VSIE => {
+                let mask = self.csrs.hideleg & csr::MIP_VS_MASK;
+                self.csrs.mie = (self.csrs.mie & !mask) | ((val << 1) & mask);
+            }
+            csr::VSTVEC => self.csrs.vstvec = val & !csr::TVEC_MODE_RESERVED,
+            csr::VSSCRATCH => self.csrs.vsscratch = val,
+            csr::VSEPC => self.csrs.vsepc = val & !1,
+            csr::VSCAUSE => self.csrs.vscause = val,
+            csr::VSTVAL => self.csrs.vstval = val,
+            csr::VSIP => {
+                let mask = self.csrs.hideleg & csr::MIP_VSSIP;
+                self.csrs.mip = (self.csrs.mip & !mask) | ((val << 1) & mask);
+            }
+            csr::VSTIMECMP => self.csrs.vstimecmp = val,
+
+            csr::PMPCFG0..=csr::PMPCFG15 => {
+                self.csrs.pmp.write_cfg((addr - csr::PMPCFG0) as usize, val)
+            }
+            csr::PMPADDR0..=csr::PMPADDR63 => self
+                .csrs
+                .pmp
+                .write_addr((addr - csr::PMPADDR0) as usize, val),
+
+            csr::FFLAGS => {
+                self.csrs.fflags = val & csr::FFLAGS_MASK;
+                self.mark_fs_dirty();
+            }
+            csr::FRM => {
+                self.csrs.frm = val & csr::FRM_MASK;
+                self.mark_fs_dirty();
+            }
+            csr::FCSR => {
+                self.csrs.fflags = val & csr::FFLAGS_MASK;
+                self.csrs.frm = (val >> csr::FRM_SHIFT) & csr::FRM_MASK;
+                self.mark_fs_dirty();
+            }
 
-            csr::SATP => self.csrs.satp = val,
+            // vl, vtype and vlenb are read-only; only vset{i}vl{i} changes vl/vtype
+            csr::VSTART => {
+                self.csrs.vstart = val;
+                self.mark_vs_dirty();
+            }
+            csr::VXSAT => {
+                self.csrs.vxsat = val & csr::VXSAT_MASK;
+                self.mark_vs_dirty();
+            }
+            csr::VXRM => {
+                self.csrs.vxrm = val & csr::VXRM_MASK;
+                self.mark_vs_dirty();
+            }
+            csr::VCSR => {
+                self.csrs.vxsat = val & csr::VXSAT_MASK;
+                self.csrs.vxrm = (val >> csr::VXRM_SHIFT) & csr::VXRM_MASK;
+                self.mark_vs_dirty();
+            }
 
             _ => {}
         }
     }
 
+    /// Builds the canonical ISA string (e.g. `rv64imafdc_zicbom_zicbop_zicboz_zicsr_zifencei_zfh_zfhmin_zba_zbb_zbc_zbkb_zbs_zknd_zkne_zknh`) from `misa`.
+    pub fn isa_string(&self) -> String {
+        let misa = self.csrs.misa;
+        let mut isa = String::from("rv64");
+        for ext in "imafdqcvh".chars() {
+            if misa & (1 << (ext as u8 - b'a')) != 0 {
+                isa.push(ext);
+            }
+        }
+
+        isa.push_str("_zicbom_zicbop_zicboz_zicsr_zifencei");
+        if misa & csr::MISA_F != 0 {
+            isa.push_str("_zfh_zfhmin");
+        }
+        // The bit-manipulation extensions are built and reported together
+        if misa & csr::MISA_B != 0 {
+            isa.push_str("_zba_zbb_zbc_zbkb_zbs");
+        }
+        isa.push_str("_zknd_zkne_zknh");
+        isa
+    }
+
+    /// Merges a write into the `writable` fields of a status register and
+    /// recomputes SD, which summarises whether FS or VS is Dirty.
+    fn write_status(status: u64, val: u64, writable: u64) -> u64 {
+        let status = (status & !writable) | (val & writable);
+        let dirty = |field: u64| status & field == field;
+        if dirty(csr::MSTATUS_FS) || dirty(csr::MSTATUS_VS) {
+            status | csr::MSTATUS_SD
+        } else {
+            status & !csr::MSTATUS_SD
+        }
+    }
+
+    /// Keeps the implemented envcfg fields in `mask`; the reserved CBIE value
+    /// 10 is stored as 00.
+    fn legal_envcfg(val: u64, mask: u64) -> u64 {
+        let val = val & mask;
+        if (val & csr::ENVCFG_CBIE) >> 4 == 0b10 {
+            val & !csr::ENVCFG_CBIE
+        } else {
+            val
+        }
+    }
+
+    /// Checks a Zicbom/Zicboz instruction against the envcfg enables of the
+    /// current mode: menvcfg below M-mode, henvcfg while V=1 and senvcfg in
+    /// U-mode. Returns the operation to perform, as CBIE=01 turns `cbo.inval`
+    /// into a flush.
+    pub(crate) fn cache_block_enabled(&self, op: CacheOp, inst: u32) -> Result<CacheOp, Trap> {
+        let field = match op {
+            CacheOp::Inval => csr::ENVCFG_CBIE,
+            CacheOp::Clean | CacheOp::Flush => csr::ENVCFG_CBCFE,
+            CacheOp::Zero => csr::ENVCFG_CBZE,
+            _ => return Ok(op),
+        };
+        if self.privilege == 3 {
+            return Ok(op);
+        }
+        let m = self.csrs.menvcfg & field;
+        let h = if self.virt {
+            self.csrs.henvcfg & field
+        } else {
+            field
+        };
+        let s = if self.privilege == 0 {
+            self.csrs.senvcfg & field
+        } else {
+            field
+        };
+        if m == 0 {
+            return Err(Trap::IllegalInstruction(inst));
+        }
+        if h == 0 || s == 0 {
+            return Err(if self.virt {
+                Trap::VirtualInstruction(inst)
+            } else {
+                Trap::IllegalInstruction(inst)
+            });
+        }
+        if op == CacheOp::Inval && [m, h, s].contains(&csr::ENVCFG_CBIE_FLUSH) {
+            Ok(CacheOp::Flush)
+        } else {
+            Ok(op)
+        }
+    }
+
+    /// Records that floating-point state changed since the last context save.
+    pub(crate) fn mark_fs_dirty(&mut self) {
+        self.csrs.mstatus |= csr::MSTATUS_FS_DIRTY | csr::MSTATUS_SD;
+        if self.virt {
+            self.csrs.vsstatus |= csr::MSTATUS_FS_DIRTY | csr::MSTATUS_SD;
+        }
+    }
+
+    /// Records that vector state changed since the last context save.
+    pub(crate) fn mark_vs_dirty(&mut self) {
+        self.csrs.mstatus |= csr::MSTATUS_VS_DIRTY | csr::MSTATUS_SD;
+        if self.virt {
+            self.csrs.vsstatus |= csr::MSTATUS_VS_DIRTY | csr::MSTATUS_SD;
+        }
+    }
+
     pub(crate) fn do_mret(&mut self) {
         self.pc = self.csrs.mepc & !1;
-        self.privilege = 1;
+        let mstatus = self.csrs.mstatus;
+        // Only M, S or U is restored; the reserved level 2 returns to U
+        self.privilege = match (mstatus & csr::MSTATUS_MPP) >> csr::MSTATUS_MPP_SHIFT {
+            2 => 0,
+            mpp => mpp as u8,
+        };
+        self.virt = self.privilege != 3 && mstatus & csr::MSTATUS_MPV != 0;
+
+        // MIE <- MPIE, MPIE <- 1, MPP <- U, MPV <- 0, and MPRV <- 0 when leaving M-mode
+        let mut mstatus = mstatus & !(csr::MSTATUS_MIE | csr::MSTATUS_MPP | csr::MSTATUS_MPV);
+        if self.privilege != 3 {
+            mstatus &= !csr::MSTATUS_MPRV;
+        }
+        if mstatus & csr::MSTATUS_MPIE != 0 {
+            mstatus |= csr::MSTATUS_MIE;
+        }
+        self.csrs.mstatus = mstatus | csr::MSTATUS_MPIE;
+
         self.if_id = Default::default();
         self.id_ex = IdEx::default();
     }
 
     pub(crate) fn do_sret(&mut self) {
-        self.pc = self.csrs.sepc & !1;
-        let spp = (self.csrs.sstatus & csr::MSTATUS_SPP) != 0;
-        self.privilege = if spp { 1 } else { 0 };
-        let spie = (self.csrs.sstatus & csr::MSTATUS_MPIE) != 0;
+        // In VS-mode, sret returns through the VS CSRs and stays virtualized
+        let (epc, status) = if self.virt {
+            (self.csrs.vsepc, self.csrs.vsstatus)
+        } else {
+            (self.csrs.sepc, self.csrs.mstatus)
+        };
+        self.pc = epc & !1;
+        self.privilege = ((status & csr::MSTATUS_SPP) >> csr::MSTATUS_SPP_SHIFT) as u8;
+
+        // SIE <- SPIE, SPIE <- 1, SPP <- U
+        let mut status = status & !(csr::MSTATUS_SIE | csr::MSTATUS_SPP);
+        if status & csr::MSTATUS_SPIE != 0 {
+            status |= csr::MSTATUS_SIE;
+        }
+        let status = status | csr::MSTATUS_SPIE;
 
-        if spie {
-            self.csrs.sstatus |= csr::MSTATUS_MIE;
+        if self.virt {
+            self.csrs.vsstatus = status;
         } else {
-            self.csrs.sstatus &= !csr::MSTATUS_MIE;
+            // HS-mode returns into the mode recorded in hstatus.SPV, then SPV <- 0.
+            // SRET never returns to M-mode, so MPRV <- 0.
+            self.csrs.mstatus = status & !csr::MSTATUS_MPRV;
+            self.virt = self.csrs.hstatus & csr::HSTATUS_SPV != 0;
+            self.csrs.hstatus &= !csr::HSTATUS_SPV;
         }
 
-        self.csrs.sstatus |= csr::MSTATUS_MPIE;
         self.if_id = Default::default();
         self.id_ex = IdEx::default();
     }
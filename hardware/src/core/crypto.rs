@@ -0,0 +1,182 @@
+/// Multiplies two elements of GF(2^8) modulo the AES polynomial x^8 + x^4 + x^3 + x + 1.
+const fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut res = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            res ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1B;
+        }
+        b >>= 1;
+    }
+    res
+}
+
+const fn build_sbox() -> [u8; 256] {
+    let mut sbox = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        // Multiplicative inverse as x^254; 0 maps to 0
+        let x = i as u8;
+        let mut inv = 1u8;
+        let mut n = 0;
+        while n < 254 {
+            inv = gf_mul(inv, x);
+            n += 1;
+        }
+        if x == 0 {
+            inv = 0;
+        }
+        sbox[i] = inv
+            ^ inv.rotate_left(1)
+            ^ inv.rotate_left(2)
+            ^ inv.rotate_left(3)
+            ^ inv.rotate_left(4)
+            ^ 0x63;
+        i += 1;
+    }
+    sbox
+}
+
+const fn build_inv_sbox(sbox: &[u8; 256]) -> [u8; 256] {
+    let mut inv = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv[sbox[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv
+}
+
+const SBOX: [u8; 256] = build_sbox();
+const INV_SBOX: [u8; 256] = build_inv_sbox(&SBOX);
+
+fn sub_bytes(x: u64, table: &[u8; 256]) -> u64 {
+    u64::from_le_bytes(x.to_le_bytes().map(|b| table[b as usize]))
+}
+
+/// Forward or inverse ShiftRows over the 128-bit state `rs2:rs1`, returning
+/// the low two columns (the half an RV64 instruction produces).
+fn shift_rows(rs1: u64, rs2: u64, inverse: bool) -> u64 {
+    let state = ((rs2 as u128) << 64) | rs1 as u128;
+    let byte = |i: usize| (state >> (i * 8)) as u8;
+
+    let mut out = [0u8; 8];
+    for (i, b) in out.iter_mut().enumerate() {
+        let (row, col) = (i % 4, i / 4);
+        let src_col = if inverse {
+            (col + 4 - row) % 4
+        } else {
+            (col + row) % 4
+        };
+        *b = byte(row + 4 * src_col);
+    }
+    u64::from_le_bytes(out)
+}
+
+fn mix_column(col: u32, inverse: bool) -> u32 {
+    let b = col.to_le_bytes();
+    let m: [u8; 4] = if inverse {
+        [0x0E, 0x0B, 0x0D, 0x09]
+    } else {
+        [0x02, 0x03, 0x01, 0x01]
+    };
+
+    let mut out = [0u8; 4];
+    for (row, o) in out.iter_mut().enumerate() {
+        for (i, &byte) in b.iter().enumerate() {
+            *o ^= gf_mul(byte, m[(i + 4 - row) % 4]);
+        }
+    }
+    u32::from_le_bytes(out)
+}
+
+fn mix_columns(x: u64, inverse: bool) -> u64 {
+    let lo = mix_column(x as u32, inverse) as u64;
+    let hi = mix_column((x >> 32) as u32, inverse) as u64;
+    (hi << 32) | lo
+}
+
+pub fn aes64es(rs1: u64, rs2: u64) -> u64 {
+    sub_bytes(shift_rows(rs1, rs2, false), &SBOX)
+}
+
+pub fn aes64esm(rs1: u64, rs2: u64) -> u64 {
+    mix_columns(aes64es(rs1, rs2), false)
+}
+
+pub fn aes64ds(rs1: u64, rs2: u64) -> u64 {
+    sub_bytes(shift_rows(rs1, rs2, true), &INV_SBOX)
+}
+
+pub fn aes64dsm(rs1: u64, rs2: u64) -> u64 {
+    mix_columns(aes64ds(rs1, rs2), true)
+}
+
+pub fn aes64im(rs1: u64) -> u64 {
+    mix_columns(rs1, true)
+}
+
+/// Key-schedule step 1. `rnum` must already be validated as 0..=0xA.
+pub fn aes64ks1i(rs1: u64, rnum: u64) -> u64 {
+    const RCON: [u32; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1B, 0x36];
+
+    let word = (rs1 >> 32) as u32;
+    // rnum = 0xA is used for the AES-256 schedule: no rotation and no round constant
+    let (word, rcon) = if rnum == 0xA {
+        (word, 0)
+    } else {
+        (word.rotate_right(8), RCON[rnum as usize])
+    };
+    let sub = sub_bytes(word as u64, &SBOX) as u32 ^ rcon;
+    ((sub as u64) << 32) | sub as u64
+}
+
+pub fn aes64ks2(rs1: u64, rs2: u64) -> u64 {
+    let w0 = (rs1 >> 32) as u32 ^ rs2 as u32;
+    let w1 = w0 ^ (rs2 >> 32) as u32;
+    ((w1 as u64) << 32) | w0 as u64
+}
+
+fn sext32(x: u32) -> u64 {
+    x as i32 as i64 as u64
+}
+
+pub fn sha256sig0(rs1: u64) -> u64 {
+    let x = rs1 as u32;
+    sext32(x.rotate_right(7) ^ x.rotate_right(18) ^ (x >> 3))
+}
+
+pub fn sha256sig1(rs1: u64) -> u64 {
+    let x = rs1 as u32;
+    sext32(x.rotate_right(17) ^ x.rotate_right(19) ^ (x >> 10))
+}
+
+pub fn sha256sum0(rs1: u64) -> u64 {
+    let x = rs1 as u32;
+    sext32(x.rotate_right(2) ^ x.rotate_right(13) ^ x.rotate_right(22))
+}
+
+pub fn sha256sum1(rs1: u64) -> u64 {
+    let x = rs1 as u32;
+    sext32(x.rotate_right(6) ^ x.rotate_right(11) ^ x.rotate_right(25))
+}
+
+pub fn sha512sig0(x: u64) -> u64 {
+    x.rotate_right(1) ^ x.rotate_right(8) ^ (x >> 7)
+}
+
+pub fn sha512sig1(x: u64) -> u64 {
+    x.rotate_right(19) ^ x.rotate_right(61) ^ (x >> 6)
+}
+
+pub fn sha512sum0(x: u64) -> u64 {
+    x.rotate_right(28) ^ x.rotate_right(34) ^ x.rotate_right(39)
+}
+
+pub fn sha512sum1(x: u64) -> u64 {
+    x.rotate_right(14) ^ x.rotate_right(18) ^ x.rotate_right(41)
+}
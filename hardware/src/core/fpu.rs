@@ -0,0 +1,730 @@
+use crate::isa::csr;
+
+/// `rm` encoding that selects the rounding mode held in `frm`.
+pub const RM_DYNAMIC: u64 = 0b111;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum RoundingMode {
+    #[default]
+    NearestEven,
+    TowardZero,
+    Down,
+    Up,
+    NearestMaxMagnitude,
+}
+
+impl RoundingMode {
+    /// Decodes a static `rm` field or the `frm` CSR. Reserved encodings return `None`.
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        match bits {
+            0b000 => Some(Self::NearestEven),
+            0b001 => Some(Self::TowardZero),
+            0b010 => Some(Self::Down),
+            0b011 => Some(Self::Up),
+            0b100 => Some(Self::NearestMaxMagnitude),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum FpFormat {
+    Half,
+    Single,
+    #[default]
+    Double,
+}
+
+impl FpFormat {
+    fn exp_bits(self) -> u32 {
+        match self {
+            FpFormat::Half => 5,
+            FpFormat::Single => 8,
+            FpFormat::Double => 11,
+        }
+    }
+
+    fn frac_bits(self) -> u32 {
+        match self {
+            FpFormat::Half => 10,
+            FpFormat::Single => 23,
+            FpFormat::Double => 52,
+        }
+    }
+
+    fn bias(self) -> i32 {
+        (1 << (self.exp_bits() - 1)) - 1
+    }
+
+    fn exp_max(self) -> u64 {
+        (1 << self.exp_bits()) - 1
+    }
+
+    fn frac_mask(self) -> u64 {
+        (1 << self.frac_bits()) - 1
+    }
+
+    fn pack(self, sign: bool, biased_exp: u64, frac: u64) -> u64 {
+        ((sign as u64) << (self.exp_bits() + self.frac_bits()))
+            | (biased_exp << self.frac_bits())
+            | (frac & self.frac_mask())
+    }
+
+    pub fn canonical_nan(self) -> u64 {
+        self.pack(false, self.exp_max(), 1 << (self.frac_bits() - 1))
+    }
+
+    fn sign_mask(self) -> u64 {
+        1 << (self.exp_bits() + self.frac_bits())
+    }
+
+    /// Reads an operand from a 64-bit register. Narrow values that are not
+    /// properly NaN-boxed read as the canonical NaN.
+    pub fn unbox(self, bits: u64) -> u64 {
+        match self {
+            FpFormat::Double => bits,
+            _ => {
+                let width = self.exp_bits() + self.frac_bits() + 1;
+                if bits >> width == u64::MAX >> width {
+                    bits & ((1 << width) - 1)
+                } else {
+                    self.canonical_nan()
+                }
+            }
+        }
+    }
+
+    /// Widens a result to the 64-bit register width, NaN-boxing narrow formats.
+    pub fn box_bits(self, bits: u64) -> u64 {
+        match self {
+            FpFormat::Double => bits,
+            _ => {
+                let width = self.exp_bits() + self.frac_bits() + 1;
+                (bits & ((1 << width) - 1)) | (u64::MAX << width)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Class {
+    Zero,
+    Finite,
+    Inf,
+    QuietNan,
+    SignalingNan,
+}
+
+/// A decoded operand: `(-1)^sign * sig * 2^exp` for finite values.
+#[derive(Clone, Copy, Debug)]
+struct Unpacked {
+    sign: bool,
+    class: Class,
+    exp: i32,
+    sig: u128,
+}
+
+impl Unpacked {
+    fn is_nan(&self) -> bool {
+        matches!(self.class, Class::QuietNan | Class::SignalingNan)
+    }
+
+    fn is_snan(&self) -> bool {
+        self.class == Class::SignalingNan
+    }
+}
+
+fn unpack(fmt: FpFormat, bits: u64) -> Unpacked {
+    let bits = fmt.unbox(bits);
+    let frac_bits = fmt.frac_bits();
+    let sign = (bits >> (fmt.exp_bits() + frac_bits)) & 1 != 0;
+    let biased = (bits >> frac_bits) & fmt.exp_max();
+    let frac = bits & fmt.frac_mask();
+
+    let (class, exp, sig) = if biased == fmt.exp_max() {
+        if frac == 0 {
+            (Class::Inf, 0, 0)
+        } else if (frac >> (frac_bits - 1)) & 1 != 0 {
+            (Class::QuietNan, 0, 0)
+        } else {
+            (Class::SignalingNan, 0, 0)
+        }
+    } else if biased == 0 {
+        if frac == 0 {
+            (Class::Zero, 0, 0)
+        } else {
+            (
+                Class::Finite,
+                1 - fmt.bias() - frac_bits as i32,
+                frac as u128,
+            )
+        }
+    } else {
+        (
+            Class::Finite,
+            biased as i32 - fmt.bias() - frac_bits as i32,
+            (frac | (1 << frac_bits)) as u128,
+        )
+    };
+
+    Unpacked {
+        sign,
+        class,
+        exp,
+        sig,
+    }
+}
+
+/// Shifts right, folding any bits shifted out into the least significant bit.
+fn shift_right_jam(sig: u128, shift: i32) -> u128 {
+    if shift <= 0 {
+        sig
+    } else if shift >= 128 {
+        (sig != 0) as u128
+    } else {
+        (sig >> shift) | ((sig & ((1 << shift) - 1)) != 0) as u128
+    }
+}
+
+/// Shifts left so the most significant set bit lands on `msb`.
+fn normalize(exp: i32, sig: u128, msb: i32) -> (i32, u128) {
+    let shift = msb - (127 - sig.leading_zeros() as i32);
+    if shift >= 0 {
+        (exp - shift, sig << shift)
+    } else {
+        (exp - shift, shift_right_jam(sig, -shift))
+    }
+}
+
+/// Maps a non-NaN encoding onto a key that orders like the value it holds.
+fn order_key(fmt: FpFormat, bits: u64) -> i64 {
+    let mag = (bits & !fmt.sign_mask()) as i64;
+    if bits & fmt.sign_mask() != 0 {
+        -mag
+    } else {
+        mag
+    }
+}
+
+/// Integer square root, returning the root and whether a remainder was left.
+fn isqrt(mut n: u128) -> (u128, bool) {
+    let mut root = 0u128;
+    let mut bit = 1u128 << 126;
+    while bit > n {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if n >= root + bit {
+            n -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    (root, n != 0)
+}
+
+/// Software IEEE-754 arithmetic with RISC-V rounding, NaN and flag semantics.
+///
+/// Results for narrow formats come back NaN-boxed; exception flags raised
+/// by every operation accumulate in `flags` using the `fflags` bit layout.
+pub struct SoftFloat {
+    pub rm: RoundingMode,
+    pub flags: u64,
+}
+
+impl SoftFloat {
+    pub fn new(rm: RoundingMode) -> Self {
+        Self { rm, flags: 0 }
+    }
+
+    fn raise(&mut self, flags: u64) {
+        self.flags |= flags;
+    }
+
+    fn invalid(&mut self, fmt: FpFormat) -> u64 {
+        self.raise(csr::FFLAGS_NV);
+        fmt.canonical_nan()
+    }
+
+    /// Returns the canonical NaN, raising NV if any operand is signaling.
+    fn propagate_nan(&mut self, fmt: FpFormat, ops: &[Unpacked]) -> u64 {
+        if ops.iter().any(|u| u.is_snan()) {
+            self.raise(csr::FFLAGS_NV);
+        }
+        fmt.canonical_nan()
+    }
+
+    /// Rounds the shifted-out part of `sig` according to the current mode.
+    fn round_shift(&self, sign: bool, sig: u128, shift: i32) -> (u128, bool) {
+        if shift <= 0 {
+            return (sig << -shift, false);
+        }
+
+        let (mant, rem, half) = if shift > 128 {
+            (0, sig, None)
+        } else if shift == 128 {
+            (0, sig, Some(1u128 << 127))
+        } else {
+            (
+                sig >> shift,
+                sig & ((1 << shift) - 1),
+                Some(1u128 << (shift - 1)),
+            )
+        };
+
+        if rem == 0 {
+            return (mant, false);
+        }
+
+        let above = half.is_some_and(|h| rem > h);
+        let tie = half.is_some_and(|h| rem == h);
+
+        let increment = match self.rm {
+            RoundingMode::NearestEven => above || (tie && (mant & 1) != 0),
+            RoundingMode::TowardZero => false,
+            RoundingMode::Down => sign,
+            RoundingMode::Up => !sign,
+            RoundingMode::NearestMaxMagnitude => above || tie,
+        };
+
+        (mant + increment as u128, true)
+    }
+
+    /// Rounds `(-1)^sign * sig * 2^exp` to `fmt`, raising NX/UF/OF as needed.
+    fn round_pack(&mut self, fmt: FpFormat, sign: bool, exp: i32, sig: u128) -> u64 {
+        if sig == 0 {
+            return fmt.pack(sign, 0, 0);
+        }
+
+        let frac = fmt.frac_bits() as i32;
+        let emin = 1 - fmt.bias();
+        let e = exp + (127 - sig.leading_zeros() as i32);
+
+        // Tininess is detected after rounding, as if the exponent were unbounded
+        let tiny = if e < emin - 1 {
+            true
+        } else if e == emin - 1 {
+            let (m, _) = self.round_shift(sign, sig, (e - frac) - exp);
+            m < (1 << (frac + 1))
+        } else {
+            false
+        };
+
+        let mut quantum = e.max(emin) - frac;
+        let (mut mant, inexact) = self.round_shift(sign, sig, quantum - exp);
+        if mant >= (1 << (frac + 1)) {
+            mant >>= 1;
+            quantum += 1;
+        }
+
+        let biased = if mant >= (1 << frac) {
+            (quantum + frac + fmt.bias()) as u64
+        } else {
+            0
+        };
+
+        if biased >= fmt.exp_max() {
+            self.raise(csr::FFLAGS_OF | csr::FFLAGS_NX);
+            let to_inf = match self.rm {
+                RoundingMode::NearestEven | RoundingMode::NearestMaxMagnitude => true,
+                RoundingMode::TowardZero => false,
+                RoundingMode::Down => sign,
+                RoundingMode::Up => !sign,
+            };
+            return if to_inf {
+                fmt.pack(sign, fmt.exp_max(), 0)
+            } else {
+                fmt.pack(sign, fmt.exp_max() - 1, fmt.frac_mask())
+            };
+        }
+
+        if inexact {
+            self.raise(csr::FFLAGS_NX);
+            if tiny {
+                self.raise(csr::FFLAGS_UF);
+            }
+        }
+
+        fmt.pack(sign, biased, mant as u64)
+    }
+
+    fn add_unpacked(&mut self, fmt: FpFormat, x: Unpacked, y: Unpacked) -> u64 {
+        match (x.class, y.class) {
+            (Class::Inf, Class::Inf) => {
+                if x.sign != y.sign {
+                    self.invalid(fmt)
+                } else {
+                    fmt.pack(x.sign, fmt.exp_max(), 0)
+                }
+            }
+            (Class::Inf, _) => fmt.pack(x.sign, fmt.exp_max(), 0),
+            (_, Class::Inf) => fmt.pack(y.sign, fmt.exp_max(), 0),
+            (Class::Zero, Class::Zero) => {
+                let sign = if x.sign == y.sign {
+                    x.sign
+                } else {
+                    self.rm == RoundingMode::Down
+                };
+                fmt.pack(sign, 0, 0)
+            }
+            (Class::Zero, _) => self.round_pack(fmt, y.sign, y.exp, y.sig),
+            (_, Class::Zero) => self.round_pack(fmt, x.sign, x.exp, x.sig),
+            _ => {
+                // Operands carry at most 106 significant bits, so aligning them at
+                // bit 124 keeps every bit that can survive cancellation.
+                let (xe, xs) = normalize(x.exp, x.sig, 124);
+                let (ye, ys) = normalize(y.exp, y.sig, 124);
+                let ((be, bs, bsign), (se, ss, ssign)) = if xe >= ye {
+                    ((xe, xs, x.sign), (ye, ys, y.sign))
+                } else {
+                    ((ye, ys, y.sign), (xe, xs, x.sign))
+                };
+                let ss = shift_right_jam(ss, be - se);
+
+                let (sign, sig) = if bsign == ssign {
+                    (bsign, bs + ss)
+                } else if bs >= ss {
+                    (bsign, bs - ss)
+                } else {
+                    (ssign, ss - bs)
+                };
+
+                if sig == 0 {
+                    return fmt.pack(self.rm == RoundingMode::Down, 0, 0);
+                }
+                self.round_pack(fmt, sign, be, sig)
+            }
+        }
+    }
+
+    fn binary_operands(fmt: FpFormat, a: u64, b: u64) -> (Unpacked, Unpacked) {
+        (unpack(fmt, a), unpack(fmt, b))
+    }
+
+    pub fn add(&mut self, fmt: FpFormat, a: u64, b: u64) -> u64 {
+        let (x, y) = Self::binary_operands(fmt, a, b);
+        if x.is_nan() || y.is_nan() {
+            return fmt.box_bits(self.propagate_nan(fmt, &[x, y]));
+        }
+        fmt.box_bits(self.add_unpacked(fmt, x, y))
+    }
+
+    pub fn sub(&mut self, fmt: FpFormat, a: u64, b: u64) -> u64 {
+        let (x, mut y) = Self::binary_operands(fmt, a, b);
+        if x.is_nan() || y.is_nan() {
+            return fmt.box_bits(self.propagate_nan(fmt, &[x, y]));
+        }
+        y.sign = !y.sign;
+        fmt.box_bits(self.add_unpacked(fmt, x, y))
+    }
+
+    /// Exact product of two non-NaN operands; inf * 0 must be screened by the caller.
+    fn multiply(x: Unpacked, y: Unpacked) -> Unpacked {
+        let sign = x.sign != y.sign;
+        let class = match (x.class, y.class) {
+            (Class::Inf, _) | (_, Class::Inf) => Class::Inf,
+            (Class::Zero, _) | (_, Class::Zero) => Class::Zero,
+            _ => Class::Finite,
+        };
+        Unpacked {
+            sign,
+            class,
+            exp: x.exp + y.exp,
+            sig: x.sig * y.sig,
+        }
+    }
+
+    fn is_inf_times_zero(x: &Unpacked, y: &Unpacked) -> bool {
+        (x.class == Class::Inf && y.class == Class::Zero)
+            || (x.class == Class::Zero && y.class == Class::Inf)
+    }
+
+    pub fn mul(&mut self, fmt: FpFormat, a: u64, b: u64) -> u64 {
+        let (x, y) = Self::binary_operands(fmt, a, b);
+        if x.is_nan() || y.is_nan() {
+            return fmt.box_bits(self.propagate_nan(fmt, &[x, y]));
+        }
+        if Self::is_inf_times_zero(&x, &y) {
+            return fmt.box_bits(self.invalid(fmt));
+        }
+
+        let p = Self::multiply(x, y);
+        let res = match p.class {
+            Class::Inf => fmt.pack(p.sign, fmt.exp_max(), 0),
+            Class::Zero => fmt.pack(p.sign, 0, 0),
+            _ => self.round_pack(fmt, p.sign, p.exp, p.sig),
+        };
+        fmt.box_bits(res)
+    }
+
+    /// Computes `(+/-)(a * b) (+/-) c` with a single rounding.
+    pub fn fma(
+        &mut self,
+        fmt: FpFormat,
+        a: u64,
+        b: u64,
+        c: u64,
+        negate_product: bool,
+        negate_addend: bool,
+    ) -> u64 {
+        let x = unpack(fmt, a);
+        let y = unpack(fmt, b);
+        let mut z = unpack(fmt, c);
+
+        // inf * 0 is invalid even when the addend is a quiet NaN
+        if Self::is_inf_times_zero(&x, &y) {
+            return fmt.box_bits(self.invalid(fmt));
+        }
+        if x.is_nan() || y.is_nan() || z.is_nan() {
+            return fmt.box_bits(self.propagate_nan(fmt, &[x, y, z]));
+        }
+
+        let mut p = Self::multiply(x, y);
+        p.sign ^= negate_product;
+        z.sign ^= negate_addend;
+        fmt.box_bits(self.add_unpacked(fmt, p, z))
+    }
+
+    pub fn div(&mut self, fmt: FpFormat, a: u64, b: u64) -> u64 {
+        let (x, y) = Self::binary_operands(fmt, a, b);
+        if x.is_nan() || y.is_nan() {
+            return fmt.box_bits(self.propagate_nan(fmt, &[x, y]));
+        }
+
+        let sign = x.sign != y.sign;
+        let res = match (x.class, y.class) {
+            (Class::Inf, Class::Inf) | (Class::Zero, Class::Zero) => self.invalid(fmt),
+            (Class::Inf, _) => fmt.pack(sign, fmt.exp_max(), 0),
+            (_, Class::Inf) | (Class::Zero, _) => fmt.pack(sign, 0, 0),
+            (_, Class::Zero) => {
+                self.raise(csr::FFLAGS_DZ);
+                fmt.pack(sign, fmt.exp_max(), 0)
+            }
+            _ => {
+                let (xe, xs) = normalize(x.exp, x.sig, 125);
+                let q = xs / y.sig;
+                let sticky = (xs % y.sig != 0) as u128;
+                self.round_pack(fmt, sign, xe - y.exp, q | sticky)
+            }
+        };
+        fmt.box_bits(res)
+    }
+
+    pub fn sqrt(&mut self, fmt: FpFormat, a: u64) -> u64 {
+        let x = unpack(fmt, a);
+        if x.is_nan() {
+            return fmt.box_bits(self.propagate_nan(fmt, &[x]));
+        }
+
+        let res = match x.class {
+            Class::Zero => fmt.pack(x.sign, 0, 0),
+            _ if x.sign => self.invalid(fmt),
+            Class::Inf => fmt.pack(false, fmt.exp_max(), 0),
+            _ => {
+                // Keep the exponent even so it halves exactly
+                let (mut e, mut s) = normalize(x.exp, x.sig, 125);
+                if e & 1 != 0 {
+                    e += 1;
+                    s >>= 1;
+                }
+                let (root, inexact) = isqrt(s);
+                self.round_pack(fmt, false, e / 2, root | inexact as u128)
+            }
+        };
+        fmt.box_bits(res)
+    }
+
+    /// FCVT between floating-point formats.
+    pub fn convert(&mut self, from: FpFormat, to: FpFormat, a: u64) -> u64 {
+        let x = unpack(from, a);
+        let res = match x.class {
+            Class::QuietNan | Class::SignalingNan => self.propagate_nan(to, &[x]),
+            Class::Inf => to.pack(x.sign, to.exp_max(), 0),
+            Class::Zero => to.pack(x.sign, 0, 0),
+            Class::Finite => self.round_pack(to, x.sign, x.exp, x.sig),
+        };
+        to.box_bits(res)
+    }
+
+    /// FCVT from a 32- or 64-bit integer register value.
+    pub fn int_to_float(&mut self, fmt: FpFormat, val: u64, signed: bool, bits: u32) -> u64 {
+        let val = match (bits, signed) {
+            (32, true) => val as i32 as i64 as u64,
+            (32, false) => val as u32 as u64,
+            _ => val,
+        };
+        let (sign, mag) = if signed && (val as i64) < 0 {
+            (true, (val as i64).unsigned_abs())
+        } else {
+            (false, val)
+        };
+        fmt.box_bits(self.round_pack(fmt, sign, 0, mag as u128))
+    }
+
+    /// FCVT to a 32- or 64-bit integer, saturating out-of-range results.
+    ///
+    /// 32-bit results are sign-extended to the register width, as RV64 requires
+    /// for both the signed and unsigned variants.
+    pub fn float_to_int(&mut self, fmt: FpFormat, a: u64, signed: bool, bits: u32) -> u64 {
+        let x = unpack(fmt, a);
+
+        let max: u64 = match (signed, bits) {
+            (true, 32) => i32::MAX as u64,
+            (true, _) => i64::MAX as u64,
+            (false, 32) => u32::MAX as u64,
+            (false, _) => u64::MAX,
+        };
+        let min: u64 = match (signed, bits) {
+            (true, 32) => i32::MIN as i64 as u64,
+            (true, _) => i64::MIN as u64,
+            (false, _) => 0,
+        };
+        let extend = |v: u64| -> u64 {
+            if bits == 32 {
+                v as u32 as i32 as i64 as u64
+            } else {
+                v
+            }
+        };
+
+        let (mag, inexact) = match x.class {
+            Class::QuietNan | Class::SignalingNan => {
+                self.raise(csr::FFLAGS_NV);
+                return extend(max);
+            }
+            Class::Inf => {
+                self.raise(csr::FFLAGS_NV);
+                return extend(if x.sign { min } else { max });
+            }
+            Class::Zero => return 0,
+            Class::Finite => {
+                if x.exp >= 0 {
+                    if x.exp > 64 {
+                        (u128::MAX, false)
+                    } else {
+                        (x.sig << x.exp, false)
+                    }
+                } else {
+                    self.round_shift(x.sign, x.sig, -x.exp)
+                }
+            }
+        };
+
+        let limit: u128 = if !signed {
+            max as u128
+        } else if x.sign {
+            1u128 << (bits - 1)
+        } else {
+            (1u128 << (bits - 1)) - 1
+        };
+
+        if (x.sign && !signed && mag != 0) || mag > limit {
+            self.raise(csr::FFLAGS_NV);
+            return extend(if x.sign { min } else { max });
+        }
+        if inexact {
+            self.raise(csr::FFLAGS_NX);
+        }
+
+        let res = if x.sign {
+            (mag as u64).wrapping_neg()
+        } else {
+            mag as u64
+        };
+        extend(res)
+    }
+
+    pub fn sgnj(&self, fmt: FpFormat, a: u64, b: u64) -> u64 {
+        let (a, b) = (fmt.unbox(a), fmt.unbox(b));
+        fmt.box_bits((a & !fmt.sign_mask()) | (b & fmt.sign_mask()))
+    }
+
+    pub fn sgnjn(&self, fmt: FpFormat, a: u64, b: u64) -> u64 {
+        let (a, b) = (fmt.unbox(a), fmt.unbox(b));
+        fmt.box_bits((a & !fmt.sign_mask()) | (!b & fmt.sign_mask()))
+    }
+
+    pub fn sgnjx(&self, fmt: FpFormat, a: u64, b: u64) -> u64 {
+        let (a, b) = (fmt.unbox(a), fmt.unbox(b));
+        fmt.box_bits(a ^ (b & fmt.sign_mask()))
+    }
+
+    /// FMIN/FMAX: a single NaN operand yields the other operand, and -0 < +0.
+    pub fn min_max(&mut self, fmt: FpFormat, a: u64, b: u64, want_max: bool) -> u64 {
+        let (x, y) = Self::binary_operands(fmt, a, b);
+        if x.is_snan() || y.is_snan() {
+            self.raise(csr::FFLAGS_NV);
+        }
+
+        let (a, b) = (fmt.unbox(a), fmt.unbox(b));
+        let res = match (x.is_nan(), y.is_nan()) {
+            (true, true) => fmt.canonical_nan(),
+            (true, false) => b,
+            (false, true) => a,
+            (false, false) => {
+                let (ka, kb) = (order_key(fmt, a), order_key(fmt, b));
+                let a_first = if ka == kb {
+                    // Only +0 and -0 tie with different encodings
+                    (a & fmt.sign_mask() != 0) != want_max
+                } else {
+                    (ka < kb) != want_max
+                };
+                if a_first { a } else { b }
+            }
+        };
+        fmt.box_bits(res)
+    }
+
+    /// FEQ is a quiet comparison: only signaling NaNs raise NV.
+    pub fn eq(&mut self, fmt: FpFormat, a: u64, b: u64) -> u64 {
+        let (x, y) = Self::binary_operands(fmt, a, b);
+        if x.is_nan() || y.is_nan() {
+            if x.is_snan() || y.is_snan() {
+                self.raise(csr::FFLAGS_NV);
+            }
+            return 0;
+        }
+        (order_key(fmt, fmt.unbox(a)) == order_key(fmt, fmt.unbox(b))) as u64
+    }
+
+    /// FLT is a signaling comparison: any NaN operand raises NV.
+    pub fn lt(&mut self, fmt: FpFormat, a: u64, b: u64) -> u64 {
+        let (x, y) = Self::binary_operands(fmt, a, b);
+        if x.is_nan() || y.is_nan() {
+            self.raise(csr::FFLAGS_NV);
+            return 0;
+        }
+        (order_key(fmt, fmt.unbox(a)) < order_key(fmt, fmt.unbox(b))) as u64
+    }
+
+    /// FLE is a signaling comparison: any NaN operand raises NV.
+    pub fn le(&mut self, fmt: FpFormat, a: u64, b: u64) -> u64 {
+        let (x, y) = Self::binary_operands(fmt, a, b);
+        if x.is_nan() || y.is_nan() {
+            self.raise(csr::FFLAGS_NV);
+            return 0;
+        }
+        (order_key(fmt, fmt.unbox(a)) <= order_key(fmt, fmt.unbox(b))) as u64
+    }
+
+    /// FCLASS: returns a one-hot mask describing the operand.
+    pub fn classify(&self, fmt: FpFormat, a: u64) -> u64 {
+        let x = unpack(fmt, a);
+        let subnormal = (fmt.unbox(a) >> fmt.frac_bits()) & fmt.exp_max() == 0;
+        let bit = match (x.class, x.sign) {
+            (Class::Inf, true) => 0,
+            (Class::Finite, true) if !subnormal => 1,
+            (Class::Finite, true) => 2,
+            (Class::Zero, true) => 3,
+            (Class::Zero, false) => 4,
+            (Class::Finite, false) if subnormal => 5,
+            (Class::Finite, false) => 6,
+            (Class::Inf, false) => 7,
+            (Class::SignalingNan, _) => 8,
+            (Class::QuietNan, _) => 9,
+        };
+        1 << bit
+    }
+}
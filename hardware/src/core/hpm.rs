@@ -0,0 +1,157 @@
+use crate::stats::SimStats;
+
+// mhpmevent fields (Sscofpmf): overflow flag and per-mode count inhibits
+const EVENT_OF: u64 = 1 << 63;
+const EVENT_MINH: u64 = 1 << 62;
+const EVENT_SINH: u64 = 1 << 61;
+const EVENT_UINH: u64 = 1 << 60;
+const EVENT_VSINH: u64 = 1 << 59;
+const EVENT_VUINH: u64 = 1 << 58;
+const EVENT_FLAGS: u64 =
+    EVENT_OF | EVENT_MINH | EVENT_SINH | EVENT_UINH | EVENT_VSINH | EVENT_VUINH;
+const EVENT_SELECT: u64 = (1 << 56) - 1;
+
+// Event selectors, each counting a SimStats event
+const EVENT_NONE: u64 = 0;
+const EVENT_L1I_MISS: u64 = 1;
+const EVENT_L1D_MISS: u64 = 2;
+const EVENT_L2_MISS: u64 = 3;
+const EVENT_L3_MISS: u64 = 4;
+const EVENT_BRANCH_MISPREDICT: u64 = 5;
+const EVENT_ITLB_MISS: u64 = 6;
+const EVENT_DTLB_MISS: u64 = 7;
+const EVENT_MEM_STALL: u64 = 8;
+const EVENT_DATA_STALL: u64 = 9;
+const EVENT_FP_OP: u64 = 10;
+pub const NUM_EVENTS: u64 = 10;
+
+// Counter indices, as in the CSR numbers; index 1 is `time`, which is not a counter
+const CY: usize = 0;
+const IR: usize = 2;
+const FIRST_HPM: usize = 3;
+const COUNTERS: usize = 32;
+
+/// mcountinhibit bits: CY, IR and HPM3-31.
+const INHIBIT_MASK: u64 = 0xFFFF_FFFD;
+
+/// mcycle, minstret and mhpmcounter3-31, counting SimStats events.
+/// Counters catch up with the statistics once per cycle in `update`.
+pub struct Hpm {
+    counters: [u64; COUNTERS],
+    events: [u64; COUNTERS],
+    /// Event totals at the last update
+    last: [u64; COUNTERS],
+    /// Counters with an event to follow
+    active: u32,
+    /// mcountinhibit
+    inhibit: u64,
+}
+
+impl Default for Hpm {
+    fn default() -> Self {
+        Self {
+            counters: [0; COUNTERS],
+            events: [EVENT_NONE; COUNTERS],
+            last: [0; COUNTERS],
+            active: 1 << CY | 1 << IR,
+            inhibit: 0,
+        }
+    }
+}
+
+fn event_total(stats: &SimStats, idx: usize, event: u64) -> u64 {
+    match (idx, event & EVENT_SELECT) {
+        (CY, _) => stats.cycles,
+        (IR, _) => stats.instructions_retired,
+        (_, EVENT_L1I_MISS) => stats.icache_misses,
+        (_, EVENT_L1D_MISS) => stats.dcache_misses,
+        (_, EVENT_L2_MISS) => stats.l2_misses,
+        (_, EVENT_L3_MISS) => stats.l3_misses,
+        (_, EVENT_BRANCH_MISPREDICT) => stats.branch_mispredictions,
+        (_, EVENT_ITLB_MISS) => stats.itlb_misses,
+        (_, EVENT_DTLB_MISS) => stats.dtlb_misses,
+        (_, EVENT_MEM_STALL) => stats.stalls_mem,
+        (_, EVENT_DATA_STALL) => stats.stalls_data,
+        (_, EVENT_FP_OP) => stats.inst_fp_arith + stats.inst_fp_fma + stats.inst_fp_div_sqrt,
+        _ => 0,
+    }
+}
+
+impl Hpm {
+    pub fn read_counter(&self, idx: usize) -> u64 {
+        self.counters[idx]
+    }
+
+    pub fn write_counter(&mut self, idx: usize, val: u64) {
+        self.counters[idx] = val;
+    }
+
+    pub fn read_event(&self, idx: usize) -> u64 {
+        self.events[idx]
+    }
+
+    /// Selects the event counter `idx` follows; unknown events count nothing.
+    pub fn write_event(&mut self, idx: usize, val: u64, stats: &SimStats) {
+        let mut event = val & (EVENT_FLAGS | EVENT_SELECT);
+        if event & EVENT_SELECT > NUM_EVENTS {
+            event &= EVENT_FLAGS;
+        }
+        self.events[idx] = event;
+        self.last[idx] = event_total(stats, idx, event);
+        if event & EVENT_SELECT != EVENT_NONE {
+            self.active |= 1 << idx;
+        } else {
+            self.active &= !(1 << idx);
+        }
+    }
+
+    /// The OF bits of mhpmevent3-31, as scountovf shows them.
+    pub fn overflow(&self) -> u64 {
+        (FIRST_HPM..COUNTERS)
+            .filter(|&i| self.events[i] & EVENT_OF != 0)
+            .fold(0, |acc, i| acc | 1 << i)
+    }
+
+    /// Adds the events since the last update to the counters not inhibited,
+    /// globally or in the current mode. Returns true when an HPM counter
+    /// overflows with its OF bit clear, which raises LCOFIP.
+    pub fn update(&mut self, stats: &SimStats, privilege: u8, virt: bool) -> bool {
+        let mode_inhibit = match (privilege, virt) {
+            (3, _) => EVENT_MINH,
+            (1, false) => EVENT_SINH,
+            (0, false) => EVENT_UINH,
+            (1, true) => EVENT_VSINH,
+            _ => EVENT_VUINH,
+        };
+
+        let mut overflow = false;
+        let mut active = self.active;
+        while active != 0 {
+            let idx = active.trailing_zeros() as usize;
+            active &= active - 1;
+
+            let total = event_total(stats, idx, self.events[idx]);
+            let delta = total.wrapping_sub(self.last[idx]);
+            self.last[idx] = total;
+            if self.inhibit & (1 << idx) != 0 || self.events[idx] & mode_inhibit != 0 {
+                continue;
+            }
+
+            let (count, wrapped) = self.counters[idx].overflowing_add(delta);
+            self.counters[idx] = count;
+            if wrapped && idx >= FIRST_HPM && self.events[idx] & EVENT_OF == 0 {
+                self.events[idx] |= EVENT_OF;
+                overflow = true;
+            }
+        }
+        overflow
+    }
+
+    pub fn read_inhibit(&self) -> u64 {
+        self.inhibit
+    }
+
+    pub fn write_inhibit(&mut self, val: u64) {
+        self.inhibit = val & INHIBIT_MASK;
+    }
+}
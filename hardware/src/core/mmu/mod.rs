@@ -5,130 +5,183 @@ use crate::system::Bus;
 
 use self::tlb::Tlb;
 
+pub mod pmp;
 pub mod tlb;
 
 pub struct Mmu {
     pub dtlb: Tlb,
     pub itlb: Tlb,
+    /// Guest virtual to host physical translations, flushed by HFENCE and by
+    /// SFENCE.VMA executed in VS-mode
+    pub guest_dtlb: Tlb,
+    pub guest_itlb: Tlb,
 }
 
-impl Mmu {
-    pub fn new(tlb_size: usize) -> Self {
-        Self {
-            dtlb: Tlb::new(tlb_size),
-            itlb: Tlb::new(tlb_size),
-        }
+/// The mode an access is performed in.
+#[derive(Clone, Copy)]
+pub struct AccessContext {
+    pub privilege: u8,
+    /// Guest accesses translate through vsatp and then hgatp
+    pub virt: bool,
+    /// HLVX loads need execute rather than read permission
+    pub hlvx: bool,
+}
+
+/// Leaf bits standing in for a bare stage, which allows every access.
+const BARE_PTE: u64 = 0x1F;
+const PTE_RWX: u64 = 0xE;
+
+fn page_fault(access: AccessType, vaddr: VirtAddr) -> Trap {
+    match access {
+        AccessType::Fetch => Trap::InstructionPageFault(vaddr.val()),
+        AccessType::Write => Trap::StorePageFault(vaddr.val()),
+        AccessType::Read => Trap::LoadPageFault(vaddr.val()),
     }
+}
 
-    pub fn translate(
-        &mut self,
-        vaddr: VirtAddr,
-        access: AccessType,
-        privilege: u8,
-        csrs: &Csrs,
-        bus: &mut Bus,
-    ) -> TranslationResult {
-        let satp = csrs.satp;
-        let mode = (satp >> csr::SATP_MODE_SHIFT) & 0xF;
+pub(crate) fn access_fault(access: AccessType, addr: u64) -> Trap {
+    match access {
+        AccessType::Fetch => Trap::InstructionAccessFault(addr),
+        AccessType::Write => Trap::StoreAccessFault(addr),
+        AccessType::Read => Trap::LoadAccessFault(addr),
+    }
+}
 
-        if privilege == 3 || mode == 0 {
-            return TranslationResult::success(PhysAddr::new(vaddr.val()), 0);
-        }
+fn guest_page_fault(access: AccessType, gva: u64, gpa: u64) -> Trap {
+    match access {
+        AccessType::Fetch => Trap::InstructionGuestPageFault(gva, gpa),
+        AccessType::Write => Trap::StoreGuestPageFault(gva, gpa),
+        AccessType::Read => Trap::LoadGuestPageFault(gva, gpa),
+    }
+}
 
-        if mode != csr::SATP_MODE_SV39 {
-            return TranslationResult::fault(Trap::InstructionAccessFault(vaddr.val()), 0);
-        }
+/// Checks the R/W/X/U bits of a leaf PTE against the access and privilege.
+/// `status` supplies SUM and MXR: mstatus, or vsstatus for the VS stage.
+fn permitted(
+    access: AccessType,
+    privilege: u8,
+    status: u64,
+    hlvx: bool,
+    (r, w, x, u): (bool, bool, bool, bool),
+) -> bool {
+    let allowed = match access {
+        AccessType::Fetch => x,
+        AccessType::Write => w,
+        AccessType::Read if hlvx => x,
+        AccessType::Read => r || (x && (status & csr::MSTATUS_MXR) != 0),
+    };
+    if !allowed {
+        return false;
+    }
+
+    match privilege {
+        0 => u,
+        // S-mode may never execute user pages; SUM only opens them to loads and stores
+        1 if u => access != AccessType::Fetch && (status & csr::MSTATUS_SUM) != 0,
+        _ => true,
+    }
+}
 
-        let vpn = vaddr.vpn2() << 18 | vaddr.vpn1() << 9 | vaddr.vpn0();
+/// One page-table format, rooted at a physical or guest physical address.
+struct PageTable {
+    root: u64,
+    levels: u32,
+    /// Extra index bits at the root level: 2 for the Sv39x4 G stage
+    root_bits: u32,
+}
 
-        let tlb_entry = if access == AccessType::Fetch {
-            self.itlb.lookup(vpn)
+impl PageTable {
+    fn index(&self, addr: u64, level: u32) -> u64 {
+        let bits = if level == self.levels - 1 {
+            9 + self.root_bits
         } else {
-            self.dtlb.lookup(vpn)
+            9
         };
+        (addr >> (12 + 9 * level)) & ((1 << bits) - 1)
+    }
+}
 
-        if let Some((ppn, r, w, x, u)) = tlb_entry {
-            // Check Permissions
-            if access == AccessType::Fetch && !x {
-                return TranslationResult::fault(Trap::InstructionPageFault(vaddr.val()), 0);
-            }
-            if access == AccessType::Write && !w {
-                return TranslationResult::fault(Trap::StorePageFault(vaddr.val()), 0);
-            }
-            if access == AccessType::Read && !r {
-                let mxr = (csrs.sstatus & csr::MSTATUS_MXR) != 0;
-                if !mxr || !x {
-                    return TranslationResult::fault(Trap::LoadPageFault(vaddr.val()), 0);
-                }
-            }
+/// Why a walk failed; the caller turns this into a trap for the original access.
+enum WalkError {
+    /// Invalid or non-permitting PTE
+    Page,
+    /// PMP refused a page-table access
+    Access,
+    /// The G stage refused this guest physical address
+    Guest(u64),
+}
 
-            if privilege == 0 && !u {
-                let fault = match access {
-                    AccessType::Fetch => Trap::InstructionPageFault(vaddr.val()),
-                    AccessType::Write => Trap::StorePageFault(vaddr.val()),
-                    AccessType::Read => Trap::LoadPageFault(vaddr.val()),
-                };
-                return TranslationResult::fault(fault, 0);
-            }
+struct Walker<'a> {
+    csrs: &'a Csrs,
+    bus: &'a mut Bus,
+    cycles: u64,
+}
 
-            if privilege == 1 && u {
-                let sum = (csrs.sstatus & csr::MSTATUS_SUM) != 0;
-                if !sum {
-                    let fault = match access {
-                        AccessType::Fetch => Trap::InstructionPageFault(vaddr.val()),
-                        AccessType::Write => Trap::StorePageFault(vaddr.val()),
-                        AccessType::Read => Trap::LoadPageFault(vaddr.val()),
-                    };
-                    return TranslationResult::fault(fault, 0);
-                }
-            }
+impl Walker<'_> {
+    /// Locates a PTE in host memory. VS-stage tables live in guest physical
+    /// memory, so their PTE addresses go through the G stage first.
+    fn pte_location(
+        &mut self,
+        pte_addr: u64,
+        access: AccessType,
+        nested: bool,
+    ) -> Result<u64, WalkError> {
+        let paddr = if nested {
+            self.g_stage(pte_addr, access, false)?.0
+        } else {
+            pte_addr
+        };
 
-            let paddr = (ppn << 12) | vaddr.page_offset();
-            return TranslationResult::success(PhysAddr::new(paddr), 0);
+        // Walks are implicit S-mode accesses, so PMP applies to them as well
+        if !self.csrs.pmp.check(paddr, 8, access, 1) {
+            return Err(WalkError::Access);
         }
+        Ok(paddr)
+    }
 
-        // Page Table Walk
-        let root_ppn = satp & 0xFFF_FFFF_FFFF;
-        let mut pt_addr = PhysAddr::new(root_ppn << 12);
-        let mut cycles = 0;
-
-        for level in (0..3).rev() {
-            let vpn_i = match level {
-                2 => vaddr.vpn2(),
-                1 => vaddr.vpn1(),
-                _ => vaddr.vpn0(),
-            };
+    /// Walks `table` for `addr`, vetting the leaf with `check`. Returns the
+    /// 4 KiB frame covering `addr` and the leaf PTE.
+    fn walk(
+        &mut self,
+        table: &PageTable,
+        addr: u64,
+        access: AccessType,
+        nested: bool,
+        check: &dyn Fn((bool, bool, bool, bool)) -> bool,
+    ) -> Result<(u64, u64), WalkError> {
+        let mut pt_addr = table.root;
 
-            let pte_addr = pt_addr.val() + (vpn_i * 8);
+        for level in (0..table.levels).rev() {
+            let pte_addr = pt_addr + table.index(addr, level) * 8;
+            let pte_paddr = self.pte_location(pte_addr, AccessType::Read, nested)?;
 
             // We bypass CPU cache simulation here for simplicity in this step
-            cycles += bus.calculate_transit_time(8);
-
-            let pte = bus.read_u64(pte_addr);
+            self.cycles += self.bus.calculate_transit_time(8);
 
-            if (pte & 1) == 0 {
-                let fault = match access {
-                    AccessType::Fetch => Trap::InstructionPageFault(vaddr.val()),
-                    AccessType::Write => Trap::StorePageFault(vaddr.val()),
-                    AccessType::Read => Trap::LoadPageFault(vaddr.val()),
-                };
-                return TranslationResult::fault(fault, cycles);
-            }
+            let pte = self.bus.read_u64(pte_paddr);
 
+            let v = pte & 1 != 0;
             let r = (pte >> 1) & 1 != 0;
             let w = (pte >> 2) & 1 != 0;
             let x = (pte >> 3) & 1 != 0;
+            let u = (pte >> 4) & 1 != 0;
 
-            if !r && !w && !x {
+            if !v || (w && !r) {
+                return Err(WalkError::Page);
+            }
+
+            let pte_ppn = (pte >> 10) & csr::SATP_PPN_MASK;
+
+            if !r && !x {
                 // Pointer to next level
-                let next_ppn = (pte >> 10) & 0xFFF_FFFF_FFFF;
-                pt_addr = PhysAddr::new(next_ppn << 12);
+                pt_addr = pte_ppn << 12;
                 continue;
             }
 
-            // Leaf found
-            if w && !r {
-                return TranslationResult::fault(Trap::StorePageFault(vaddr.val()), cycles);
+            // Leaf found; a superpage must be aligned to its size
+            let superpage_mask = (1 << (9 * level)) - 1;
+            if pte_ppn & superpage_mask != 0 || !check((r, w, x, u)) {
+                return Err(WalkError::Page);
             }
 
             // A/D Bit Updates
@@ -147,29 +200,186 @@ impl Mmu {
             }
 
             if update {
-                bus.write_u64(pte_addr, new_pte);
-                cycles += 10;
+                let pte_paddr = self.pte_location(pte_addr, AccessType::Write, nested)?;
+                self.bus.write_u64(pte_paddr, new_pte);
+                self.cycles += 10;
             }
 
-            let pte_ppn = (pte >> 10) & 0xFFF_FFFF_FFFF;
-            let offset_mask = (1 << (12 + 9 * level)) - 1;
-            let final_paddr = (pte_ppn << 12) | (vaddr.val() & offset_mask);
+            // The TLB holds 4 KiB translations, so a superpage contributes the
+            // frame covering this particular page
+            return Ok((pte_ppn | ((addr >> 12) & superpage_mask), new_pte));
+        }
 
-            // Refill TLB
-            if access == AccessType::Fetch {
-                self.itlb.insert(vpn, pte_ppn, new_pte);
-            } else {
-                self.dtlb.insert(vpn, pte_ppn, new_pte);
+        // Ran out of levels without reaching a leaf
+        Err(WalkError::Page)
+    }
+
+    /// Translates a guest physical address through hgatp, returning the host
+    /// physical address and the leaf PTE.
+    fn g_stage(
+        &mut self,
+        gpa: u64,
+        access: AccessType,
+        hlvx: bool,
+    ) -> Result<(u64, u64), WalkError> {
+        let hgatp = self.csrs.hgatp;
+        if hgatp >> csr::SATP_MODE_SHIFT == csr::SATP_MODE_BARE {
+            return Ok((gpa, BARE_PTE));
+        }
+
+        // Sv39x4 widens guest physical addresses to 41 bits
+        if gpa >> 41 != 0 {
+            return Err(WalkError::Guest(gpa));
+        }
+
+        let table = PageTable {
+            root: (hgatp & csr::HGATP_PPN_MASK) << 12,
+            levels: 3,
+            root_bits: 2,
+        };
+        // G-stage leaves must be user pages; they are checked as U-mode accesses
+        let mstatus = self.csrs.mstatus;
+        let check = |perms| permitted(access, 0, mstatus, hlvx, perms);
+        match self.walk(&table, gpa, access, false, &check) {
+            Ok((ppn, pte)) => Ok(((ppn << 12) | (gpa & 0xFFF), pte)),
+            Err(WalkError::Page) => Err(WalkError::Guest(gpa)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Mmu {
+    pub fn new(tlb_size: usize) -> Self {
+        Self {
+            dtlb: Tlb::new(tlb_size),
+            itlb: Tlb::new(tlb_size),
+            guest_dtlb: Tlb::new(tlb_size),
+            guest_itlb: Tlb::new(tlb_size),
+        }
+    }
+
+    pub fn translate(
+        &mut self,
+        vaddr: VirtAddr,
+        access: AccessType,
+        ctx: AccessContext,
+        csrs: &Csrs,
+        bus: &mut Bus,
+    ) -> TranslationResult {
+        let (atp, status) = if ctx.virt {
+            // mstatus.MXR also applies to the VS stage
+            let mxr = csrs.mstatus & csr::MSTATUS_MXR;
+            (csrs.vsatp, csrs.vsstatus | mxr)
+        } else {
+            (csrs.satp, csrs.mstatus)
+        };
+        let mode = (atp >> csr::SATP_MODE_SHIFT) & 0xF;
+        let g_bare = csrs.hgatp >> csr::SATP_MODE_SHIFT == csr::SATP_MODE_BARE;
+
+        let untranslated = if ctx.virt {
+            mode == csr::SATP_MODE_BARE && g_bare
+        } else {
+            ctx.privilege == 3 || mode == csr::SATP_MODE_BARE
+        };
+        if untranslated {
+            return TranslationResult::success(PhysAddr::new(vaddr.val()), 0);
+        }
+
+        // A bare vsatp leaves guest addresses to the G stage alone
+        let levels = match mode {
+            csr::SATP_MODE_BARE => 0,
+            csr::SATP_MODE_SV39 => 3,
+            csr::SATP_MODE_SV48 => 4,
+            csr::SATP_MODE_SV57 => 5,
+            _ => return TranslationResult::fault(page_fault(access, vaddr), 0),
+        };
+
+        let vpn = if levels > 0 {
+            // Bits above the virtual address width must all equal its top bit
+            let va_bits = 12 + 9 * levels;
+            let shift = 64 - va_bits;
+            if (((vaddr.val() << shift) as i64) >> shift) as u64 != vaddr.val() {
+                return TranslationResult::fault(page_fault(access, vaddr), 0);
             }
+            (vaddr.val() >> 12) & ((1 << (va_bits - 12)) - 1)
+        } else {
+            vaddr.val() >> 12
+        };
 
-            return TranslationResult::success(PhysAddr::new(final_paddr), cycles);
+        let tlb = match (ctx.virt, access) {
+            (false, AccessType::Fetch) => &mut self.itlb,
+            (false, _) => &mut self.dtlb,
+            (true, AccessType::Fetch) => &mut self.guest_itlb,
+            (true, _) => &mut self.guest_dtlb,
+        };
+
+        if let Some((ppn, r, w, x, u)) = tlb.lookup(vpn) {
+            // Guest entries merge both stages; the U bit is the VS stage's,
+            // and is meaningless when that stage is bare
+            let privilege = if levels == 0 { 3 } else { ctx.privilege };
+            if permitted(access, privilege, status, ctx.hlvx, (r, w, x, u)) {
+                let paddr = (ppn << 12) | vaddr.page_offset();
+                return TranslationResult::success(PhysAddr::new(paddr), 0);
+            }
+            // For guests, walking again tells which stage refuses the access
+            if !ctx.virt {
+                return TranslationResult::fault(page_fault(access, vaddr), 0);
+            }
         }
 
-        let fault = match access {
-            AccessType::Fetch => Trap::InstructionPageFault(vaddr.val()),
-            AccessType::Write => Trap::StorePageFault(vaddr.val()),
-            AccessType::Read => Trap::LoadPageFault(vaddr.val()),
+        let mut walker = Walker {
+            csrs,
+            bus,
+            cycles: 0,
         };
-        TranslationResult::fault(fault, cycles)
+        let table = PageTable {
+            root: (atp & csr::SATP_PPN_MASK) << 12,
+            levels,
+            root_bits: 0,
+        };
+        let check = |perms| permitted(access, ctx.privilege, status, ctx.hlvx, perms);
+
+        let result = if !ctx.virt {
+            walker.walk(&table, vaddr.val(), access, false, &check)
+        } else {
+            let vs_stage = if levels > 0 {
+                walker.walk(&table, vaddr.val(), access, true, &check)
+            } else {
+                Ok((vpn, BARE_PTE))
+            };
+
+            vs_stage.and_then(|(gpa_ppn, vs_pte)| {
+                let gpa = (gpa_ppn << 12) | vaddr.page_offset();
+                let (paddr, g_pte) = walker.g_stage(gpa, access, ctx.hlvx)?;
+                // Cache only the permissions both stages grant
+                let pte = (vs_pte & !PTE_RWX) | (vs_pte & g_pte & PTE_RWX);
+                Ok((paddr >> 12, pte))
+            })
+        };
+
+        let cycles = walker.cycles;
+        let mut res = match result {
+            Ok((page_ppn, pte)) => {
+                // Refill TLB
+                tlb.insert(vpn, page_ppn, pte);
+                let paddr = (page_ppn << 12) | vaddr.page_offset();
+                TranslationResult::success(PhysAddr::new(paddr), cycles)
+            }
+            Err(WalkError::Page) => TranslationResult::fault(page_fault(access, vaddr), cycles),
+            Err(WalkError::Access) => {
+                TranslationResult::fault(access_fault(access, vaddr.val()), cycles)
+            }
+            Err(WalkError::Guest(gpa)) => {
+                TranslationResult::fault(guest_page_fault(access, vaddr.val(), gpa), cycles)
+            }
+        };
+        res.tlb_miss = true;
+        res
+    }
+
+    /// Drops guest translations, for HFENCE and for SFENCE.VMA in VS-mode.
+    pub fn flush_guest(&mut self) {
+        self.guest_dtlb.flush();
+        self.guest_itlb.flush();
     }
 }
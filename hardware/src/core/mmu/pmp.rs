@@ -0,0 +1,133 @@
+use crate::core::types::AccessType;
+
+// pmpcfg entry fields
+const PMP_R: u8 = 1 << 0;
+const PMP_W: u8 = 1 << 1;
+const PMP_X: u8 = 1 << 2;
+const PMP_A_SHIFT: u8 = 3;
+const PMP_A: u8 = 3 << PMP_A_SHIFT;
+const PMP_L: u8 = 1 << 7;
+
+// Address-matching modes held in the A field
+const MODE_OFF: u8 = 0;
+const MODE_TOR: u8 = 1;
+const MODE_NA4: u8 = 2;
+const MODE_NAPOT: u8 = 3;
+
+/// pmpaddr holds bits 55:2 of a physical address.
+const PMPADDR_MASK: u64 = (1 << 54) - 1;
+
+pub const MAX_ENTRIES: usize = 64;
+
+/// Physical Memory Protection entries with a 4-byte grain.
+#[derive(Default)]
+pub struct Pmp {
+    cfg: Vec<u8>,
+    addr: Vec<u64>,
+}
+
+impl Pmp {
+    pub fn new(entries: usize) -> Self {
+        let entries = entries.min(MAX_ENTRIES);
+        Self {
+            cfg: vec![0; entries],
+            addr: vec![0; entries],
+        }
+    }
+
+    fn mode(&self, i: usize) -> u8 {
+        self.cfg
+            .get(i)
+            .map_or(MODE_OFF, |c| (c & PMP_A) >> PMP_A_SHIFT)
+    }
+
+    fn locked(&self, i: usize) -> bool {
+        self.cfg.get(i).is_some_and(|c| c & PMP_L != 0)
+    }
+
+    /// Reads pmpcfg`reg`; on RV64 each even-numbered register packs eight entries.
+    pub fn read_cfg(&self, reg: usize) -> u64 {
+        (0..8).fold(0, |acc, j| {
+            let cfg = self.cfg.get(reg * 4 + j).copied().unwrap_or(0);
+            acc | (cfg as u64) << (8 * j)
+        })
+    }
+
+    pub fn write_cfg(&mut self, reg: usize, val: u64) {
+        for j in 0..8 {
+            let i = reg * 4 + j;
+            if i >= self.cfg.len() || self.locked(i) {
+                continue;
+            }
+            let mut cfg = (val >> (8 * j)) as u8 & (PMP_L | PMP_A | PMP_X | PMP_W | PMP_R);
+            // W without R is reserved
+            if cfg & PMP_R == 0 {
+                cfg &= !PMP_W;
+            }
+            self.cfg[i] = cfg;
+        }
+    }
+
+    pub fn read_addr(&self, i: usize) -> u64 {
+        self.addr.get(i).copied().unwrap_or(0)
+    }
+
+    pub fn write_addr(&mut self, i: usize, val: u64) {
+        // A locked TOR entry also freezes the pmpaddr below it, which is its base
+        let tor_locked = self.locked(i + 1) && self.mode(i + 1) == MODE_TOR;
+        if i < self.addr.len() && !self.locked(i) && !tor_locked {
+            self.addr[i] = val & PMPADDR_MASK;
+        }
+    }
+
+    /// Returns the byte range `[start, end)` matched by entry `i`, if any.
+    fn range(&self, i: usize) -> Option<(u64, u64)> {
+        let addr = self.addr[i];
+        match self.mode(i) {
+            MODE_TOR => {
+                let start = if i == 0 { 0 } else { self.addr[i - 1] << 2 };
+                let end = addr << 2;
+                (start < end).then_some((start, end))
+            }
+            MODE_NA4 => Some((addr << 2, (addr << 2) + 4)),
+            MODE_NAPOT => {
+                let size = 1 << (addr.trailing_ones() + 3);
+                let start = (addr << 2) & !(size - 1);
+                Some((start, start + size))
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks an access of `size` bytes at `paddr`. The lowest-numbered entry
+    /// that matches any byte decides, and it must cover the whole access.
+    pub fn check(&self, paddr: u64, size: u64, access: AccessType, privilege: u8) -> bool {
+        let end = paddr.saturating_add(size);
+
+        for (i, &cfg) in self.cfg.iter().enumerate() {
+            let Some((start, stop)) = self.range(i) else {
+                continue;
+            };
+            if end <= start || paddr >= stop {
+                continue;
+            }
+            if paddr < start || end > stop {
+                return false;
+            }
+
+            // M-mode is only held to locked entries
+            if privilege == 3 && cfg & PMP_L == 0 {
+                return true;
+            }
+            let perm = match access {
+                AccessType::Fetch => PMP_X,
+                AccessType::Read => PMP_R,
+                AccessType::Write => PMP_W,
+            };
+            return cfg & perm != 0;
+        }
+
+        // Unmatched accesses succeed only in M-mode, or when no entries are implemented
+        privilege == 3 || self.cfg.is_empty()
+    }
+}
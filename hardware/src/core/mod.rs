@@ -2,11 +2,19 @@ pub mod bp;
 pub mod cache;
 pub mod control;
 pub mod cpu;
+pub mod crypto;
+pub mod fpu;
+pub mod hpm;
 pub mod mmu;
+pub mod ooo;
 pub mod pipeline;
 pub mod prefetch;
 pub mod register_file;
+pub mod scoreboard;
 pub mod stages;
+pub mod trigger;
 pub mod types;
+pub mod units;
+pub mod vpu;
 
 pub use self::cpu::Cpu;
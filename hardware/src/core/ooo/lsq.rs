@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+
+use super::{RobEntry, State};
+
+const WAIT_TABLE_SIZE: usize = 4096;
+
+/// The memory footprint of a load, store or ordered access, known once the
+/// instruction has executed functionally at dispatch.
+#[derive(Clone, Copy, Debug)]
+pub struct MemAccess {
+    pub addr: u64,
+    pub size: u64,
+    pub load: bool,
+    pub store: bool,
+    /// Atomics, cache-block operations and vector accesses issue only from the
+    /// ROB head, and no younger load passes them
+    pub ordered: bool,
+}
+
+impl MemAccess {
+    fn overlaps(&self, other: &MemAccess) -> bool {
+        self.addr < other.addr.wrapping_add(other.size)
+            && other.addr < self.addr.wrapping_add(self.size)
+    }
+
+    fn covers(&self, other: &MemAccess) -> bool {
+        self.addr <= other.addr
+            && other.addr.wrapping_add(other.size) <= self.addr.wrapping_add(self.size)
+    }
+}
+
+/// Where an issuing load gets its data.
+pub enum LoadSource {
+    Cache,
+    /// Forwarded from an older store, available once that store has completed
+    Forward {
+        seq: u64,
+        ready: u64,
+    },
+}
+
+/// Memory dependence predictor: loads that once overtook a store they depended
+/// on wait for every older store address from then on.
+pub struct WaitTable {
+    entries: Vec<bool>,
+}
+
+impl Default for WaitTable {
+    fn default() -> Self {
+        Self {
+            entries: vec![false; WAIT_TABLE_SIZE],
+        }
+    }
+}
+
+impl WaitTable {
+    fn index(pc: u64) -> usize {
+        (pc >> 1) as usize % WAIT_TABLE_SIZE
+    }
+
+    pub fn predicts_dependence(&self, pc: u64) -> bool {
+        self.entries[Self::index(pc)]
+    }
+
+    pub fn train(&mut self, pc: u64) {
+        self.entries[Self::index(pc)] = true;
+    }
+}
+
+/// Decides whether the load at `idx` may issue at `now`, searching the older
+/// stores from youngest to oldest. A fully covering store that has issued
+/// forwards its data; a partially overlapping one holds the load until it
+/// commits. Stores whose address is still unknown are passed only when
+/// `speculate` is set.
+pub fn load_source(
+    rob: &VecDeque<RobEntry>,
+    idx: usize,
+    now: u64,
+    speculate: bool,
+) -> Option<LoadSource> {
+    let load = rob[idx].mem?;
+    for older in rob.range(..idx).rev() {
+        let Some(mem) = older.mem else {
+            continue;
+        };
+        let complete = match older.state {
+            State::Issued { complete } => Some(complete),
+            State::Waiting => None,
+        };
+        if mem.ordered && complete.is_none_or(|c| c > now) {
+            return None;
+        }
+        if !mem.store {
+            continue;
+        }
+        match complete {
+            None if speculate => continue,
+            None => return None,
+            Some(_) if !mem.overlaps(&load) => continue,
+            Some(ready) if mem.covers(&load) => {
+                return Some(LoadSource::Forward {
+                    seq: older.seq,
+                    ready,
+                });
+            }
+            Some(_) => return None,
+        }
+    }
+    Some(LoadSource::Cache)
+}
+
+/// Finds the oldest load younger than the store at `idx` that has already
+/// issued with data the store should have supplied.
+pub fn find_violation(rob: &VecDeque<RobEntry>, idx: usize) -> Option<usize> {
+    let store = rob[idx].mem?;
+    let seq = rob[idx].seq;
+    rob.iter()
+        .enumerate()
+        .skip(idx + 1)
+        .find(|(_, e)| {
+            matches!(e.state, State::Issued { .. })
+                && e.mem
+                    .is_some_and(|m| m.load && !m.ordered && m.overlaps(&store))
+                && e.forwarded_from.is_none_or(|from| from < seq)
+        })
+        .map(|(i, _)| i)
+}
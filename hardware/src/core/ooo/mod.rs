@@ -0,0 +1,609 @@
+pub mod lsq;
+pub mod rename;
+
+use std::collections::VecDeque;
+
+use self::lsq::{LoadSource, MemAccess, WaitTable};
+use self::rename::{PhysReg, RenameTable};
+use crate::config::{IssueQueue, OooConfig};
+use crate::core::Cpu;
+use crate::core::control::{self, AtomicOp, CacheOp, ControlSignals};
+use crate::core::pipeline::{ExMem, ExMemEntry, IdEx, IdExEntry, MemWb};
+use crate::core::stages;
+use crate::core::units::{FuClass, FunctionalUnits, IssuePorts, Port};
+use crate::core::vpu::VecOp;
+use crate::isa::abi;
+
+/// Cycles a load and everything younger wait before reissuing after a
+/// memory-order violation, standing in for the refetch a real core performs.
+const REPLAY_PENALTY: u64 = 4;
+
+/// Issue queues: integer (ALU, branch and system), memory, and FP/vector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Queue {
+    Int,
+    Mem,
+    Fp,
+}
+
+impl Queue {
+    fn of(ctrl: &ControlSignals) -> Self {
+        if ctrl.mem_read
+            || ctrl.mem_write
+            || ctrl.cache_op != CacheOp::None
+            || ctrl.vec_op.is_memory()
+        {
+            Self::Mem
+        } else if ctrl.vec_op != VecOp::None
+            || ctrl.fp_reg_write
+            || ctrl.rs1_fp
+            || ctrl.rs2_fp
+            || ctrl.rs3_fp
+        {
+            Self::Fp
+        } else {
+            Self::Int
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Waiting,
+    Issued { complete: u64 },
+}
+
+/// When fetch may resume after the instruction `seq` redirected it.
+#[derive(Clone, Copy, Debug)]
+enum Resume {
+    /// A mispredicted branch or jump, once it has executed
+    Complete(u64),
+    /// A trap or serializing instruction, once it has committed
+    Commit(u64),
+}
+
+/// Resources whose exhaustion holds up dispatch.
+enum Stall {
+    Rob,
+    IssueQueue,
+    Lsq,
+    Regs,
+}
+
+pub struct RobEntry {
+    seq: u64,
+    pc: u64,
+    queue: Queue,
+    srcs: Vec<PhysReg>,
+    /// The destination register and the mapping it replaced, freed at commit
+    dest: Option<(PhysReg, usize)>,
+    /// An older instruction that must complete first. The vector registers are
+    /// not renamed, so vector instructions execute in program order.
+    after: Option<u64>,
+    /// The functional unit it occupies when it issues
+    unit: Option<FuClass>,
+    ports: Vec<Port>,
+    /// Execution latency, or for memory accesses the cache latency that
+    /// follows address generation
+    latency: u64,
+    mem: Option<MemAccess>,
+    /// The store a load took its data from
+    forwarded_from: Option<u64>,
+    state: State,
+    not_before: u64,
+}
+
+/// Out-of-order back-end behind the shared fetch stage and branch predictors.
+///
+/// Instructions execute functionally in program order as they dispatch,
+/// through the same decode, execute, memory and write-back logic as the
+/// in-order pipeline, so architectural state and traps are always precise.
+/// The ROB, rename tables, issue queues and load/store queues then decide when
+/// each instruction issues, completes and commits. Fetch stops behind a
+/// mispredicted branch until it executes, and behind a trap or serializing
+/// instruction until it commits; wrong-path instructions are never dispatched.
+pub struct OooCore {
+    rob: VecDeque<RobEntry>,
+    rob_size: usize,
+    next_seq: u64,
+    int_regs: RenameTable,
+    fp_regs: RenameTable,
+    distributed: bool,
+    iq_size: usize,
+    iq_used: [usize; 3],
+    lq_size: usize,
+    sq_size: usize,
+    lq_used: usize,
+    sq_used: usize,
+    issue_width: usize,
+    commit_width: usize,
+    speculative_loads: bool,
+    wait_table: WaitTable,
+    last_vector: Option<u64>,
+    /// Cycles until the fetched group arrives from the instruction cache
+    fetch_busy: u64,
+    resume: Option<Resume>,
+}
+
+/// The access a load, store or ordered memory operation makes, or `None`.
+fn mem_access(ex: &ExMemEntry) -> Option<MemAccess> {
+    let ctrl = &ex.ctrl;
+    let ordered = ctrl.atomic_op != AtomicOp::None
+        || ctrl.cache_op != CacheOp::None
+        || ctrl.vec_op.is_memory();
+    if ex.trap.is_some() || !(ctrl.mem_read || ctrl.mem_write || ordered) {
+        return None;
+    }
+    Some(MemAccess {
+        addr: ex.alu,
+        size: ctrl.width.bytes().max(1),
+        load: ctrl.mem_read,
+        store: ctrl.mem_write,
+        ordered,
+    })
+}
+
+/// Cache-block operations are neither loads nor stores but hold a store queue entry.
+fn uses_store_queue(load: bool, store: bool) -> bool {
+    store || !load
+}
+
+impl OooCore {
+    pub fn new(config: &OooConfig) -> Self {
+        Self {
+            rob: VecDeque::with_capacity(config.rob_size),
+            rob_size: config.rob_size.max(1),
+            next_seq: 0,
+            int_regs: RenameTable::new(config.int_phys_regs),
+            fp_regs: RenameTable::new(config.fp_phys_regs),
+            distributed: config.issue_queue == IssueQueue::Distributed,
+            iq_size: config.iq_size.max(1),
+            iq_used: [0; 3],
+            lq_size: config.load_queue.max(1),
+            sq_size: config.store_queue.max(1),
+            lq_used: 0,
+            sq_used: 0,
+            issue_width: config.issue_width.max(1),
+            commit_width: config.commit_width.max(1),
+            speculative_loads: config.speculative_loads,
+            wait_table: WaitTable::default(),
+            last_vector: None,
+            fetch_busy: 0,
+            resume: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rob.is_empty()
+    }
+
+    pub fn print_occupancy(&self) {
+        eprintln!(
+            "ROB:{} IQ:{} LQ:{} SQ:{}",
+            self.rob.len(),
+            self.iq_used.iter().sum::<usize>(),
+            self.lq_used,
+            self.sq_used
+        );
+    }
+
+    fn committed(&self, seq: u64) -> bool {
+        self.rob.front().is_none_or(|head| head.seq > seq)
+    }
+
+    fn completed(&self, seq: u64, now: u64) -> bool {
+        if self.committed(seq) {
+            return true;
+        }
+        let idx = (seq - self.rob[0].seq) as usize;
+        matches!(self.rob[idx].state, State::Issued { complete } if complete <= now)
+    }
+
+    fn regs(&mut self, fp: bool) -> &mut RenameTable {
+        if fp {
+            &mut self.fp_regs
+        } else {
+            &mut self.int_regs
+        }
+    }
+
+    fn cycle(
+        &mut self,
+        cpu: &mut Cpu,
+        units: &mut FunctionalUnits,
+        ports: &mut IssuePorts,
+    ) -> Result<(), String> {
+        cpu.stats.cycles += 1;
+        cpu.track_mode_cycles(1);
+        cpu.stats.rob_occupancy += self.rob.len() as u64;
+        let now = cpu.stats.cycles;
+
+        self.commit(now);
+        self.issue(cpu, units, ports, now);
+        self.dispatch(cpu, units)?;
+        if cpu.exit_code.is_some() {
+            return Ok(());
+        }
+        self.fetch(cpu, now)?;
+
+        cpu.regs.write(abi::REG_ZERO, 0);
+        Ok(())
+    }
+
+    /// Retires completed instructions from the ROB head in program order,
+    /// freeing the registers and queue entries they no longer need.
+    fn commit(&mut self, now: u64) {
+        for _ in 0..self.commit_width {
+            let Some(head) = self.rob.front() else {
+                break;
+            };
+            if !matches!(head.state, State::Issued { complete } if complete <= now) {
+                break;
+            }
+            let Some(entry) = self.rob.pop_front() else {
+                break;
+            };
+            if let Some((preg, old)) = entry.dest {
+                self.regs(preg.fp).release(old);
+            }
+            if let Some(mem) = entry.mem {
+                if mem.load {
+                    self.lq_used -= 1;
+                }
+                if uses_store_queue(mem.load, mem.store) {
+                    self.sq_used -= 1;
+                }
+            }
+        }
+    }
+
+    /// Issues up to `issue_width` ready instructions, oldest first, each on a
+    /// free functional unit and issue port.
+    fn issue(
+        &mut self,
+        cpu: &mut Cpu,
+        units: &mut FunctionalUnits,
+        ports: &mut IssuePorts,
+        now: u64,
+    ) {
+        let mut issued = 0;
+        let mut busy = [false; FuClass::COUNT];
+        let mut conflicts = false;
+        ports.reset();
+        for idx in 0..self.rob.len() {
+            if issued == self.issue_width {
+                break;
+            }
+            let Some((complete, forwarded_from)) = self.ready(idx, now) else {
+                continue;
+            };
+            let unit = self.rob[idx].unit;
+            if let Some(class) = unit
+                && !units.available(class, now)
+            {
+                busy[class as usize] = true;
+                continue;
+            }
+            if !ports.claim(&self.rob[idx].ports) {
+                cpu.stats.port_conflicts += 1;
+                conflicts = true;
+                continue;
+            }
+            if let Some(class) = unit {
+                units.issue(class, now);
+            }
+            issued += 1;
+
+            let entry = &mut self.rob[idx];
+            entry.state = State::Issued { complete };
+            entry.forwarded_from = forwarded_from;
+            let (queue, dest, mem) = (entry.queue, entry.dest, entry.mem);
+            self.iq_used[queue as usize] -= 1;
+            if let Some((preg, _)) = dest {
+                self.regs(preg.fp).set_ready(preg.idx, complete);
+            }
+            if forwarded_from.is_some() {
+                cpu.stats.load_forwards += 1;
+            }
+
+            // A store that finds a younger load already issued past it replays that load
+            if mem.is_some_and(|m| m.store && !m.ordered)
+                && let Some(violator) = lsq::find_violation(&self.rob, idx)
+            {
+                cpu.stats.mem_order_violations += 1;
+                self.replay(violator, now);
+            }
+        }
+
+        for class in FuClass::ALL {
+            if busy[class as usize] {
+                cpu.stats.stalls_units[class as usize] += 1;
+            }
+        }
+        if ports.is_modelled() {
+            for (total, used) in cpu.stats.ports_used.iter_mut().zip(ports.used()) {
+                *total += used as u64;
+            }
+        }
+
+        // Nothing could issue because every waiting instruction needs an operand
+        let structural = conflicts || busy.contains(&true);
+        if issued == 0 && !structural && self.rob.iter().any(|e| e.state == State::Waiting) {
+            cpu.stats.stalls_data += 1;
+        }
+    }
+
+    /// Returns when the instruction at `idx` would complete if it issued now,
+    /// and the store a load forwards from, or `None` if it cannot issue yet.
+    fn ready(&self, idx: usize, now: u64) -> Option<(u64, Option<u64>)> {
+        let entry = &self.rob[idx];
+        if entry.state != State::Waiting || entry.not_before > now {
+            return None;
+        }
+        let regs_ready = entry.srcs.iter().all(|r| {
+            let regs = if r.fp { &self.fp_regs } else { &self.int_regs };
+            regs.ready_at(r.idx) <= now
+        });
+        if !regs_ready || entry.after.is_some_and(|seq| !self.completed(seq, now)) {
+            return None;
+        }
+
+        let Some(mem) = entry.mem else {
+            return Some((now + entry.latency, None));
+        };
+        if mem.ordered {
+            return (idx == 0).then_some((now + 1 + entry.latency, None));
+        }
+        if !mem.load {
+            // Stores only compute their address and data here; they write at commit
+            return Some((now + 1, None));
+        }
+
+        let speculate = self.speculative_loads && !self.wait_table.predicts_dependence(entry.pc);
+        match lsq::load_source(&self.rob, idx, now, speculate)? {
+            LoadSource::Cache => Some((now + 1 + entry.latency, None)),
+            LoadSource::Forward { seq, ready } => Some((ready.max(now) + 1, Some(seq))),
+        }
+    }
+
+    /// Sends the load at `idx` and everything younger back to the issue queues.
+    fn replay(&mut self, idx: usize, now: u64) {
+        self.wait_table.train(self.rob[idx].pc);
+        for entry in self.rob.range_mut(idx..) {
+            if entry.state != State::Waiting {
+                entry.state = State::Waiting;
+                self.iq_used[entry.queue as usize] += 1;
+                if let Some((preg, _)) = entry.dest {
+                    let regs = if preg.fp {
+                        &mut self.fp_regs
+                    } else {
+                        &mut self.int_regs
+                    };
+                    regs.set_ready(preg.idx, u64::MAX);
+                }
+            }
+            entry.forwarded_from = None;
+            entry.not_before = now + REPLAY_PENALTY;
+        }
+    }
+
+    fn structural_stall(&self, ctrl: &ControlSignals, rd: usize) -> Option<Stall> {
+        let queue = Queue::of(ctrl);
+        let iq_full = if self.distributed {
+            self.iq_used[queue as usize] >= self.iq_size
+        } else {
+            self.iq_used.iter().sum::<usize>() >= self.iq_size
+        };
+        let memory = queue == Queue::Mem;
+
+        if self.rob.len() >= self.rob_size {
+            Some(Stall::Rob)
+        } else if iq_full {
+            Some(Stall::IssueQueue)
+        } else if (ctrl.mem_read && self.lq_used >= self.lq_size)
+            || (memory
+                && uses_store_queue(ctrl.mem_read, ctrl.mem_write)
+                && self.sq_used >= self.sq_size)
+        {
+            Some(Stall::Lsq)
+        } else if (ctrl.fp_reg_write && self.fp_regs.free_count() == 0)
+            || (ctrl.reg_write && rd != 0 && self.int_regs.free_count() == 0)
+        {
+            Some(Stall::Regs)
+        } else {
+            None
+        }
+    }
+
+    /// Renames and dispatches up to `pipeline_width` fetched instructions,
+    /// executing each functionally on its way into the ROB.
+    fn dispatch(&mut self, cpu: &mut Cpu, units: &FunctionalUnits) -> Result<(), String> {
+        if self.fetch_busy > 0 {
+            return Ok(());
+        }
+
+        for _ in 0..cpu.pipeline_width {
+            if cpu.if_id.entries.is_empty() || cpu.exit_code.is_some() {
+                break;
+            }
+
+            // Decode the oldest fetched instruction on its own; nops vanish here
+            let rest = cpu.if_id.entries.split_off(1);
+            let fetched = cpu.if_id.entries[0].clone();
+            stages::decode::decode_stage(cpu)?;
+            let Some(id) = cpu.id_ex.entries.first().cloned() else {
+                cpu.if_id.entries = rest;
+                continue;
+            };
+
+            // Serializing instructions wait for the ROB to drain
+            let serializing = id.ctrl.is_system || id.ctrl.is_fence || id.ctrl.is_fence_i;
+            let stall = self.structural_stall(&id.ctrl, id.rd);
+            if stall.is_some() || (serializing && !self.rob.is_empty()) {
+                match stall {
+                    Some(Stall::Rob) => cpu.stats.stalls_rob_full += 1,
+                    Some(Stall::IssueQueue) => cpu.stats.stalls_iq_full += 1,
+                    Some(Stall::Lsq) => cpu.stats.stalls_lsq_full += 1,
+                    Some(Stall::Regs) => cpu.stats.stalls_regs_full += 1,
+                    None => {}
+                }
+                cpu.id_ex = IdEx::default();
+                cpu.if_id.entries = std::iter::once(fetched).chain(rest).collect();
+                break;
+            }
+
+            cpu.if_id.entries = rest;
+            let had_rest = !cpu.if_id.entries.is_empty();
+            let fetch_pc = cpu.pc;
+            let Some((mut entry, trapped)) = self.execute(cpu, units, &id)? else {
+                break;
+            };
+
+            // Sources are looked up before the destination is renamed
+            entry.srcs = control::source_regs(id.inst, &id.ctrl)
+                .into_iter()
+                .map(|(arch, fp)| PhysReg {
+                    fp,
+                    idx: self.regs(fp).lookup(arch),
+                })
+                .collect();
+
+            if !trapped {
+                let fp = id.ctrl.fp_reg_write;
+                if fp || (id.ctrl.reg_write && id.rd != 0) {
+                    entry.dest = self
+                        .regs(fp)
+                        .allocate(id.rd)
+                        .map(|(idx, old)| (PhysReg { fp, idx }, old));
+                }
+            }
+            if id.ctrl.vec_op != VecOp::None {
+                entry.after = self.last_vector.replace(entry.seq);
+            }
+            if let Some(mem) = entry.mem {
+                if mem.load {
+                    self.lq_used += 1;
+                }
+                if uses_store_queue(mem.load, mem.store) {
+                    self.sq_used += 1;
+                }
+            }
+            self.iq_used[entry.queue as usize] += 1;
+            let seq = entry.seq;
+            self.rob.push_back(entry);
+
+            // Anything that changed the fetch path holds fetch until it resolves
+            let redirected = cpu.pc != fetch_pc || (had_rest && cpu.if_id.entries.is_empty());
+            if redirected || trapped {
+                cpu.if_id = Default::default();
+                self.resume = Some(if !trapped && (id.ctrl.branch || id.ctrl.jump) {
+                    Resume::Complete(seq)
+                } else {
+                    Resume::Commit(seq)
+                });
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes the decoded instruction in `id_ex` through the in-order stages.
+    /// Returns its ROB entry and whether it trapped, or `None` if the run ended.
+    fn execute(
+        &mut self,
+        cpu: &mut Cpu,
+        units: &FunctionalUnits,
+        id: &IdExEntry,
+    ) -> Result<Option<(RobEntry, bool)>, String> {
+        let (stall_cycles, traps) = (cpu.stall_cycles, cpu.stats.traps_taken);
+
+        stages::execute::execute_stage(cpu)?;
+        let mem = cpu.ex_mem.entries.first().and_then(mem_access);
+        stages::memory_access::mem_stage(cpu)?;
+        stages::write_back::wb_stage(cpu)?;
+        cpu.id_ex = IdEx::default();
+        cpu.ex_mem = ExMem::default();
+        cpu.mem_wb = MemWb::default();
+
+        // Memory charges its latency as pipeline stalls; here it belongs to the instruction
+        let mem_latency = cpu.stall_cycles - stall_cycles;
+        cpu.stall_cycles = stall_cycles;
+
+        if cpu.exit_code.is_some() {
+            return Ok(None);
+        }
+        let trapped = cpu.stats.traps_taken != traps;
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let mem = mem.filter(|_| !trapped);
+        let unit = FuClass::of(&id.ctrl).filter(|_| !trapped);
+        Ok(Some((
+            RobEntry {
+                seq,
+                pc: id.pc,
+                queue: Queue::of(&id.ctrl),
+                srcs: Vec::new(),
+                dest: None,
+                after: None,
+                unit,
+                ports: Port::needed(&id.ctrl),
+                latency: match unit {
+                    Some(class) => units.latency(class),
+                    None if mem.is_some() => mem_latency,
+                    None => 1,
+                },
+                mem,
+                forwarded_from: None,
+                state: State::Waiting,
+                not_before: 0,
+            },
+            trapped,
+        )))
+    }
+
+    /// Fetches the next group once the previous one has been consumed and no
+    /// redirect is outstanding.
+    fn fetch(&mut self, cpu: &mut Cpu, now: u64) -> Result<(), String> {
+        if self.fetch_busy > 0 {
+            self.fetch_busy -= 1;
+            cpu.stats.stalls_mem += 1;
+            return Ok(());
+        }
+        if let Some(resume) = self.resume {
+            let resolved = match resume {
+                Resume::Complete(seq) => self.completed(seq, now),
+                Resume::Commit(seq) => self.committed(seq),
+            };
+            if !resolved {
+                return Ok(());
+            }
+            self.resume = None;
+        }
+        if cpu.if_id.entries.is_empty() && !cpu.wfi {
+            let stall_cycles = cpu.stall_cycles;
+            stages::fetch::fetch_stage(cpu)?;
+            self.fetch_busy = cpu.stall_cycles - stall_cycles;
+            cpu.stall_cycles = stall_cycles;
+        }
+        Ok(())
+    }
+}
+
+/// Advances the out-of-order back-end by one cycle.
+pub fn tick(cpu: &mut Cpu) -> Result<(), String> {
+    let Some(mut core) = cpu.ooo.take() else {
+        return Ok(());
+    };
+    // The back-end times the units and ports itself, and renaming replaces the
+    // scoreboard, so the in-order stages see none of them
+    let mut units = std::mem::take(&mut cpu.units);
+    let mut ports = std::mem::take(&mut cpu.ports);
+    let scoreboard = std::mem::take(&mut cpu.scoreboard);
+    let result = core.cycle(cpu, &mut units, &mut ports);
+    cpu.units = units;
+    cpu.ports = ports;
+    cpu.scoreboard = scoreboard;
+    cpu.ooo = Some(core);
+    result
+}
@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+
+/// Architectural registers per register file.
+pub const ARCH_REGS: usize = 32;
+
+/// A physical register, in the integer or the floating-point file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PhysReg {
+    pub fp: bool,
+    pub idx: usize,
+}
+
+/// Register alias table, free list and readiness for one register file.
+/// Values live in the architectural register file; physical registers only
+/// carry the cycle their value becomes available.
+pub struct RenameTable {
+    map: [usize; ARCH_REGS],
+    free: VecDeque<usize>,
+    ready: Vec<u64>,
+}
+
+impl RenameTable {
+    /// `phys` is clamped so that at least one register is left to rename into.
+    pub fn new(phys: usize) -> Self {
+        let phys = phys.max(ARCH_REGS + 1);
+        Self {
+            map: std::array::from_fn(|i| i),
+            free: (ARCH_REGS..phys).collect(),
+            ready: vec![0; phys],
+        }
+    }
+
+    pub fn lookup(&self, arch: usize) -> usize {
+        self.map[arch]
+    }
+
+    pub fn free_count(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Maps `arch` to a fresh register that is not ready until its producer
+    /// issues. Returns the new register and the one it replaces.
+    pub fn allocate(&mut self, arch: usize) -> Option<(usize, usize)> {
+        let new = self.free.pop_front()?;
+        self.ready[new] = u64::MAX;
+        Some((new, std::mem::replace(&mut self.map[arch], new)))
+    }
+
+    /// Returns a register to the free list once no younger instruction can read it.
+    pub fn release(&mut self, preg: usize) {
+        self.free.push_back(preg);
+    }
+
+    pub fn ready_at(&self, preg: usize) -> u64 {
+        self.ready[preg]
+    }
+
+    pub fn set_ready(&mut self, preg: usize, cycle: u64) {
+        self.ready[preg] = cycle;
+    }
+}
@@ -1,18 +1,22 @@
 use crate::core::control::ControlSignals;
 use crate::core::types::Trap;
 
-#[derive(Clone, Copy, Default, Debug)]
+#[derive(Clone, Default, Debug)]
 pub struct IfIdEntry {
     pub pc: u64,
     pub inst: u32,
+    pub inst_size: u64,
     pub pred_taken: bool,
     pub pred_target: u64,
+    /// A fetch fault or execute trigger; the instruction traps instead of executing
+    pub trap: Option<Trap>,
 }
 
 #[derive(Clone, Default, Debug)]
 pub struct IdExEntry {
     pub pc: u64,
     pub inst: u32,
+    pub inst_size: u64,
     pub rs1: usize,
     pub rs2: usize,
     pub rs3: usize,
@@ -31,6 +35,7 @@ pub struct IdExEntry {
 pub struct ExMemEntry {
     pub pc: u64,
     pub inst: u32,
+    pub inst_size: u64,
     pub rd: usize,
     pub alu: u64,
     pub store_data: u64,
@@ -42,6 +47,7 @@ pub struct ExMemEntry {
 pub struct MemWbEntry {
     pub pc: u64,
     pub inst: u32,
+    pub inst_size: u64,
     pub rd: usize,
     pub alu: u64,
     pub load_data: u64,
@@ -49,32 +55,16 @@ pub struct MemWbEntry {
     pub trap: Option<Trap>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Default, Debug)]
 pub struct IfId {
     pub entries: Vec<IfIdEntry>,
 }
 
-impl Default for IfId {
-    fn default() -> Self {
-        Self {
-            entries: Vec::new(),
-        }
-    }
-}
-
 #[derive(Clone, Default, Debug)]
 pub struct IdEx {
     pub entries: Vec<IdExEntry>,
 }
 
-impl IdEx {
-    pub fn bubble() -> Self {
-        Self {
-            entries: Vec::new(),
-        }
-    }
-}
-
 #[derive(Clone, Default, Debug)]
 pub struct ExMem {
     pub entries: Vec<ExMemEntry>,
@@ -1,13 +1,13 @@
 pub struct RegisterFile {
     regs: [u64; 32],
-    fregs: [f64; 32],
+    fregs: [u64; 32],
 }
 
 impl RegisterFile {
     pub fn new() -> Self {
         Self {
             regs: [0; 32],
-            fregs: [0.0; 32],
+            fregs: [0; 32],
         }
     }
 
@@ -22,11 +22,11 @@ impl RegisterFile {
     }
 
     pub fn read_f(&self, idx: usize) -> u64 {
-        self.fregs[idx].to_bits()
+        self.fregs[idx]
     }
 
     pub fn write_f(&mut self, idx: usize, val: u64) {
-        self.fregs[idx] = f64::from_bits(val);
+        self.fregs[idx] = val;
     }
 
     pub fn dump(&self) {
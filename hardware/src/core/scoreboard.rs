@@ -0,0 +1,56 @@
+use super::units::FuClass;
+
+const REGS: usize = 32;
+
+/// An operand that is not ready when an instruction wants to execute.
+#[derive(Clone, Copy, Debug)]
+pub struct Hazard {
+    /// The functional unit producing it, or `None` for a load
+    pub unit: Option<FuClass>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Pending {
+    ready: u64,
+    unit: Option<FuClass>,
+}
+
+/// Per-register result timing for the in-order pipeline: the cycle from which
+/// an instruction executing can take each integer and FP register, through
+/// forwarding or the register file. The default tracks nothing.
+#[derive(Default)]
+pub struct Scoreboard {
+    int: Vec<Pending>,
+    fp: Vec<Pending>,
+}
+
+impl Scoreboard {
+    pub fn new() -> Self {
+        Self {
+            int: vec![Pending::default(); REGS],
+            fp: vec![Pending::default(); REGS],
+        }
+    }
+
+    /// Records that `reg` is written with a result ready at `ready`. The
+    /// newest writer decides, since it supplies the value later readers see.
+    pub fn reserve(&mut self, reg: usize, fp: bool, ready: u64, unit: Option<FuClass>) {
+        if !fp && reg == 0 {
+            return;
+        }
+        let file = if fp { &mut self.fp } else { &mut self.int };
+        if let Some(entry) = file.get_mut(reg) {
+            *entry = Pending { ready, unit };
+        }
+    }
+
+    /// The first of `sources` that is not ready for an instruction executing at `at`.
+    pub fn hazard(&self, sources: &[(usize, bool)], at: u64) -> Option<Hazard> {
+        sources.iter().find_map(|&(reg, fp)| {
+            let file = if fp { &self.fp } else { &self.int };
+            file.get(reg)
+                .filter(|p| p.ready > at)
+                .map(|p| Hazard { unit: p.unit })
+        })
+    }
+}
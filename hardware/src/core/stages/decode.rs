@@ -1,25 +1,201 @@
 use crate::core::Cpu;
-use crate::core::control::{AluOp, AtomicOp, ControlSignals, CsrOp, MemWidth, OpASrc, OpBSrc};
+use crate::core::control::{
+    self, AluOp, AtomicOp, CacheOp, ControlSignals, CsrOp, MemWidth, OpASrc, OpBSrc,
+};
+use crate::core::fpu::FpFormat;
 use crate::core::pipeline::{IdEx, IdExEntry};
 use crate::core::types::Trap;
-use crate::isa::instruction::InstructionBits;
-use crate::isa::{decoder, funct3, funct5, funct7, opcodes, sys_ops};
+use crate::core::units::Port;
+use crate::core::vpu::VecOp;
+use crate::isa::instruction::{Decoded, InstructionBits};
+use crate::isa::{csr, decoder, funct3, funct5, funct6, funct7, opcodes, rvc, sys_ops};
+
+/// Maps the `fmt` field of OP-FP and fused multiply-add instructions.
+fn fp_format(fmt: u32) -> Option<FpFormat> {
+    match fmt {
+        0b00 => Some(FpFormat::Single),
+        0b01 => Some(FpFormat::Double),
+        0b10 => Some(FpFormat::Half),
+        _ => None,
+    }
+}
+
+/// Maps the `width` field of LOAD-FP/STORE-FP to a vector element width.
+fn vector_width(width: u32) -> Option<MemWidth> {
+    match width {
+        funct3::VE8 => Some(MemWidth::Byte),
+        funct3::VE16 => Some(MemWidth::Half),
+        funct3::VE32 => Some(MemWidth::Word),
+        funct3::VE64 => Some(MemWidth::Double),
+        _ => None,
+    }
+}
+
+/// Whether an instruction uses FP or vector state while it is Off. Vector
+/// FP arithmetic needs both.
+fn state_off(cpu: &Cpu, d: &Decoded, ctrl: &ControlSignals) -> bool {
+    let fp = ctrl.fp_reg_write
+        || ctrl.rs1_fp
+        || ctrl.rs2_fp
+        || ctrl.rs3_fp
+        || (d.opcode == opcodes::OP_V && matches!(d.funct3, funct3::OPFVV | funct3::OPFVF));
+    let vector = ctrl.vec_op != VecOp::None;
+    (fp && !cpu.state_enabled(csr::MSTATUS_FS)) || (vector && !cpu.state_enabled(csr::MSTATUS_VS))
+}
+
+/// Selects the addressing mode of a vector load/store. Only unit-stride and
+/// strided accesses without segments are supported.
+fn vector_mem_op(d: &Decoded) -> Option<VecOp> {
+    let nf = d.raw >> 29;
+    let mew = (d.raw >> 28) & 1;
+    let mop = (d.raw >> 26) & 0x3;
+    match (nf, mew, mop) {
+        (0, 0, 0b00) if d.rs2 == 0 => Some(VecOp::UnitStride),
+        (0, 0, 0b10) => Some(VecOp::Strided),
+        _ => None,
+    }
+}
+
+/// Decodes the OP-V major opcode.
+fn vector_op(d: &Decoded) -> Option<VecOp> {
+    let funct6 = d.raw >> 26;
+    let unmasked = (d.raw >> 25) & 1 == 1;
+    let vv = matches!(d.funct3, funct3::OPIVV | funct3::OPMVV | funct3::OPFVV);
+    let imm = d.funct3 == funct3::OPIVI;
+
+    let op = match d.funct3 {
+        funct3::OPCFG => match d.raw >> 30 {
+            0b00 | 0b01 => VecOp::Vsetvli,
+            0b11 => VecOp::Vsetivli,
+            _ if (d.raw >> 25) & 0x1f == 0 => VecOp::Vsetvl,
+            _ => return None,
+        },
+        funct3::OPIVV | funct3::OPIVX | funct3::OPIVI => match funct6 {
+            funct6::VADD => VecOp::Add,
+            funct6::VSUB if !imm => VecOp::Sub,
+            funct6::VRSUB if !vv => VecOp::Rsub,
+            funct6::VMINU if !imm => VecOp::Minu,
+            funct6::VMIN if !imm => VecOp::Min,
+            funct6::VMAXU if !imm => VecOp::Maxu,
+            funct6::VMAX if !imm => VecOp::Max,
+            funct6::VAND => VecOp::And,
+            funct6::VOR => VecOp::Or,
+            funct6::VXOR => VecOp::Xor,
+            funct6::VMERGE_VMV if unmasked && d.rs2 == 0 => VecOp::Mv,
+            funct6::VMERGE_VMV if !unmasked => VecOp::Merge,
+            funct6::VMSEQ => VecOp::Mseq,
+            funct6::VMSNE => VecOp::Msne,
+            funct6::VMSLTU if !imm => VecOp::Msltu,
+            funct6::VMSLT if !imm => VecOp::Mslt,
+            funct6::VMSLEU => VecOp::Msleu,
+            funct6::VMSLE => VecOp::Msle,
+            funct6::VMSGTU if !vv => VecOp::Msgtu,
+            funct6::VMSGT if !vv => VecOp::Msgt,
+            funct6::VSLL => VecOp::Sll,
+            funct6::VSRL => VecOp::Srl,
+            funct6::VSRA => VecOp::Sra,
+            _ => return None,
+        },
+        funct3::OPMVV | funct3::OPMVX => match funct6 {
+            funct6::VREDSUM if vv => VecOp::RedSum,
+            funct6::VREDMINU if vv => VecOp::RedMinu,
+            funct6::VREDMIN if vv => VecOp::RedMin,
+            funct6::VREDMAXU if vv => VecOp::RedMaxu,
+            funct6::VREDMAX if vv => VecOp::RedMax,
+            funct6::VWXUNARY0 if vv && unmasked && d.rs1 == 0 => VecOp::MvXS,
+            funct6::VWXUNARY0 if !vv && unmasked && d.rs2 == 0 => VecOp::MvSX,
+            funct6::VXUNARY0 if vv => match d.rs1 {
+                0b00010 => VecOp::ZextVf8,
+                0b00011 => VecOp::SextVf8,
+                0b00100 => VecOp::ZextVf4,
+                0b00101 => VecOp::SextVf4,
+                0b00110 => VecOp::ZextVf2,
+                0b00111 => VecOp::SextVf2,
+                _ => return None,
+            },
+            funct6::VDIVU => VecOp::Divu,
+            funct6::VDIV => VecOp::Div,
+            funct6::VREMU => VecOp::Remu,
+            funct6::VREM => VecOp::Rem,
+            funct6::VMULHU => VecOp::Mulhu,
+            funct6::VMUL => VecOp::Mul,
+            funct6::VMULH => VecOp::Mulh,
+            funct6::VMADD => VecOp::Madd,
+            funct6::VMACC => VecOp::Macc,
+            funct6::VNMSAC => VecOp::Nmsac,
+            _ => return None,
+        },
+        funct3::OPFVV | funct3::OPFVF => match funct6 {
+            funct6::VFADD => VecOp::FAdd,
+            funct6::VFREDUSUM if vv => VecOp::FRedUSum,
+            funct6::VFSUB => VecOp::FSub,
+            funct6::VFREDOSUM if vv => VecOp::FRedOSum,
+            funct6::VFMIN => VecOp::FMin,
+            funct6::VFMAX => VecOp::FMax,
+            funct6::VWFUNARY0 if vv && unmasked && d.rs1 == 0 => VecOp::FMvFS,
+            funct6::VWFUNARY0 if !vv && unmasked && d.rs2 == 0 => VecOp::FMvSF,
+            funct6::VFUNARY1 if vv && d.rs1 == 0 => VecOp::FSqrt,
+            funct6::VFMERGE_VFMV if !vv && unmasked && d.rs2 == 0 => VecOp::FMv,
+            funct6::VFMERGE_VFMV if !vv && !unmasked => VecOp::FMerge,
+            funct6::VMFEQ => VecOp::MFeq,
+            funct6::VMFLE => VecOp::MFle,
+            funct6::VMFLT => VecOp::MFlt,
+            funct6::VFDIV => VecOp::FDiv,
+            funct6::VFMUL => VecOp::FMul,
+            funct6::VFMACC => VecOp::FMacc,
+            funct6::VFNMACC => VecOp::FNmacc,
+            funct6::VFMSAC => VecOp::FMsac,
+            funct6::VFNMSAC => VecOp::FNmsac,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(op)
+}
 
 pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
     let mut decoded = Vec::new();
     let mut consumed_count = 0;
     let mut bundle_writes: Vec<(usize, bool)> = Vec::new();
+    cpu.ports.reset();
 
     for if_entry in &cpu.if_id.entries {
-        let inst = if_entry.inst;
-        if inst == 0x0000_0013 || inst == 0 {
+        if let Some(trap) = &if_entry.trap {
+            decoded.push(IdExEntry {
+                pc: if_entry.pc,
+                trap: Some(trap.clone()),
+                ..Default::default()
+            });
+            consumed_count += 1;
+            continue;
+        }
+
+        let inst = if if_entry.inst_size == 2 {
+            match rvc::expand(if_entry.inst as u16) {
+                Some(expanded) => expanded,
+                None => {
+                    decoded.push(IdExEntry {
+                        pc: if_entry.pc,
+                        inst: if_entry.inst,
+                        inst_size: if_entry.inst_size,
+                        trap: Some(Trap::IllegalInstruction(if_entry.inst)),
+                        ..Default::default()
+                    });
+                    consumed_count += 1;
+                    continue;
+                }
+            }
+        } else {
+            if_entry.inst
+        };
+        if inst == 0x0000_0013 || (inst == 0 && if_entry.inst_size == 4) {
             consumed_count += 1;
             continue;
         }
 
         let d = decoder::decode(inst);
 
-        let decode_logic = |d: &crate::isa::instruction::Decoded| -> Result<ControlSignals, Trap> {
+        let decode_logic = |d: &Decoded| -> Result<ControlSignals, Trap> {
             let mut c = ControlSignals {
                 a_src: OpASrc::Reg1,
                 b_src: OpBSrc::Imm,
@@ -104,27 +280,97 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
                     c.is_rv32 = d.opcode == opcodes::OP_IMM_32;
                     c.a_src = OpASrc::Reg1;
                     c.b_src = OpBSrc::Imm;
-                    c.alu = match d.funct3 {
-                        funct3::ADD_SUB => AluOp::Add,
-                        funct3::SLL => AluOp::Sll,
-                        funct3::SLT => AluOp::Slt,
-                        funct3::SLTU => AluOp::Sltu,
-                        funct3::XOR => AluOp::Xor,
-                        funct3::SRL_SRA => {
-                            if (d.funct7 & 0x20) != 0 {
-                                AluOp::Sra
+
+                    // Shift-type immediates carry a function selector in their upper bits
+                    let funct6 = d.raw >> 26;
+                    let funct12 = d.raw >> 20;
+                    c.alu = match (d.funct3, c.is_rv32) {
+                        (funct3::ADD_SUB, _) => AluOp::Add,
+                        (funct3::SLT, false) => AluOp::Slt,
+                        (funct3::SLTU, false) => AluOp::Sltu,
+                        (funct3::XOR, false) => AluOp::Xor,
+                        (funct3::OR, false) if d.rd == 0 => {
+                            // ori x0 encodes the Zicbop prefetch hints; the address is rs1 + imm[11:5]
+                            c.cache_op = match d.rs2 as u32 {
+                                funct7::PREFETCH_I => CacheOp::PrefetchI,
+                                funct7::PREFETCH_R => CacheOp::PrefetchR,
+                                funct7::PREFETCH_W => CacheOp::PrefetchW,
+                                _ => CacheOp::None,
+                            };
+                            if c.cache_op.is_prefetch() {
+                                c.reg_write = false;
+                                AluOp::Add
                             } else {
-                                AluOp::Srl
+                                AluOp::Or
                             }
                         }
-                        funct3::OR => AluOp::Or,
-                        funct3::AND => AluOp::And,
+                        (funct3::OR, false) => AluOp::Or,
+                        (funct3::AND, false) => AluOp::And,
+                        (funct3::SLL, false) => match funct6 {
+                            funct6::SLLI => AluOp::Sll,
+                            funct6::BCLRI_BEXTI => AluOp::Bclr,
+                            funct6::BINVI => AluOp::Binv,
+                            funct6::BSETI => AluOp::Bset,
+                            _ if funct12 == funct7::AES64IM_IMM => AluOp::Aes64Im,
+                            // rnum values above 0xA are reserved
+                            _ if funct12 & !0xF == funct7::AES64KS1I_IMM
+                                && funct12 & 0xF <= 0xA =>
+                            {
+                                AluOp::Aes64Ks1i
+                            }
+                            _ if funct12 == funct7::SHA256SUM0_IMM => AluOp::Sha256Sum0,
+                            _ if funct12 == funct7::SHA256SUM1_IMM => AluOp::Sha256Sum1,
+                            _ if funct12 == funct7::SHA256SIG0_IMM => AluOp::Sha256Sig0,
+                            _ if funct12 == funct7::SHA256SIG1_IMM => AluOp::Sha256Sig1,
+                            _ if funct12 == funct7::SHA512SUM0_IMM => AluOp::Sha512Sum0,
+                            _ if funct12 == funct7::SHA512SUM1_IMM => AluOp::Sha512Sum1,
+                            _ if funct12 == funct7::SHA512SIG0_IMM => AluOp::Sha512Sig0,
+                            _ if funct12 == funct7::SHA512SIG1_IMM => AluOp::Sha512Sig1,
+                            _ if d.funct7 == funct7::ROTATE => match d.rs2 as u32 {
+                                funct7::UNARY_CLZ => AluOp::Clz,
+                                funct7::UNARY_CTZ => AluOp::Ctz,
+                                funct7::UNARY_CPOP => AluOp::Cpop,
+                                funct7::UNARY_SEXT_B => AluOp::SextB,
+                                funct7::UNARY_SEXT_H => AluOp::SextH,
+                                _ => return Err(Trap::IllegalInstruction(inst)),
+                            },
+                            _ => return Err(Trap::IllegalInstruction(inst)),
+                        },
+                        (funct3::SRL_SRA, false) => match funct6 {
+                            funct6::SRLI => AluOp::Srl,
+                            funct6::SRAI => AluOp::Sra,
+                            funct6::RORI => AluOp::Ror,
+                            funct6::BCLRI_BEXTI => AluOp::Bext,
+                            _ if funct12 == funct7::ORC_B_IMM => AluOp::OrcB,
+                            _ if funct12 == funct7::REV8_IMM => AluOp::Rev8,
+                            _ if funct12 == funct7::BREV8_IMM => AluOp::Brev8,
+                            _ => return Err(Trap::IllegalInstruction(inst)),
+                        },
+                        (funct3::SLL, true) => match d.funct7 {
+                            funct7::DEFAULT => AluOp::Sll,
+                            _ if funct6 == funct6::SLLI_UW => {
+                                c.is_rv32 = false;
+                                AluOp::SllUw
+                            }
+                            funct7::ROTATE => match d.rs2 as u32 {
+                                funct7::UNARY_CLZ => AluOp::Clz,
+                                funct7::UNARY_CTZ => AluOp::Ctz,
+                                funct7::UNARY_CPOP => AluOp::Cpop,
+                                _ => return Err(Trap::IllegalInstruction(inst)),
+                            },
+                            _ => return Err(Trap::IllegalInstruction(inst)),
+                        },
+                        (funct3::SRL_SRA, true) => match d.funct7 {
+                            funct7::DEFAULT => AluOp::Srl,
+                            funct7::SRA => AluOp::Sra,
+                            funct7::ROTATE => AluOp::Ror,
+                            _ => return Err(Trap::IllegalInstruction(inst)),
+                        },
                         _ => return Err(Trap::IllegalInstruction(inst)),
                     };
                 }
-                opcodes::OP_REG | opcodes::OP_REG_32 => {
+                opcodes::OP_REG => {
                     c.reg_write = true;
-                    c.is_rv32 = d.opcode == opcodes::OP_REG_32;
                     c.a_src = OpASrc::Reg1;
                     c.b_src = OpBSrc::Reg2;
                     c.alu = match (d.funct3, d.funct7) {
@@ -146,9 +392,94 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
                         (funct3::SRL_SRA, funct7::M_EXTENSION) => AluOp::Divu,
                         (funct3::OR, funct7::M_EXTENSION) => AluOp::Rem,
                         (funct3::AND, funct7::M_EXTENSION) => AluOp::Remu,
+                        (funct3::SLT, funct7::SH_ADD) => AluOp::Sh1Add,
+                        (funct3::XOR, funct7::SH_ADD) => AluOp::Sh2Add,
+                        (funct3::OR, funct7::SH_ADD) => AluOp::Sh3Add,
+                        (funct3::AND, funct7::ANDN_ORN_XNOR) => AluOp::Andn,
+                        (funct3::OR, funct7::ANDN_ORN_XNOR) => AluOp::Orn,
+                        (funct3::XOR, funct7::ANDN_ORN_XNOR) => AluOp::Xnor,
+                        (funct3::XOR, funct7::MIN_MAX_CLMUL) => AluOp::Min,
+                        (funct3::SRL_SRA, funct7::MIN_MAX_CLMUL) => AluOp::Minu,
+                        (funct3::OR, funct7::MIN_MAX_CLMUL) => AluOp::Max,
+                        (funct3::AND, funct7::MIN_MAX_CLMUL) => AluOp::Maxu,
+                        (funct3::SLL, funct7::MIN_MAX_CLMUL) => AluOp::Clmul,
+                        (funct3::SLT, funct7::MIN_MAX_CLMUL) => AluOp::Clmulr,
+                        (funct3::SLTU, funct7::MIN_MAX_CLMUL) => AluOp::Clmulh,
+                        (funct3::SLL, funct7::ROTATE) => AluOp::Rol,
+                        (funct3::SRL_SRA, funct7::ROTATE) => AluOp::Ror,
+                        (funct3::SLL, funct7::BCLR_BEXT) => AluOp::Bclr,
+                        (funct3::SRL_SRA, funct7::BCLR_BEXT) => AluOp::Bext,
+                        (funct3::SLL, funct7::BINV) => AluOp::Binv,
+                        (funct3::SLL, funct7::BSET) => AluOp::Bset,
+                        (funct3::XOR, funct7::PACK) => AluOp::Pack,
+                        (funct3::AND, funct7::PACK) => AluOp::Packh,
+                        (funct3::ADD_SUB, funct7::AES64ES) => AluOp::Aes64Es,
+                        (funct3::ADD_SUB, funct7::AES64ESM) => AluOp::Aes64Esm,
+                        (funct3::ADD_SUB, funct7::AES64DS) => AluOp::Aes64Ds,
+                        (funct3::ADD_SUB, funct7::AES64DSM) => AluOp::Aes64Dsm,
+                        (funct3::ADD_SUB, funct7::AES64KS2) => AluOp::Aes64Ks2,
                         _ => return Err(Trap::IllegalInstruction(inst)),
                     };
                 }
+                opcodes::OP_REG_32 => {
+                    c.reg_write = true;
+                    c.is_rv32 = true;
+                    c.a_src = OpASrc::Reg1;
+                    c.b_src = OpBSrc::Reg2;
+                    c.alu = match (d.funct3, d.funct7) {
+                        (funct3::ADD_SUB, funct7::DEFAULT) => AluOp::Add,
+                        (funct3::ADD_SUB, funct7::SUB) => AluOp::Sub,
+                        (funct3::SLL, funct7::DEFAULT) => AluOp::Sll,
+                        (funct3::SRL_SRA, funct7::DEFAULT) => AluOp::Srl,
+                        (funct3::SRL_SRA, funct7::SRA) => AluOp::Sra,
+                        (funct3::ADD_SUB, funct7::M_EXTENSION) => AluOp::Mul,
+                        (funct3::XOR, funct7::M_EXTENSION) => AluOp::Div,
+                        (funct3::SRL_SRA, funct7::M_EXTENSION) => AluOp::Divu,
+                        (funct3::OR, funct7::M_EXTENSION) => AluOp::Rem,
+                        (funct3::AND, funct7::M_EXTENSION) => AluOp::Remu,
+                        (funct3::SLL, funct7::ROTATE) => AluOp::Rol,
+                        (funct3::SRL_SRA, funct7::ROTATE) => AluOp::Ror,
+                        // The .uw forms and zext.h produce full-width results
+                        (funct3::ADD_SUB, funct7::ADD_UW) => {
+                            c.is_rv32 = false;
+                            AluOp::AddUw
+                        }
+                        (funct3::SLT, funct7::SH_ADD) => {
+                            c.is_rv32 = false;
+                            AluOp::Sh1AddUw
+                        }
+                        (funct3::XOR, funct7::SH_ADD) => {
+                            c.is_rv32 = false;
+                            AluOp::Sh2AddUw
+                        }
+                        (funct3::OR, funct7::SH_ADD) => {
+                            c.is_rv32 = false;
+                            AluOp::Sh3AddUw
+                        }
+                        (funct3::XOR, funct7::ZEXT_H) if d.rs2 == 0 => {
+                            c.is_rv32 = false;
+                            AluOp::ZextH
+                        }
+                        (funct3::XOR, funct7::PACK) => AluOp::Packw,
+                        _ => return Err(Trap::IllegalInstruction(inst)),
+                    };
+                }
+                opcodes::OP_MISC_MEM => match d.funct3 {
+                    funct3::FENCE => c.is_fence = true,
+                    funct3::FENCE_I => c.is_fence_i = true,
+                    funct3::CBO if d.rd == 0 => {
+                        c.a_src = OpASrc::Reg1;
+                        c.b_src = OpBSrc::Zero;
+                        c.cache_op = match d.raw >> 20 {
+                            funct7::CBO_INVAL_IMM => CacheOp::Inval,
+                            funct7::CBO_CLEAN_IMM => CacheOp::Clean,
+                            funct7::CBO_FLUSH_IMM => CacheOp::Flush,
+                            funct7::CBO_ZERO_IMM => CacheOp::Zero,
+                            _ => return Err(Trap::IllegalInstruction(inst)),
+                        };
+                    }
+                    _ => return Err(Trap::IllegalInstruction(inst)),
+                },
                 opcodes::OP_SYSTEM => {
                     c.is_system = true;
                     match d.raw {
@@ -157,7 +488,46 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
                         sys_ops::MRET => c.is_mret = true,
                         sys_ops::SRET => c.is_sret = true,
                         sys_ops::WFI => {}
-                        sys_ops::SFENCE_VMA => {}
+                        // Fences flush every address and ASID/VMID whatever rs1 and rs2 name
+                        _ if matches!(
+                            d.raw & sys_ops::FENCE_VMA_MASK,
+                            sys_ops::SFENCE_VMA | sys_ops::HFENCE_VVMA | sys_ops::HFENCE_GVMA
+                        ) => {}
+                        _ if d.funct3 == sys_ops::HLSV => {
+                            // HLV/HSV flow through MEM like ordinary loads and stores
+                            c.is_system = false;
+                            c.hyper_mem = true;
+                            c.a_src = OpASrc::Reg1;
+                            c.b_src = OpBSrc::Zero;
+                            c.width = match d.funct7 & !funct7::HSV {
+                                funct7::HLV_B => MemWidth::Byte,
+                                funct7::HLV_H => MemWidth::Half,
+                                funct7::HLV_W => MemWidth::Word,
+                                funct7::HLV_D => MemWidth::Double,
+                                _ => return Err(Trap::IllegalInstruction(inst)),
+                            };
+
+                            if d.funct7 & funct7::HSV != 0 {
+                                if d.rd != 0 {
+                                    return Err(Trap::IllegalInstruction(inst));
+                                }
+                                c.mem_write = true;
+                            } else {
+                                c.reg_write = true;
+                                c.mem_read = true;
+                                match (d.rs2, c.width) {
+                                    (funct7::HLV_SIGNED, _) => c.signed_load = true,
+                                    (funct7::HLV_UNSIGNED, MemWidth::Double) => {
+                                        return Err(Trap::IllegalInstruction(inst));
+                                    }
+                                    (funct7::HLV_UNSIGNED, _) => {}
+                                    (funct7::HLVX, MemWidth::Half | MemWidth::Word) => {
+                                        c.hlvx = true
+                                    }
+                                    _ => return Err(Trap::IllegalInstruction(inst)),
+                                }
+                            }
+                        }
                         _ => {
                             c.csr_addr = inst.csr();
                             c.a_src = OpASrc::Reg1;
@@ -208,27 +578,41 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
                         _ => return Err(Trap::IllegalInstruction(inst)),
                     };
                 }
-                opcodes::OP_LOAD_FP => {
-                    c.fp_reg_write = true;
-                    c.mem_read = true;
+                opcodes::OP_LOAD_FP | opcodes::OP_STORE_FP => {
+                    let is_load = d.opcode == opcodes::OP_LOAD_FP;
+                    c.mem_read = is_load;
+                    c.mem_write = !is_load;
                     c.a_src = OpASrc::Reg1;
                     c.b_src = OpBSrc::Imm;
-                    match d.funct3 {
-                        0x2 => c.width = MemWidth::Word,
-                        0x3 => c.width = MemWidth::Double,
-                        _ => return Err(Trap::IllegalInstruction(inst)),
+                    if let Some(width) = vector_width(d.funct3) {
+                        // Base comes from rs1 alone; strided forms carry the stride in rs2
+                        c.width = width;
+                        c.b_src = OpBSrc::Zero;
+                        c.vec_op = vector_mem_op(d).ok_or(Trap::IllegalInstruction(inst))?;
+                    } else {
+                        c.fp_reg_write = is_load;
+                        c.rs2_fp = !is_load;
+                        (c.width, c.fp_fmt) = match d.funct3 {
+                            funct3::LH => (MemWidth::Half, FpFormat::Half),
+                            funct3::LW => (MemWidth::Word, FpFormat::Single),
+                            funct3::LD => (MemWidth::Double, FpFormat::Double),
+                            _ => return Err(Trap::IllegalInstruction(inst)),
+                        };
                     }
                 }
-                opcodes::OP_STORE_FP => {
-                    c.mem_write = true;
-                    c.rs2_fp = true;
-                    c.a_src = OpASrc::Reg1;
-                    c.b_src = OpBSrc::Imm;
-                    match d.funct3 {
-                        0x2 => c.width = MemWidth::Word,
-                        0x3 => c.width = MemWidth::Double,
-                        _ => return Err(Trap::IllegalInstruction(inst)),
+                opcodes::OP_V => {
+                    c.vec_op = vector_op(d).ok_or(Trap::IllegalInstruction(inst))?;
+                    match c.vec_op {
+                        VecOp::Vsetvli | VecOp::Vsetivli => c.reg_write = true,
+                        VecOp::Vsetvl => {
+                            c.reg_write = true;
+                            c.b_src = OpBSrc::Reg2;
+                        }
+                        VecOp::MvXS => c.reg_write = true,
+                        VecOp::FMvFS => c.fp_reg_write = true,
+                        _ => {}
                     }
+                    c.rs1_fp = d.funct3 == funct3::OPFVF;
                 }
                 opcodes::OP_FP => {
                     c.fp_reg_write = true;
@@ -236,7 +620,7 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
                     c.rs2_fp = true;
                     c.b_src = OpBSrc::Reg2;
                     let fmt = d.funct7 & 0x3;
-                    c.is_rv32 = fmt == 0;
+                    c.fp_fmt = fp_format(fmt).ok_or(Trap::IllegalInstruction(inst))?;
                     let op = d.funct7 >> 2;
                     match op {
                         funct7::FADD => c.alu = AluOp::FAdd,
@@ -244,6 +628,9 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
                         funct7::FMUL => c.alu = AluOp::FMul,
                         funct7::FDIV => c.alu = AluOp::FDiv,
                         funct7::FSQRT => {
+                            if d.rs2 != 0 {
+                                return Err(Trap::IllegalInstruction(inst));
+                            }
                             c.rs2_fp = false;
                             c.alu = AluOp::FSqrt;
                         }
@@ -268,6 +655,9 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
                                 _ => return Err(Trap::IllegalInstruction(inst)),
                             }
                         }
+                        funct7::FCLASS_MV_X_F if d.rs2 != 0 => {
+                            return Err(Trap::IllegalInstruction(inst));
+                        }
                         funct7::FCLASS_MV_X_F => match d.funct3 {
                             funct3::FMV_X_W => {
                                 c.fp_reg_write = false;
@@ -290,10 +680,12 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
                             c.reg_write = true;
                             c.rs1_fp = true;
                             c.rs2_fp = false;
-                            c.alu = if d.rs2 == 1 {
-                                AluOp::FCvtLS
-                            } else {
-                                AluOp::FCvtWS
+                            c.alu = match d.rs2 {
+                                0 => AluOp::FCvtWS,
+                                1 => AluOp::FCvtWuS,
+                                2 => AluOp::FCvtLS,
+                                3 => AluOp::FCvtLuS,
+                                _ => return Err(Trap::IllegalInstruction(inst)),
                             };
                         }
                         funct7::FCVT_F_W => {
@@ -302,13 +694,18 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
                             c.rs1_fp = false;
                             c.rs2_fp = false;
                             c.a_src = OpASrc::Reg1;
-                            c.alu = if d.rs2 == 1 {
-                                AluOp::FCvtSL
-                            } else {
-                                AluOp::FCvtSW
+                            c.alu = match d.rs2 {
+                                0 => AluOp::FCvtSW,
+                                1 => AluOp::FCvtSWu,
+                                2 => AluOp::FCvtSL,
+                                3 => AluOp::FCvtSLu,
+                                _ => return Err(Trap::IllegalInstruction(inst)),
                             };
                         }
                         funct7::FMV_F_X => {
+                            if d.rs2 != 0 || d.funct3 != 0 {
+                                return Err(Trap::IllegalInstruction(inst));
+                            }
                             c.fp_reg_write = true;
                             c.reg_write = false;
                             c.rs1_fp = false;
@@ -318,11 +715,15 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
                         }
                         funct7::FCVT_DS => {
                             c.rs2_fp = false;
-                            if d.rs2 == 1 {
-                                c.alu = AluOp::FCvtSD;
-                            } else {
-                                c.alu = AluOp::FCvtDS;
-                            }
+                            c.alu = match (fmt, d.rs2) {
+                                (0, 1) => AluOp::FCvtSD,
+                                (1, 0) => AluOp::FCvtDS,
+                                (0, 2) => AluOp::FCvtSH,
+                                (2, 0) => AluOp::FCvtHS,
+                                (1, 2) => AluOp::FCvtDH,
+                                (2, 1) => AluOp::FCvtHD,
+                                _ => return Err(Trap::IllegalInstruction(inst)),
+                            };
                         }
                         _ => return Err(Trap::IllegalInstruction(inst)),
                     }
@@ -333,7 +734,7 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
                     c.rs2_fp = true;
                     c.rs3_fp = true;
                     c.b_src = OpBSrc::Reg2;
-                    c.is_rv32 = (d.funct7 & 3) == 0;
+                    c.fp_fmt = fp_format(d.funct7 & 0x3).ok_or(Trap::IllegalInstruction(inst))?;
                     c.alu = match d.opcode {
                         opcodes::OP_FMADD => AluOp::FMAdd,
                         opcodes::OP_FMSUB => AluOp::FMSub,
@@ -348,33 +749,74 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
         };
 
         let (ctrl, trap) = match decode_logic(&d) {
+            Ok(c) if state_off(cpu, &d, &c) => (
+                ControlSignals::default(),
+                Some(Trap::IllegalInstruction(if_entry.inst)),
+            ),
             Ok(c) => (c, None),
+            // Report the instruction as fetched rather than its 32-bit expansion
+            Err(Trap::IllegalInstruction(_)) => (
+                ControlSignals::default(),
+                Some(Trap::IllegalInstruction(if_entry.inst)),
+            ),
             Err(t) => (ControlSignals::default(), Some(t)),
         };
 
-        let mut hazard = false;
+        // Vector instructions access the vector register file outside the
+        // forwarding network, so they issue alone to keep it in program order.
+        let is_vector = ctrl.vec_op != VecOp::None;
+        if is_vector && !decoded.is_empty() {
+            break;
+        }
 
-        if d.rs1 != 0 || ctrl.rs1_fp {
-            let is_fp = ctrl.rs1_fp;
-            if bundle_writes.contains(&(d.rs1, is_fp)) {
-                hazard = true;
-            }
+        // Fences issue alone, and only once everything older has left MEM and
+        // retired, so the accesses they order are complete when they execute
+        let is_fence = ctrl.is_fence || ctrl.is_fence_i;
+        if is_fence
+            && (!decoded.is_empty()
+                || !cpu.ex_mem.entries.is_empty()
+                || !cpu.mem_wb.entries.is_empty())
+        {
+            break;
         }
-        if d.rs2 != 0 || ctrl.rs2_fp {
-            let is_fp = ctrl.rs2_fp;
-            if bundle_writes.contains(&(d.rs2, is_fp)) {
-                hazard = true;
-            }
+
+        // Instructions that change architectural state as they execute do not
+        // issue behind an access that could still fault in MEM: system
+        // instructions, FP operations accruing fflags, and every instruction
+        // while an icount trigger is counting them
+        let may_fault =
+            |e: &IdExEntry| e.ctrl.mem_read || e.ctrl.mem_write || e.ctrl.cache_op != CacheOp::None;
+        let acts_in_ex =
+            ctrl.is_system || ctrl.alu.accrues_fflags() || cpu.csrs.triggers.counting();
+        if acts_in_ex && decoded.iter().any(may_fault) {
+            break;
         }
 
-        let rs3_idx = inst.rs3();
-        if ctrl.rs3_fp {
-            if bundle_writes.contains(&(rs3_idx, true)) {
-                hazard = true;
+        // Results from earlier in the bundle are not forwarded within the cycle
+        let sources = if trap.is_none() {
+            control::source_regs(inst, &ctrl)
+        } else {
+            Vec::new()
+        };
+        if sources.iter().any(|src| bundle_writes.contains(src)) {
+            break;
+        }
+
+        // Operands still being produced hold the instruction, and everything
+        // behind it, until they are ready when it would execute next cycle
+        if let Some(hazard) = cpu.scoreboard.hazard(&sources, cpu.stats.cycles + 1) {
+            if decoded.is_empty() {
+                cpu.stats.stalls_data += 1;
+                if let Some(unit) = hazard.unit {
+                    cpu.stats.result_waits[unit as usize] += 1;
+                }
             }
+            break;
         }
 
-        if hazard {
+        // An oversubscribed port ends the bundle
+        if !cpu.ports.claim(&Port::needed(&ctrl)) {
+            cpu.stats.port_conflicts += 1;
             break;
         }
 
@@ -395,6 +837,7 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
         } else {
             cpu.regs.read(d.rs2)
         };
+        let rs3_idx = inst.rs3();
         let rv3 = if ctrl.rs3_fp {
             cpu.regs.read_f(rs3_idx)
         } else {
@@ -411,11 +854,16 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
         decoded.push(IdExEntry {
             pc: if_entry.pc,
             inst,
+            inst_size: if_entry.inst_size,
             rs1: d.rs1,
             rs2: d.rs2,
             rs3: rs3_idx,
             rd: d.rd,
-            imm: d.imm,
+            imm: if ctrl.cache_op.is_prefetch() {
+                d.imm & !0x1f
+            } else {
+                d.imm
+            },
             rv1,
             rv2,
             rv3,
@@ -426,6 +874,15 @@ pub fn decode_stage(cpu: &mut Cpu) -> Result<(), String> {
         });
 
         consumed_count += 1;
+        if is_vector || is_fence {
+            break;
+        }
+    }
+
+    if cpu.ports.is_modelled() {
+        for (total, used) in cpu.stats.ports_used.iter_mut().zip(cpu.ports.used()) {
+            *total += used as u64;
+        }
     }
 
     cpu.id_ex = IdEx { entries: decoded };
@@ -1,14 +1,14 @@
 use crate::core::Cpu;
 use crate::core::control::{AluOp, CsrOp, OpASrc, OpBSrc};
-use crate::core::pipeline::{ExMem, ExMemEntry, IfId};
+use crate::core::crypto;
+use crate::core::fpu::{FpFormat, RM_DYNAMIC, RoundingMode, SoftFloat};
+use crate::core::pipeline::{ExMem, ExMemEntry, IdExEntry, IfId};
 use crate::core::types::Trap;
-use crate::isa::{abi, funct3, opcodes, sys_ops};
+use crate::core::units::FuClass;
+use crate::core::vpu::{self, VecOp};
+use crate::isa::{abi, csr, funct3, opcodes, sys_ops};
 
-fn box_f32(f: f32) -> u64 {
-    (f.to_bits() as u64) | 0xFFFF_FFFF_0000_0000
-}
-
-fn alu(op: AluOp, a: u64, b: u64, c: u64, is32: bool) -> u64 {
+fn alu(op: AluOp, a: u64, b: u64, is32: bool) -> u64 {
     let sh6 = (b & 0x3f) as u32;
     match op {
         AluOp::Add => {
@@ -111,10 +111,8 @@ fn alu(op: AluOp, a: u64, b: u64, c: u64, is32: bool) -> u64 {
                 } else {
                     ((a as u32) / (b as u32)) as i64 as u64
                 }
-            } else if b == 0 {
-                -1i64 as u64
             } else {
-                a / b
+                a.checked_div(b).unwrap_or(u64::MAX)
             }
         }
         AluOp::Rem => {
@@ -143,89 +141,234 @@ fn alu(op: AluOp, a: u64, b: u64, c: u64, is32: bool) -> u64 {
                 a % b
             }
         }
-        _ => {
+        AluOp::Sh1Add => (a << 1).wrapping_add(b),
+        AluOp::Sh2Add => (a << 2).wrapping_add(b),
+        AluOp::Sh3Add => (a << 3).wrapping_add(b),
+        AluOp::AddUw => (a as u32 as u64).wrapping_add(b),
+        AluOp::Sh1AddUw => ((a as u32 as u64) << 1).wrapping_add(b),
+        AluOp::Sh2AddUw => ((a as u32 as u64) << 2).wrapping_add(b),
+        AluOp::Sh3AddUw => ((a as u32 as u64) << 3).wrapping_add(b),
+        AluOp::SllUw => (a as u32 as u64) << sh6,
+        AluOp::Andn => a & !b,
+        AluOp::Orn => a | !b,
+        AluOp::Xnor => !(a ^ b),
+        AluOp::Clz => {
             if is32 {
-                let fa = f32::from_bits(a as u32);
-                let fb = f32::from_bits(b as u32);
-                let fc = f32::from_bits(c as u32);
-                match op {
-                    AluOp::FAdd => box_f32(fa + fb),
-                    AluOp::FSub => box_f32(fa - fb),
-                    AluOp::FMul => box_f32(fa * fb),
-                    AluOp::FDiv => box_f32(fa / fb),
-                    AluOp::FSqrt => box_f32(fa.sqrt()),
-                    AluOp::FMin => box_f32(fa.min(fb)),
-                    AluOp::FMax => box_f32(fa.max(fb)),
-                    AluOp::FMAdd => box_f32(fa.mul_add(fb, fc)),
-                    AluOp::FMSub => box_f32(fa.mul_add(fb, -fc)),
-                    AluOp::FNMAdd => box_f32((-fa).mul_add(fb, -fc)),
-                    AluOp::FNMSub => box_f32((-fa).mul_add(fb, fc)),
-                    AluOp::FSgnJ => box_f32(f32::from_bits(
-                        (fa.to_bits() & !0x8000_0000) | (fb.to_bits() & 0x8000_0000),
-                    )),
-                    AluOp::FSgnJN => box_f32(f32::from_bits(
-                        (fa.to_bits() & !0x8000_0000) | (!fb.to_bits() & 0x8000_0000),
-                    )),
-                    AluOp::FSgnJX => {
-                        box_f32(f32::from_bits(fa.to_bits() ^ (fb.to_bits() & 0x8000_0000)))
-                    }
-                    AluOp::FEq => (fa == fb) as u64,
-                    AluOp::FLt => (fa < fb) as u64,
-                    AluOp::FLe => (fa <= fb) as u64,
-                    AluOp::FCvtWS => (fa as i32) as i64 as u64,
-                    AluOp::FCvtLS => (fa as i64) as u64,
-                    AluOp::FCvtSD => box_f32(fa as f32),
-                    AluOp::FCvtSW => ((a as i32) as f64).to_bits(),
-                    AluOp::FCvtSL => ((a as i64) as f64).to_bits(),
-                    AluOp::FCvtDS => (f32::from_bits(a as u32) as f64).to_bits(),
-                    AluOp::FMvToF => box_f32(f32::from_bits(a as u32)),
-                    AluOp::FMvToX => (a as i32) as u64,
-                    _ => 0,
-                }
+                (a as u32).leading_zeros() as u64
             } else {
-                let fa = f64::from_bits(a);
-                let fb = f64::from_bits(b);
-                let fc = f64::from_bits(c);
-                match op {
-                    AluOp::FAdd => (fa + fb).to_bits(),
-                    AluOp::FSub => (fa - fb).to_bits(),
-                    AluOp::FMul => (fa * fb).to_bits(),
-                    AluOp::FDiv => (fa / fb).to_bits(),
-                    AluOp::FSqrt => fa.sqrt().to_bits(),
-                    AluOp::FMin => fa.min(fb).to_bits(),
-                    AluOp::FMax => fa.max(fb).to_bits(),
-                    AluOp::FMAdd => fa.mul_add(fb, fc).to_bits(),
-                    AluOp::FMSub => fa.mul_add(fb, -fc).to_bits(),
-                    AluOp::FNMAdd => (-fa).mul_add(fb, -fc).to_bits(),
-                    AluOp::FNMSub => (-fa).mul_add(fb, fc).to_bits(),
-                    AluOp::FSgnJ => f64::from_bits(
-                        (fa.to_bits() & !0x8000_0000_0000_0000)
-                            | (fb.to_bits() & 0x8000_0000_0000_0000),
-                    )
-                    .to_bits(),
-                    AluOp::FSgnJN => f64::from_bits(
-                        (fa.to_bits() & !0x8000_0000_0000_0000)
-                            | (!fb.to_bits() & 0x8000_0000_0000_0000),
-                    )
-                    .to_bits(),
-                    AluOp::FSgnJX => {
-                        f64::from_bits(fa.to_bits() ^ (fb.to_bits() & 0x8000_0000_0000_0000))
-                            .to_bits()
-                    }
-                    AluOp::FEq => (fa == fb) as u64,
-                    AluOp::FLt => (fa < fb) as u64,
-                    AluOp::FLe => (fa <= fb) as u64,
-                    AluOp::FCvtWS => (fa as i32) as i64 as u64,
-                    AluOp::FCvtLS => (fa as i64) as u64,
-                    AluOp::FCvtSD => box_f32(fa as f32),
-                    AluOp::FCvtSW => ((a as i32) as f64).to_bits(),
-                    AluOp::FCvtSL => ((a as i64) as f64).to_bits(),
-                    AluOp::FMvToF => a,
-                    AluOp::FMvToX => a,
-                    _ => 0,
+                a.leading_zeros() as u64
+            }
+        }
+        AluOp::Ctz => {
+            if is32 {
+                (a as u32).trailing_zeros() as u64
+            } else {
+                a.trailing_zeros() as u64
+            }
+        }
+        AluOp::Cpop => {
+            if is32 {
+                (a as u32).count_ones() as u64
+            } else {
+                a.count_ones() as u64
+            }
+        }
+        AluOp::Max => (a as i64).max(b as i64) as u64,
+        AluOp::Maxu => a.max(b),
+        AluOp::Min => (a as i64).min(b as i64) as u64,
+        AluOp::Minu => a.min(b),
+        AluOp::SextB => a as i8 as i64 as u64,
+        AluOp::SextH => a as i16 as i64 as u64,
+        AluOp::ZextH => a & 0xFFFF,
+        AluOp::Rol => {
+            if is32 {
+                (a as u32).rotate_left(b as u32 & 0x1f) as i32 as i64 as u64
+            } else {
+                a.rotate_left(sh6)
+            }
+        }
+        AluOp::Ror => {
+            if is32 {
+                (a as u32).rotate_right(b as u32 & 0x1f) as i32 as i64 as u64
+            } else {
+                a.rotate_right(sh6)
+            }
+        }
+        AluOp::OrcB => {
+            let mut res = 0;
+            for byte in 0..8 {
+                if (a >> (byte * 8)) & 0xFF != 0 {
+                    res |= 0xFF << (byte * 8);
                 }
             }
+            res
         }
+        AluOp::Rev8 => a.swap_bytes(),
+        AluOp::Clmul => clmul(a, b) as u64,
+        AluOp::Clmulh => (clmul(a, b) >> 64) as u64,
+        AluOp::Clmulr => (clmul(a, b) >> 63) as u64,
+        AluOp::Bclr => a & !(1 << sh6),
+        AluOp::Bext => (a >> sh6) & 1,
+        AluOp::Binv => a ^ (1 << sh6),
+        AluOp::Bset => a | (1 << sh6),
+        AluOp::Pack => (b << 32) | (a & 0xFFFF_FFFF),
+        AluOp::Packh => ((b & 0xFF) << 8) | (a & 0xFF),
+        AluOp::Packw => ((((b & 0xFFFF) << 16) | (a & 0xFFFF)) as i32) as i64 as u64,
+        AluOp::Brev8 => a.swap_bytes().reverse_bits(),
+        AluOp::Aes64Es => crypto::aes64es(a, b),
+        AluOp::Aes64Esm => crypto::aes64esm(a, b),
+        AluOp::Aes64Ds => crypto::aes64ds(a, b),
+        AluOp::Aes64Dsm => crypto::aes64dsm(a, b),
+        AluOp::Aes64Ks1i => crypto::aes64ks1i(a, b & 0xF),
+        AluOp::Aes64Ks2 => crypto::aes64ks2(a, b),
+        AluOp::Aes64Im => crypto::aes64im(a),
+        AluOp::Sha256Sig0 => crypto::sha256sig0(a),
+        AluOp::Sha256Sig1 => crypto::sha256sig1(a),
+        AluOp::Sha256Sum0 => crypto::sha256sum0(a),
+        AluOp::Sha256Sum1 => crypto::sha256sum1(a),
+        AluOp::Sha512Sig0 => crypto::sha512sig0(a),
+        AluOp::Sha512Sig1 => crypto::sha512sig1(a),
+        AluOp::Sha512Sum0 => crypto::sha512sum0(a),
+        AluOp::Sha512Sum1 => crypto::sha512sum1(a),
+        _ => 0,
+    }
+}
+
+/// Full 128-bit carry-less product of two XLEN operands.
+fn clmul(a: u64, b: u64) -> u128 {
+    let mut res = 0u128;
+    for i in 0..64 {
+        if (b >> i) & 1 != 0 {
+            res ^= (a as u128) << i;
+        }
+    }
+    res
+}
+
+fn is_fp_op(op: AluOp) -> bool {
+    matches!(
+        op,
+        AluOp::FAdd
+            | AluOp::FSub
+            | AluOp::FMul
+            | AluOp::FDiv
+            | AluOp::FSqrt
+            | AluOp::FMin
+            | AluOp::FMax
+            | AluOp::FMAdd
+            | AluOp::FMSub
+            | AluOp::FNMAdd
+            | AluOp::FNMSub
+            | AluOp::FCvtWS
+            | AluOp::FCvtLS
+            | AluOp::FCvtSW
+            | AluOp::FCvtSL
+            | AluOp::FCvtWuS
+            | AluOp::FCvtLuS
+            | AluOp::FCvtSWu
+            | AluOp::FCvtSLu
+            | AluOp::FCvtSD
+            | AluOp::FCvtDS
+            | AluOp::FCvtSH
+            | AluOp::FCvtHS
+            | AluOp::FCvtDH
+            | AluOp::FCvtHD
+            | AluOp::FSgnJ
+            | AluOp::FSgnJN
+            | AluOp::FSgnJX
+            | AluOp::FEq
+            | AluOp::FLt
+            | AluOp::FLe
+            | AluOp::FClass
+            | AluOp::FMvToX
+            | AluOp::FMvToF
+    )
+}
+
+/// Whether the instruction's funct3 is an `rm` field rather than an opcode extension.
+fn uses_rounding_mode(op: AluOp) -> bool {
+    matches!(
+        op,
+        AluOp::FAdd
+            | AluOp::FSub
+            | AluOp::FMul
+            | AluOp::FDiv
+            | AluOp::FSqrt
+            | AluOp::FMAdd
+            | AluOp::FMSub
+            | AluOp::FNMAdd
+            | AluOp::FNMSub
+            | AluOp::FCvtWS
+            | AluOp::FCvtLS
+            | AluOp::FCvtSW
+            | AluOp::FCvtSL
+            | AluOp::FCvtWuS
+            | AluOp::FCvtLuS
+            | AluOp::FCvtSWu
+            | AluOp::FCvtSLu
+            | AluOp::FCvtSD
+            | AluOp::FCvtDS
+            | AluOp::FCvtSH
+            | AluOp::FCvtHS
+            | AluOp::FCvtDH
+            | AluOp::FCvtHD
+    )
+}
+
+fn fpu(op: AluOp, a: u64, b: u64, c: u64, fmt: FpFormat, sf: &mut SoftFloat) -> u64 {
+    match op {
+        AluOp::FAdd => sf.add(fmt, a, b),
+        AluOp::FSub => sf.sub(fmt, a, b),
+        AluOp::FMul => sf.mul(fmt, a, b),
+        AluOp::FDiv => sf.div(fmt, a, b),
+        AluOp::FSqrt => sf.sqrt(fmt, a),
+        AluOp::FMAdd => sf.fma(fmt, a, b, c, false, false),
+        AluOp::FMSub => sf.fma(fmt, a, b, c, false, true),
+        AluOp::FNMSub => sf.fma(fmt, a, b, c, true, false),
+        AluOp::FNMAdd => sf.fma(fmt, a, b, c, true, true),
+        AluOp::FCvtWS => sf.float_to_int(fmt, a, true, 32),
+        AluOp::FCvtLS => sf.float_to_int(fmt, a, true, 64),
+        AluOp::FCvtSW => sf.int_to_float(fmt, a, true, 32),
+        AluOp::FCvtSL => sf.int_to_float(fmt, a, true, 64),
+        AluOp::FCvtSD => sf.convert(FpFormat::Double, FpFormat::Single, a),
+        AluOp::FCvtDS => sf.convert(FpFormat::Single, FpFormat::Double, a),
+        AluOp::FCvtSH => sf.convert(FpFormat::Half, FpFormat::Single, a),
+        AluOp::FCvtHS => sf.convert(FpFormat::Single, FpFormat::Half, a),
+        AluOp::FCvtDH => sf.convert(FpFormat::Half, FpFormat::Double, a),
+        AluOp::FCvtHD => sf.convert(FpFormat::Double, FpFormat::Half, a),
+        AluOp::FCvtWuS => sf.float_to_int(fmt, a, false, 32),
+        AluOp::FCvtLuS => sf.float_to_int(fmt, a, false, 64),
+        AluOp::FCvtSWu => sf.int_to_float(fmt, a, false, 32),
+        AluOp::FCvtSLu => sf.int_to_float(fmt, a, false, 64),
+        AluOp::FSgnJ => sf.sgnj(fmt, a, b),
+        AluOp::FSgnJN => sf.sgnjn(fmt, a, b),
+        AluOp::FSgnJX => sf.sgnjx(fmt, a, b),
+        AluOp::FMin => sf.min_max(fmt, a, b, false),
+        AluOp::FMax => sf.min_max(fmt, a, b, true),
+        AluOp::FEq => sf.eq(fmt, a, b),
+        AluOp::FLt => sf.lt(fmt, a, b),
+        AluOp::FLe => sf.le(fmt, a, b),
+        AluOp::FClass => sf.classify(fmt, a),
+        // Moves transfer raw bits; FMV.X.W/H sign-extend without checking the NaN-box
+        AluOp::FMvToF => fmt.box_bits(a),
+        AluOp::FMvToX => match fmt {
+            FpFormat::Half => a as u16 as i16 as i64 as u64,
+            FpFormat::Single => a as u32 as i32 as i64 as u64,
+            FpFormat::Double => a,
+        },
+        _ => 0,
+    }
+}
+
+/// An instruction that traps instead of executing. The trap is taken when it
+/// reaches write-back, once everything older has retired.
+fn trap_entry(id: &IdExEntry, trap: Trap) -> ExMemEntry {
+    ExMemEntry {
+        pc: id.pc,
+        inst: id.inst,
+        inst_size: id.inst_size,
+        trap: Some(trap),
+        ..Default::default()
     }
 }
 
@@ -235,21 +378,28 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
 
     let entries = cpu.id_ex.entries.clone();
 
-    for id in entries {
+    // Instructions start in order, so one waiting for a busy unit holds the
+    // stage and everything behind it
+    let now = cpu.stats.cycles;
+    let mut issue_at = now;
+    let mut waited_on = None;
+    let mut unit_results = Vec::new();
+
+    for mut id in entries {
         if flush_remaining {
             break;
         }
 
+        // A pending icount trigger breaks before the next instruction
+        if cpu.csrs.triggers.take_pending() {
+            ex_results.push(trap_entry(&id, Trap::Breakpoint(id.pc)));
+            flush_remaining = true;
+            continue;
+        }
+
         if let Some(trap) = id.trap.clone() {
-            ex_results.push(ExMemEntry {
-                pc: id.pc,
-                inst: id.inst,
-                rd: id.rd,
-                alu: 0,
-                store_data: 0,
-                ctrl: id.ctrl,
-                trap: Some(trap),
-            });
+            ex_results.push(trap_entry(&id, trap));
+            flush_remaining = true;
             continue;
         }
 
@@ -257,6 +407,10 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
             eprintln!("EX  pc={:#x}", id.pc);
         }
 
+        if let Some((privilege, virt)) = cpu.trigger_mode() {
+            cpu.csrs.triggers.count_instruction(privilege, virt);
+        }
+
         let (fwd_a, fwd_b, fwd_c) =
             crate::core::control::forward_rs(&id, &cpu.ex_mem, &cpu.wb_latch);
         let store_data = fwd_b;
@@ -273,7 +427,81 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
         };
         let op_c = fwd_c;
 
+        // HLV/HSV belong to HS-mode, and to U-mode when hstatus.HU allows them
+        if id.ctrl.hyper_mem {
+            let hu = cpu.csrs.hstatus & csr::HSTATUS_HU != 0;
+            let trap = if cpu.virt {
+                Some(Trap::VirtualInstruction(id.inst))
+            } else if cpu.privilege == 0 && !hu {
+                Some(Trap::IllegalInstruction(id.inst))
+            } else {
+                None
+            };
+            if let Some(trap) = trap {
+                ex_results.push(trap_entry(&id, trap));
+                flush_remaining = true;
+                continue;
+            }
+        }
+
+        // Below M-mode the envcfg enables decide whether a cache-block operation runs
+        match cpu.cache_block_enabled(id.ctrl.cache_op, id.inst) {
+            Ok(op) => id.ctrl.cache_op = op,
+            Err(trap) => {
+                ex_results.push(trap_entry(&id, trap));
+                flush_remaining = true;
+                continue;
+            }
+        }
+
+        if id.ctrl.is_fence || id.ctrl.is_fence_i {
+            // Decode holds a fence until everything older has retired; refetching
+            // after it keeps younger accesses from overlapping with them.
+            if id.ctrl.is_fence_i {
+                if cpu.trace {
+                    eprintln!("EX  FENCE.I - Invalidating L1-I");
+                }
+                cpu.l1_i_cache.invalidate_all();
+            }
+
+            cpu.if_id = IfId::default();
+            cpu.pc = id.pc.wrapping_add(id.inst_size);
+            flush_remaining = true;
+
+            ex_results.push(ExMemEntry {
+                pc: id.pc,
+                inst: id.inst,
+                inst_size: id.inst_size,
+                rd: id.rd,
+                alu: 0,
+                store_data,
+                ctrl: id.ctrl,
+                trap: None,
+            });
+            continue;
+        }
+
         if id.ctrl.is_system {
+            // Only M-mode may mret. VU-mode never may sret, nor VS-mode under VTSR;
+            // U-mode never may either, nor HS-mode under TSR.
+            if id.ctrl.is_mret && cpu.privilege != 3 {
+                ex_results.push(trap_entry(&id, Trap::IllegalInstruction(id.inst)));
+                flush_remaining = true;
+                continue;
+            }
+            let vtsr = cpu.csrs.hstatus & csr::HSTATUS_VTSR != 0;
+            if id.ctrl.is_sret && cpu.virt && (cpu.privilege == 0 || vtsr) {
+                ex_results.push(trap_entry(&id, Trap::VirtualInstruction(id.inst)));
+                flush_remaining = true;
+                continue;
+            }
+            let tsr = cpu.csrs.mstatus & csr::MSTATUS_TSR != 0;
+            if id.ctrl.is_sret && (cpu.privilege == 0 || (cpu.privilege == 1 && tsr)) {
+                ex_results.push(trap_entry(&id, Trap::IllegalInstruction(id.inst)));
+                flush_remaining = true;
+                continue;
+            }
+
             if id.ctrl.is_mret {
                 cpu.do_mret();
                 flush_remaining = true;
@@ -287,15 +515,87 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
                 continue;
             }
 
-            if id.inst == sys_ops::SFENCE_VMA {
+            let fence = id.inst & sys_ops::FENCE_VMA_MASK;
+            if matches!(
+                fence,
+                sys_ops::SFENCE_VMA | sys_ops::HFENCE_VVMA | sys_ops::HFENCE_GVMA
+            ) {
+                // Fences are virtual-instruction faults in VU-mode, and in VS-mode for
+                // HFENCE or under VTVM; U-mode may never use them, nor HS-mode
+                // SFENCE.VMA or HFENCE.GVMA under TVM
+                let hfence = fence != sys_ops::SFENCE_VMA;
+                let vtvm = cpu.csrs.hstatus & csr::HSTATUS_VTVM != 0;
+                let tvm = cpu.csrs.mstatus & csr::MSTATUS_TVM != 0;
+                let trap = if cpu.virt && (cpu.privilege == 0 || hfence || vtvm) {
+                    Some(Trap::VirtualInstruction(id.inst))
+                } else if cpu.privilege == 0
+                    || (cpu.privilege == 1 && tvm && fence != sys_ops::HFENCE_VVMA)
+                {
+                    Some(Trap::IllegalInstruction(id.inst))
+                } else {
+                    None
+                };
+                if let Some(trap) = trap {
+                    ex_results.push(trap_entry(&id, trap));
+                    flush_remaining = true;
+                    continue;
+                }
+
                 if cpu.trace {
-                    eprintln!("EX  SFENCE.VMA - Flushing TLBs");
+                    let name = if hfence { "HFENCE" } else { "SFENCE.VMA" };
+                    eprintln!("EX  {} - Flushing TLBs", name);
+                }
+                // Guest TLB entries depend on both stages, so any fence from or
+                // about a guest drops them all
+                if cpu.virt || hfence {
+                    cpu.mmu.flush_guest();
+                } else {
+                    cpu.mmu.dtlb.flush();
+                    cpu.mmu.itlb.flush();
                 }
-                cpu.mmu.dtlb.flush();
-                cpu.mmu.itlb.flush();
                 ex_results.push(ExMemEntry {
                     pc: id.pc,
                     inst: id.inst,
+                    inst_size: id.inst_size,
+                    rd: id.rd,
+                    alu: 0,
+                    store_data,
+                    ctrl: id.ctrl,
+                    trap: None,
+                });
+                continue;
+            }
+
+            if id.inst == sys_ops::WFI {
+                // U-mode may never wait; S-mode only while mstatus.TW is clear.
+                // Guests that could wait in HS-mode fault to the hypervisor instead.
+                let tw = cpu.csrs.mstatus & csr::MSTATUS_TW != 0;
+                let vtw = cpu.csrs.hstatus & csr::HSTATUS_VTW != 0;
+                let trap = if (cpu.privilege == 0 && !cpu.virt) || (cpu.privilege < 3 && tw) {
+                    Some(Trap::IllegalInstruction(id.inst))
+                } else if cpu.virt && (cpu.privilege == 0 || vtw) {
+                    Some(Trap::VirtualInstruction(id.inst))
+                } else {
+                    None
+                };
+                if let Some(trap) = trap {
+                    ex_results.push(trap_entry(&id, trap));
+                    flush_remaining = true;
+                    continue;
+                }
+
+                if cpu.trace {
+                    eprintln!("EX  WFI - Waiting for interrupt");
+                }
+                // Younger instructions are refetched after wake-up
+                cpu.wfi = true;
+                cpu.pc = id.pc.wrapping_add(id.inst_size);
+                cpu.if_id = IfId::default();
+                flush_remaining = true;
+                ex_results.push(ExMemEntry {
+                    pc: id.pc,
+                    inst: id.inst,
+                    inst_size: id.inst_size,
                     rd: id.rd,
                     alu: 0,
                     store_data,
@@ -306,21 +606,16 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
             }
 
             if id.inst == sys_ops::ECALL {
-                // Helper to resolve register values including current bundle
-                let get_val = |reg: usize, cpu: &Cpu, current_results: &[ExMemEntry]| -> u64 {
+                // Helper to resolve register values from older instructions in
+                // flight; decode keeps writers of a0/a1/a7 out of this bundle
+                let get_val = |reg: usize, cpu: &Cpu| -> u64 {
                     if reg == 0 {
                         return 0;
                     }
-                    for entry in current_results.iter().rev() {
-                        if entry.ctrl.reg_write && entry.rd == reg {
-                            return entry.alu;
-                        }
-                    }
-
                     for entry in cpu.ex_mem.entries.iter().rev() {
                         if entry.ctrl.reg_write && entry.rd == reg {
                             return if entry.ctrl.jump {
-                                entry.pc.wrapping_add(4)
+                                entry.pc.wrapping_add(entry.inst_size)
                             } else {
                                 entry.alu
                             };
@@ -332,7 +627,7 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
                             return if entry.ctrl.mem_read {
                                 entry.load_data
                             } else if entry.ctrl.jump {
-                                entry.pc.wrapping_add(4)
+                                entry.pc.wrapping_add(entry.inst_size)
                             } else {
                                 entry.alu
                             };
@@ -343,15 +638,15 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
 
                 // Only intercept SYS_EXIT if we are in Direct Mode (no kernel).
                 if cpu.direct_mode {
-                    let val_a7 = get_val(abi::REG_A7, cpu, &ex_results);
-                    let val_a0 = get_val(abi::REG_A0, cpu, &ex_results);
+                    let val_a7 = get_val(abi::REG_A7, cpu);
+                    let val_a0 = get_val(abi::REG_A0, cpu);
 
                     if val_a7 == sys_ops::SYS_EXIT {
                         cpu.exit_code = Some(val_a0);
                         return Ok(());
                     } else if val_a0 == sys_ops::SYS_EXIT {
                         // Handle legacy/alternative convention if needed
-                        let val_a1 = get_val(abi::REG_A1, cpu, &ex_results);
+                        let val_a1 = get_val(abi::REG_A1, cpu);
                         cpu.exit_code = Some(val_a1);
                         return Ok(());
                     }
@@ -359,18 +654,40 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
 
                 let trap = match cpu.privilege {
                     0 => Trap::EnvironmentCallFromUMode,
+                    1 if cpu.virt => Trap::EnvironmentCallFromVSMode,
                     1 => Trap::EnvironmentCallFromSMode,
                     3 => Trap::EnvironmentCallFromMMode,
                     _ => Trap::EnvironmentCallFromMMode,
                 };
 
-                cpu.trap(trap, id.pc);
+                ex_results.push(trap_entry(&id, trap));
                 flush_remaining = true;
                 continue;
             }
 
             if id.ctrl.csr_op != CsrOp::None {
-                let old = cpu.csr_read(id.ctrl.csr_addr);
+                let addr = id.ctrl.csr_addr;
+                // CSRRS/CSRRC with x0 (or a zero immediate) read without writing
+                let writes = id.rs1 != 0
+                    || !matches!(
+                        id.ctrl.csr_op,
+                        CsrOp::Rs | CsrOp::Rc | CsrOp::Rsi | CsrOp::Rci
+                    );
+                let old = if cpu.csr_accessible(addr, writes) {
+                    cpu.csr_read(addr)
+                } else {
+                    None
+                };
+                let Some(old) = old else {
+                    let trap = if cpu.csr_virtual_fault(addr, writes) {
+                        Trap::VirtualInstruction(id.inst)
+                    } else {
+                        Trap::IllegalInstruction(id.inst)
+                    };
+                    ex_results.push(trap_entry(&id, trap));
+                    flush_remaining = true;
+                    continue;
+                };
                 let src = match id.ctrl.csr_op {
                     CsrOp::Rwi | CsrOp::Rsi | CsrOp::Rci => (id.rs1 as u64) & 0x1f,
                     _ => fwd_a,
@@ -381,15 +698,18 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
                     CsrOp::Rc | CsrOp::Rci => old & !src,
                     CsrOp::None => old,
                 };
-                cpu.csr_write(id.ctrl.csr_addr, new);
+                if writes {
+                    cpu.csr_write(addr, new);
+                }
 
                 cpu.if_id = IfId::default();
-                cpu.pc = id.pc.wrapping_add(4);
+                cpu.pc = id.pc.wrapping_add(id.inst_size);
                 flush_remaining = true;
 
                 ex_results.push(ExMemEntry {
                     pc: id.pc,
                     inst: id.inst,
+                    inst_size: id.inst_size,
                     rd: id.rd,
                     alu: old,
                     store_data,
@@ -400,48 +720,67 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
             }
         }
 
-        let alu_out = if (id.ctrl.alu as i32 >= AluOp::FCvtSW as i32
-            && id.ctrl.alu as i32 <= AluOp::FCvtSL as i32)
-            || id.ctrl.alu as i32 == AluOp::FMvToF as i32
-        {
-            match id.ctrl.alu {
-                AluOp::FCvtSW => {
-                    if id.ctrl.is_rv32 {
-                        box_f32((op_a as i32) as f32)
-                    } else {
-                        ((op_a as i32) as f64).to_bits()
-                    }
-                }
-                AluOp::FCvtSL => {
-                    if id.ctrl.is_rv32 {
-                        box_f32((op_a as i64) as f32)
-                    } else {
-                        ((op_a as i64) as f64).to_bits()
-                    }
-                }
-                AluOp::FCvtSD => {
-                    let val_d = f64::from_bits(op_a);
-                    let val_s = val_d as f32;
-                    box_f32(val_s)
-                }
-                AluOp::FCvtDS => {
-                    let val_s = f32::from_bits(op_a as u32);
-                    let val_d = val_s as f64;
-                    val_d.to_bits()
-                }
-                AluOp::FMvToF => {
-                    if id.ctrl.is_rv32 {
-                        box_f32(f32::from_bits(op_a as u32))
-                    } else {
-                        op_a
+        if id.ctrl.vec_op != VecOp::None && !id.ctrl.vec_op.is_memory() {
+            let (alu_out, trap) =
+                match vpu::execute::execute(cpu, id.ctrl.vec_op, id.inst, op_a, op_b) {
+                    Ok(val) => (val, None),
+                    Err(trap) => {
+                        flush_remaining = true;
+                        (0, Some(trap))
                     }
-                }
-                _ => 0,
+                };
+            ex_results.push(ExMemEntry {
+                pc: id.pc,
+                inst: id.inst,
+                inst_size: id.inst_size,
+                rd: id.rd,
+                alu: alu_out,
+                store_data,
+                ctrl: id.ctrl,
+                trap,
+            });
+            continue;
+        }
+
+        let alu_out = if is_fp_op(id.ctrl.alu) {
+            let rm_bits = match (id.inst >> 12) as u64 & 0x7 {
+                RM_DYNAMIC => cpu.csrs.frm,
+                rm => rm,
+            };
+            let rm = if uses_rounding_mode(id.ctrl.alu) {
+                RoundingMode::from_bits(rm_bits)
+            } else {
+                Some(RoundingMode::default())
+            };
+
+            // Reserved rounding modes, static or via frm, are illegal
+            let Some(rm) = rm else {
+                ex_results.push(trap_entry(&id, Trap::IllegalInstruction(id.inst)));
+                flush_remaining = true;
+                continue;
+            };
+
+            let mut sf = SoftFloat::new(rm);
+            let res = fpu(id.ctrl.alu, op_a, op_b, op_c, id.ctrl.fp_fmt, &mut sf);
+            if sf.flags != 0 {
+                cpu.csrs.fflags |= sf.flags;
+                cpu.mark_fs_dirty();
             }
+            res
         } else {
-            alu(id.ctrl.alu, op_a, op_b, op_c, id.ctrl.is_rv32)
+            alu(id.ctrl.alu, op_a, op_b, id.ctrl.is_rv32)
         };
 
+        if let Some(class) = FuClass::of(&id.ctrl)
+            && let Some((start, complete)) = cpu.units.issue(class, issue_at)
+        {
+            if start > issue_at {
+                issue_at = start;
+                waited_on = Some(class);
+            }
+            unit_results.push((ex_results.len(), complete, class));
+        }
+
         if id.ctrl.branch {
             let taken = match (id.inst >> 12) & 0x7 {
                 funct3::BEQ => op_a == op_b,
@@ -453,7 +792,7 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
                 _ => false,
             };
             let actual_target = id.pc.wrapping_add(id.imm as u64);
-            let fallthrough = id.pc.wrapping_add(4);
+            let fallthrough = id.pc.wrapping_add(id.inst_size);
 
             let predicted_target = if id.pred_taken {
                 id.pred_target
@@ -496,7 +835,7 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
             let predicted_target = if id.pred_taken {
                 id.pred_target
             } else {
-                id.pc.wrapping_add(4)
+                id.pc.wrapping_add(id.inst_size)
             };
 
             if actual_target != predicted_target {
@@ -511,7 +850,7 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
 
             if is_call {
                 cpu.branch_predictor
-                    .on_call(id.pc, id.pc.wrapping_add(4), actual_target);
+                    .on_call(id.pc, id.pc.wrapping_add(id.inst_size), actual_target);
             } else if is_ret {
                 cpu.branch_predictor.on_return();
             }
@@ -520,6 +859,7 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
         ex_results.push(ExMemEntry {
             pc: id.pc,
             inst: id.inst,
+            inst_size: id.inst_size,
             rd: id.rd,
             alu: alu_out,
             store_data,
@@ -528,6 +868,27 @@ pub fn execute_stage(cpu: &mut Cpu) -> Result<(), String> {
         });
     }
 
+    if let Some(class) = waited_on {
+        let stall = issue_at - now;
+        cpu.alu_timer += stall;
+        cpu.stats.stalls_units[class as usize] += stall;
+    }
+
+    // Results forward to the next cycle's bundle, loads one cycle later from
+    // MEM, and functional units once their latency has passed
+    for (idx, entry) in ex_results.iter().enumerate() {
+        if entry.trap.is_some() || !(entry.ctrl.reg_write || entry.ctrl.fp_reg_write) {
+            continue;
+        }
+        let (ready, unit) = match unit_results.iter().find(|&&(i, ..)| i == idx) {
+            Some(&(_, complete, class)) => (complete, Some(class)),
+            None if entry.ctrl.mem_read => (issue_at + 2, None),
+            None => (issue_at + 1, None),
+        };
+        cpu.scoreboard
+            .reserve(entry.rd, entry.ctrl.fp_reg_write, ready, unit);
+    }
+
     cpu.ex_mem = ExMem {
         entries: ex_results,
     };
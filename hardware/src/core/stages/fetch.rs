@@ -1,20 +1,53 @@
 use crate::core::Cpu;
 use crate::core::pipeline::{IfId, IfIdEntry};
-use crate::core::types::{AccessType, TranslationResult, Trap, VirtAddr};
-use crate::isa::{abi, opcodes};
+use crate::core::types::{AccessType, PhysAddr, TranslationResult, Trap, VirtAddr};
+use crate::isa::{abi, csr, opcodes, rvc};
+
+const PAGE_SIZE: u64 = 4096;
+
+/// Ends the fetch group at a faulting address. Only the first instruction of a
+/// group carries the fault; a later one is left to the next fetch, which only
+/// reaches it if the instructions before it do not redirect.
+fn fetch_fault(fetched: &mut Vec<IfIdEntry>, pc: u64, trap: Trap) {
+    if fetched.is_empty() {
+        fetched.push(IfIdEntry {
+            pc,
+            trap: Some(trap),
+            ..Default::default()
+        });
+    }
+}
 
 pub fn fetch_stage(cpu: &mut Cpu) -> Result<(), String> {
     let mut fetched = Vec::new();
     let mut current_pc = cpu.pc;
 
+    // IALIGN is 16 with the C extension enabled, 32 otherwise
+    let align_mask = if (cpu.csrs.misa & csr::MISA_C) != 0 {
+        1
+    } else {
+        3
+    };
+
     for _ in 0..cpu.pipeline_width {
-        if current_pc % 4 != 0 {
-            if fetched.is_empty() {
-                return Err(format!(
-                    "{:?}",
-                    Trap::InstructionAddressMisaligned(current_pc)
-                ));
-            }
+        if (current_pc & align_mask) != 0 {
+            fetch_fault(
+                &mut fetched,
+                current_pc,
+                Trap::InstructionAddressMisaligned(current_pc),
+            );
+            break;
+        }
+
+        // Execute triggers match the address before anything is fetched from it
+        if let Some((privilege, virt)) = cpu.trigger_mode()
+            && cpu.csrs.triggers.match_execute(current_pc, privilege, virt)
+        {
+            fetched.push(IfIdEntry {
+                pc: current_pc,
+                trap: Some(Trap::Breakpoint(current_pc)),
+                ..Default::default()
+            });
             break;
         }
 
@@ -22,42 +55,81 @@ pub fn fetch_stage(cpu: &mut Cpu) -> Result<(), String> {
             paddr,
             cycles,
             trap,
-        } = cpu.translate(VirtAddr::new(current_pc), AccessType::Fetch);
+            ..
+        } = cpu.translate(VirtAddr::new(current_pc), AccessType::Fetch, 2);
         cpu.stall_cycles += cycles;
 
-        if let Some(trap_msg) = trap {
-            if fetched.is_empty() {
-                return Err(format!("{:?}", trap_msg));
-            }
+        if let Some(trap) = trap {
+            fetch_fault(&mut fetched, current_pc, trap);
             break;
         }
 
         let latency = cpu.simulate_memory_access(paddr, AccessType::Fetch);
         cpu.stall_cycles += latency;
 
-        let inst = cpu.bus.bus.read_u32(paddr.val());
+        let low = cpu.bus.bus.read_u16(paddr.val());
+
+        let (inst, inst_size) = if rvc::is_compressed(low) {
+            (low as u32, 2)
+        } else {
+            let upper_pc = current_pc.wrapping_add(2);
+
+            // The upper half of a 32-bit instruction may live on the next page
+            let upper_paddr = if upper_pc.is_multiple_of(PAGE_SIZE) {
+                let TranslationResult {
+                    paddr: upper,
+                    cycles,
+                    trap,
+                    ..
+                } = cpu.translate(VirtAddr::new(upper_pc), AccessType::Fetch, 2);
+                cpu.stall_cycles += cycles;
+
+                if let Some(trap) = trap {
+                    fetch_fault(&mut fetched, current_pc, trap);
+                    break;
+                }
+                upper
+            } else {
+                PhysAddr::new(paddr.val().wrapping_add(2))
+            };
+
+            // ...or on the next cache line
+            let line_mask = !(cpu.l1_i_cache.line_bytes() as u64 - 1);
+            if (paddr.val() & line_mask) != (upper_paddr.val() & line_mask) {
+                let latency = cpu.simulate_memory_access(upper_paddr, AccessType::Fetch);
+                cpu.stall_cycles += latency;
+            }
+
+            let high = cpu.bus.bus.read_u16(upper_paddr.val());
+            (((high as u32) << 16) | low as u32, 4)
+        };
 
         if cpu.trace {
             eprintln!("IF  pc={:#x} inst={:#010x}", current_pc, inst);
         }
 
-        let opcode = inst & 0x7f;
-        let rd = ((inst >> 7) & 0x1f) as usize;
-        let rs1 = ((inst >> 15) & 0x1f) as usize;
-        let mut next_pc_calc = current_pc.wrapping_add(4);
+        // Branch prediction works on the expanded form of compressed instructions
+        let bits = if inst_size == 2 {
+            rvc::expand(inst as u16).unwrap_or(0)
+        } else {
+            inst
+        };
+
+        let opcode = bits & 0x7f;
+        let rd = ((bits >> 7) & 0x1f) as usize;
+        let rs1 = ((bits >> 15) & 0x1f) as usize;
+        let mut next_pc_calc = current_pc.wrapping_add(inst_size);
         let mut pred_taken = false;
         let mut pred_target = 0;
         let mut stop_fetch = false;
 
         if opcode == opcodes::OP_BRANCH {
             let (taken, target) = cpu.branch_predictor.predict_branch(current_pc);
-            if taken {
-                if let Some(tgt) = target {
-                    next_pc_calc = tgt;
-                    pred_taken = true;
-                    pred_target = tgt;
-                    stop_fetch = true;
-                }
+            if taken && let Some(tgt) = target {
+                next_pc_calc = tgt;
+                pred_taken = true;
+                pred_target = tgt;
+                stop_fetch = true;
             }
         } else if opcode == opcodes::OP_JAL {
             if let Some(tgt) = cpu.branch_predictor.predict_btb(current_pc) {
@@ -84,8 +156,10 @@ pub fn fetch_stage(cpu: &mut Cpu) -> Result<(), String> {
         fetched.push(IfIdEntry {
             pc: current_pc,
             inst,
+            inst_size,
             pred_taken,
             pred_target,
+            trap: None,
         });
 
         current_pc = next_pc_calc;
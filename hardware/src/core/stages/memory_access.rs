@@ -1,7 +1,10 @@
 use crate::core::Cpu;
-use crate::core::control::{AtomicOp, MemWidth};
-use crate::core::pipeline::{MemWb, MemWbEntry};
+use crate::core::control::{AtomicOp, CacheOp, MemWidth};
+use crate::core::mmu::AccessContext;
+use crate::core::pipeline::{ExMemEntry, IdEx, IfId, MemWb, MemWbEntry};
 use crate::core::types::{AccessType, TranslationResult, Trap, VirtAddr};
+use crate::core::vpu;
+use crate::isa::csr;
 
 fn atomic_alu(op: AtomicOp, mem_val: u64, reg_val: u64, width: MemWidth) -> u64 {
     if matches!(width, MemWidth::Word) {
@@ -39,6 +42,60 @@ fn atomic_alu(op: AtomicOp, mem_val: u64, reg_val: u64, width: MemWidth) -> u64
     }
 }
 
+/// Performs a Zicbom/Zicboz cache-block operation or a Zicbop prefetch on
+/// the block containing `ex.alu`.
+fn cache_block_op(cpu: &mut Cpu, ex: &ExMemEntry) -> Option<Trap> {
+    let op = ex.ctrl.cache_op;
+    // Management operations need read or write permission, and fault as stores
+    let access_type = match op {
+        CacheOp::PrefetchI => AccessType::Fetch,
+        CacheOp::PrefetchR | CacheOp::Inval | CacheOp::Clean | CacheOp::Flush => AccessType::Read,
+        _ => AccessType::Write,
+    };
+
+    // The operation covers the whole block, so all of it must pass the PMP check
+    let block_bytes = cpu.l1_d_cache.line_bytes() as u64;
+    let block_vaddr = ex.alu & !(block_bytes - 1);
+
+    let vaddr = VirtAddr::new(block_vaddr);
+    let mut result = cpu.translate(vaddr, access_type, block_bytes);
+    if result.trap.is_some() && access_type == AccessType::Read && !op.is_prefetch() {
+        cpu.stall_cycles += result.cycles;
+        result = cpu.translate(vaddr, AccessType::Write, block_bytes);
+    }
+    let TranslationResult {
+        paddr,
+        cycles,
+        trap: fault,
+        ..
+    } = result;
+    cpu.stall_cycles += cycles;
+
+    if let Some(t) = fault {
+        // Prefetches are hints and never trap
+        return if op.is_prefetch() { None } else { Some(t) };
+    }
+
+    if paddr.val() < cpu.mmio_base {
+        let lat = cpu.simulate_cache_op(paddr, op);
+        cpu.stall_cycles += lat;
+    }
+
+    if op == CacheOp::Zero {
+        let base = paddr.val() & !(block_bytes - 1);
+        for off in (0..block_bytes).step_by(8) {
+            cpu.bus.bus.write_u64(base + off, 0);
+        }
+        if cpu
+            .load_reservation
+            .is_some_and(|r| r & !(block_bytes - 1) == base)
+        {
+            cpu.load_reservation = None;
+        }
+    }
+    None
+}
+
 pub fn mem_stage(cpu: &mut Cpu) -> Result<(), String> {
     let mut mem_results = Vec::new();
     let entries = cpu.ex_mem.entries.clone();
@@ -46,8 +103,9 @@ pub fn mem_stage(cpu: &mut Cpu) -> Result<(), String> {
     for ex in entries {
         let mut ld = 0;
         let mut trap = ex.trap.clone();
+        let is_vector = ex.ctrl.vec_op.is_memory();
 
-        if ex.ctrl.mem_read || ex.ctrl.mem_write {
+        if !is_vector && (ex.ctrl.mem_read || ex.ctrl.mem_write) {
             let align_mask = match ex.ctrl.width {
                 MemWidth::Byte => 0,
                 MemWidth::Half => 1,
@@ -65,7 +123,42 @@ pub fn mem_stage(cpu: &mut Cpu) -> Result<(), String> {
             }
         }
 
-        if trap.is_none() && (ex.ctrl.mem_read || ex.ctrl.mem_write) {
+        // Load and store triggers break before the access, or after it under mcontrol.timing
+        let mut break_after = false;
+        if ex.trap.is_none()
+            && !is_vector
+            && ex.ctrl.cache_op == CacheOp::None
+            && (ex.ctrl.mem_read || ex.ctrl.mem_write)
+            && let Some((privilege, virt)) = cpu.trigger_mode()
+            && let Some(after) = cpu.csrs.triggers.match_data(
+                ex.alu,
+                ex.ctrl.mem_read,
+                ex.ctrl.mem_write,
+                privilege,
+                virt,
+            )
+        {
+            if !after {
+                trap = Some(Trap::Breakpoint(ex.alu));
+            } else if trap.is_none() {
+                break_after = true;
+            }
+        }
+
+        if trap.is_none() && is_vector {
+            if cpu.trace {
+                eprintln!("MEM pc={:#x} VECTOR addr={:#x}", ex.pc, ex.alu);
+            }
+            trap = vpu::memory::access(cpu, &ex);
+        } else if trap.is_none() && ex.ctrl.cache_op != CacheOp::None {
+            if cpu.trace {
+                eprintln!(
+                    "MEM pc={:#x} CBO {:?} addr={:#x}",
+                    ex.pc, ex.ctrl.cache_op, ex.alu
+                );
+            }
+            trap = cache_block_op(cpu, &ex);
+        } else if trap.is_none() && (ex.ctrl.mem_read || ex.ctrl.mem_write) {
             if cpu.trace {
                 if ex.ctrl.mem_read {
                     eprintln!("MEM pc={:#x} LOAD addr={:#x}", ex.pc, ex.alu);
@@ -83,11 +176,28 @@ pub fn mem_stage(cpu: &mut Cpu) -> Result<(), String> {
                 AccessType::Read
             };
 
+            // HLV/HSV access memory as the guest last trapped from
+            let ctx = if ex.ctrl.hyper_mem {
+                AccessContext {
+                    privilege: (cpu.csrs.hstatus & csr::HSTATUS_SPVP != 0) as u8,
+                    virt: true,
+                    hlvx: ex.ctrl.hlvx,
+                }
+            } else {
+                cpu.access_context()
+            };
+
             let TranslationResult {
                 paddr,
                 cycles,
                 trap: fault,
-            } = cpu.translate(VirtAddr::new(ex.alu), access_type);
+                ..
+            } = cpu.translate_in(
+                VirtAddr::new(ex.alu),
+                access_type,
+                ex.ctrl.width.bytes(),
+                ctx,
+            );
             cpu.stall_cycles += cycles;
 
             if let Some(t) = fault {
@@ -175,8 +285,8 @@ pub fn mem_stage(cpu: &mut Cpu) -> Result<(), String> {
                             (MemWidth::Double, _) => cpu.bus.bus.read_u64(raw_paddr),
                             _ => 0,
                         };
-                        if ex.ctrl.fp_reg_write && matches!(ex.ctrl.width, MemWidth::Word) {
-                            ld |= 0xFFFF_FFFF_0000_0000;
+                        if ex.ctrl.fp_reg_write {
+                            ld = ex.ctrl.fp_fmt.box_bits(ld);
                         }
                     } else if ex.ctrl.mem_write {
                         if cpu.load_reservation == Some(raw_paddr) {
@@ -201,15 +311,29 @@ pub fn mem_stage(cpu: &mut Cpu) -> Result<(), String> {
             eprintln!("MEM pc={:#x}", ex.pc);
         }
 
+        let trapped = trap.is_some();
         mem_results.push(MemWbEntry {
             pc: ex.pc,
             inst: ex.inst,
+            inst_size: ex.inst_size,
             rd: ex.rd,
             alu: ex.alu,
             load_data: ld,
             ctrl: ex.ctrl,
             trap,
         });
+
+        if break_after {
+            cpu.trap(Trap::Breakpoint(ex.alu), ex.pc.wrapping_add(ex.inst_size));
+            break;
+        }
+
+        // The trap is taken at write-back; nothing younger may execute meanwhile
+        if trapped {
+            cpu.id_ex = IdEx::default();
+            cpu.if_id = IfId::default();
+            break;
+        }
     }
 
     cpu.mem_wb = MemWb {
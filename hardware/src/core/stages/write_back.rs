@@ -1,19 +1,43 @@
 use crate::core::Cpu;
 use crate::core::control::AluOp;
-use crate::core::types::Trap;
+use crate::core::fpu::FpFormat;
+use crate::core::pipeline::ExMem;
+use crate::core::vpu::VecOp;
 
-pub fn wb_stage(cpu: &mut Cpu) -> Result<(), Trap> {
-    for wb in &cpu.mem_wb.entries {
-        if let Some(trap) = &wb.trap {
-            return Err(trap.clone());
-        }
+pub fn wb_stage(cpu: &mut Cpu) -> Result<(), String> {
+    // Instructions older than a trapping one retire; it and everything younger do not
+    let trapping = cpu
+        .mem_wb
+        .entries
+        .iter()
+        .enumerate()
+        .find_map(|(idx, wb)| Some((idx, wb.pc, wb.trap.clone()?)));
+    let retiring = trapping
+        .as_ref()
+        .map_or(cpu.mem_wb.entries.len(), |&(idx, ..)| idx);
 
+    for wb in &cpu.mem_wb.entries[..retiring] {
         if cpu.trace {
             eprintln!("WB  pc={:#x}", wb.pc);
         }
 
         if wb.inst != 0 && wb.inst != 0x13 {
             cpu.stats.instructions_retired += 1;
+
+            let is_fp = wb.ctrl.fp_reg_write || wb.ctrl.rs1_fp || wb.ctrl.rs2_fp;
+            if (is_fp && wb.ctrl.fp_fmt == FpFormat::Half)
+                || matches!(wb.ctrl.alu, AluOp::FCvtSH | AluOp::FCvtDH)
+            {
+                cpu.stats.inst_fp_half += 1;
+            }
+
+            if wb.ctrl.vec_op != VecOp::None {
+                cpu.stats.inst_vector += 1;
+            }
+            if wb.ctrl.alu.is_crypto() {
+                cpu.stats.inst_crypto += 1;
+            }
+
             if wb.ctrl.mem_read {
                 if wb.ctrl.fp_reg_write {
                     cpu.stats.inst_fp_load += 1;
@@ -48,8 +72,16 @@ pub fn wb_stage(cpu: &mut Cpu) -> Result<(), Trap> {
                     | AluOp::FCvtLS
                     | AluOp::FCvtSW
                     | AluOp::FCvtSL
+                    | AluOp::FCvtWuS
+                    | AluOp::FCvtLuS
+                    | AluOp::FCvtSWu
+                    | AluOp::FCvtSLu
                     | AluOp::FCvtSD
                     | AluOp::FCvtDS
+                    | AluOp::FCvtSH
+                    | AluOp::FCvtHS
+                    | AluOp::FCvtDH
+                    | AluOp::FCvtHD
                     | AluOp::FMvToX
                     | AluOp::FMvToF => cpu.stats.inst_fp_arith += 1,
                     AluOp::FDiv | AluOp::FSqrt => cpu.stats.inst_fp_div_sqrt += 1,
@@ -64,7 +96,7 @@ pub fn wb_stage(cpu: &mut Cpu) -> Result<(), Trap> {
         let val = if wb.ctrl.mem_read {
             wb.load_data
         } else if wb.ctrl.jump {
-            wb.pc.wrapping_add(4)
+            wb.pc.wrapping_add(wb.inst_size)
         } else {
             wb.alu
         };
@@ -83,5 +115,14 @@ pub fn wb_stage(cpu: &mut Cpu) -> Result<(), Trap> {
             cpu.regs.write(wb.rd, val);
         }
     }
+
+    if let Some((idx, pc, trap)) = trapping {
+        if cpu.trace {
+            eprintln!("WB  pc={:#x} TRAP {:?}", pc, trap);
+        }
+        cpu.mem_wb.entries.truncate(idx);
+        cpu.ex_mem = ExMem::default();
+        cpu.trap(trap, pc);
+    }
     Ok(())
 }
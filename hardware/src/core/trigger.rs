@@ -0,0 +1,303 @@
+// tdata1.type values
+const TYPE_SHIFT: u32 = 60;
+const TYPE_NONE: u64 = 0;
+const TYPE_MCONTROL: u64 = 2;
+const TYPE_ICOUNT: u64 = 3;
+const TYPE_MCONTROL6: u64 = 6;
+const TYPE_DISABLED: u64 = 15;
+
+// Fields shared by mcontrol and mcontrol6; action, select and size are
+// hardwired to zero (breakpoint exception, address match, any size)
+const MATCH_SHIFT: u32 = 7;
+const MATCH: u64 = 0xF << MATCH_SHIFT;
+const CHAIN: u64 = 1 << 11;
+const M: u64 = 1 << 6;
+const S: u64 = 1 << 4;
+const U: u64 = 1 << 3;
+const EXECUTE: u64 = 1 << 2;
+const STORE: u64 = 1 << 1;
+const LOAD: u64 = 1 << 0;
+const ADDRESS_FIELDS: u64 = MATCH | CHAIN | M | S | U | EXECUTE | STORE | LOAD;
+
+// mcontrol fields; maskmax reports NAPOT ranges of any size
+const MCONTROL_MASKMAX: u64 = 0x3F << 53;
+const MCONTROL_HIT: u64 = 1 << 20;
+const MCONTROL_TIMING: u64 = 1 << 18;
+
+// mcontrol6 fields
+const MCONTROL6_VS: u64 = 1 << 24;
+const MCONTROL6_VU: u64 = 1 << 23;
+const MCONTROL6_HIT0: u64 = 1 << 22;
+
+// icount fields
+const ICOUNT_VS: u64 = 1 << 26;
+const ICOUNT_VU: u64 = 1 << 25;
+const ICOUNT_HIT: u64 = 1 << 24;
+const ICOUNT_COUNT_SHIFT: u32 = 10;
+const ICOUNT_COUNT: u64 = 0x3FFF << ICOUNT_COUNT_SHIFT;
+const ICOUNT_M: u64 = 1 << 9;
+const ICOUNT_PENDING: u64 = 1 << 8;
+const ICOUNT_S: u64 = 1 << 7;
+const ICOUNT_U: u64 = 1 << 6;
+
+// Address comparisons selected by the match field
+const MATCH_EQUAL: u64 = 0;
+const MATCH_NAPOT: u64 = 1;
+const MATCH_GE: u64 = 2;
+const MATCH_LT: u64 = 3;
+
+/// tinfo: version 1 (Sdtrig 1.0) and the supported types.
+const TINFO: u64 =
+    1 << 24 | 1 << TYPE_DISABLED | 1 << TYPE_MCONTROL6 | 1 << TYPE_ICOUNT | 1 << TYPE_MCONTROL;
+
+fn trigger_type(tdata1: u64) -> u64 {
+    tdata1 >> TYPE_SHIFT
+}
+
+fn is_address(tdata1: u64) -> bool {
+    matches!(trigger_type(tdata1), TYPE_MCONTROL | TYPE_MCONTROL6)
+}
+
+/// Whether the trigger is enabled in the given mode.
+fn mode_matches(tdata1: u64, privilege: u8, virt: bool) -> bool {
+    let (m, s, u, vs, vu) = match trigger_type(tdata1) {
+        // mcontrol has no VS/VU bits, so it never matches in guests
+        TYPE_MCONTROL => (M, S, U, 0, 0),
+        TYPE_MCONTROL6 => (M, S, U, MCONTROL6_VS, MCONTROL6_VU),
+        TYPE_ICOUNT => (ICOUNT_M, ICOUNT_S, ICOUNT_U, ICOUNT_VS, ICOUNT_VU),
+        _ => return false,
+    };
+    let bit = match (privilege, virt) {
+        (3, _) => m,
+        (1, false) => s,
+        (0, false) => u,
+        (1, true) => vs,
+        _ => vu,
+    };
+    tdata1 & bit != 0
+}
+
+fn address_matches(tdata1: u64, tdata2: u64, addr: u64) -> bool {
+    match (tdata1 & MATCH) >> MATCH_SHIFT {
+        MATCH_EQUAL => addr == tdata2,
+        // The trailing ones of tdata2 and the zero above them are ignored
+        MATCH_NAPOT => {
+            let mask = tdata2 ^ tdata2.wrapping_add(1);
+            addr | mask == tdata2 | mask
+        }
+        MATCH_GE => addr >= tdata2,
+        MATCH_LT => addr < tdata2,
+        _ => false,
+    }
+}
+
+/// Clears unsupported fields; unsupported types leave the trigger disabled.
+fn legalize(val: u64) -> u64 {
+    let ty = trigger_type(val);
+    let fields = match ty {
+        TYPE_MCONTROL => {
+            let mut fields = val & (MCONTROL_HIT | MCONTROL_TIMING | ADDRESS_FIELDS);
+            // Instruction triggers always fire before the instruction executes
+            if fields & EXECUTE != 0 {
+                fields &= !MCONTROL_TIMING;
+            }
+            fields | MCONTROL_MASKMAX
+        }
+        TYPE_MCONTROL6 => val & (MCONTROL6_VS | MCONTROL6_VU | MCONTROL6_HIT0 | ADDRESS_FIELDS),
+        TYPE_ICOUNT => {
+            val & (ICOUNT_VS
+                | ICOUNT_VU
+                | ICOUNT_HIT
+                | ICOUNT_COUNT
+                | ICOUNT_M
+                | ICOUNT_PENDING
+                | ICOUNT_S
+                | ICOUNT_U)
+        }
+        _ => return TYPE_DISABLED << TYPE_SHIFT,
+    };
+    let fields = if is_address(val) && (fields & MATCH) >> MATCH_SHIFT > MATCH_LT {
+        fields & !MATCH
+    } else {
+        fields
+    };
+    ty << TYPE_SHIFT | fields
+}
+
+fn set_hit(tdata1: &mut u64) {
+    *tdata1 |= match trigger_type(*tdata1) {
+        TYPE_MCONTROL => MCONTROL_HIT,
+        TYPE_MCONTROL6 => MCONTROL6_HIT0,
+        TYPE_ICOUNT => ICOUNT_HIT,
+        _ => 0,
+    };
+}
+
+#[derive(Clone, Copy)]
+struct Trigger {
+    tdata1: u64,
+    tdata2: u64,
+}
+
+/// Sdtrig trigger module: address-match triggers (mcontrol/mcontrol6) that
+/// raise breakpoints on fetches, loads and stores, and icount triggers for
+/// single-stepping. Triggers only take the breakpoint-exception action.
+#[derive(Default)]
+pub struct Triggers {
+    triggers: Vec<Trigger>,
+    select: usize,
+}
+
+impl Triggers {
+    pub fn new(count: usize) -> Self {
+        Self {
+            triggers: vec![
+                Trigger {
+                    tdata1: TYPE_DISABLED << TYPE_SHIFT,
+                    tdata2: 0,
+                };
+                count
+            ],
+            select: 0,
+        }
+    }
+
+    pub fn read_select(&self) -> u64 {
+        self.select as u64
+    }
+
+    /// Out-of-range selections are ignored, so reading back tselect tells
+    /// software how many triggers exist.
+    pub fn write_select(&mut self, val: u64) {
+        if (val as usize) < self.triggers.len() {
+            self.select = val as usize;
+        }
+    }
+
+    pub fn read_tdata1(&self) -> u64 {
+        self.triggers
+            .get(self.select)
+            .map_or(TYPE_NONE, |t| t.tdata1)
+    }
+
+    pub fn write_tdata1(&mut self, val: u64) {
+        let last = self.triggers.len().saturating_sub(1);
+        if let Some(t) = self.triggers.get_mut(self.select) {
+            t.tdata1 = legalize(val);
+            // The last trigger has nothing to chain to
+            if self.select == last && is_address(t.tdata1) {
+                t.tdata1 &= !CHAIN;
+            }
+        }
+    }
+
+    pub fn read_tdata2(&self) -> u64 {
+        self.triggers.get(self.select).map_or(0, |t| t.tdata2)
+    }
+
+    pub fn write_tdata2(&mut self, val: u64) {
+        if let Some(t) = self.triggers.get_mut(self.select) {
+            t.tdata2 = val;
+        }
+    }
+
+    pub fn read_tinfo(&self) -> u64 {
+        if self.select < self.triggers.len() {
+            TINFO
+        } else {
+            1 << TYPE_NONE
+        }
+    }
+
+    /// Finds the first chain of address triggers that all match, marking them hit.
+    /// A chain runs from a trigger with `chain` clear back over the ones before
+    /// it that have it set. Returns the last trigger's tdata1.
+    fn fire(&mut self, matches: impl Fn(&Trigger) -> bool) -> Option<u64> {
+        let mut start = 0;
+        while start < self.triggers.len() {
+            let mut end = start;
+            while end + 1 < self.triggers.len()
+                && is_address(self.triggers[end].tdata1)
+                && self.triggers[end].tdata1 & CHAIN != 0
+            {
+                end += 1;
+            }
+
+            if self.triggers[start..=end].iter().all(&matches) {
+                for t in &mut self.triggers[start..=end] {
+                    set_hit(&mut t.tdata1);
+                }
+                return Some(self.triggers[end].tdata1);
+            }
+            start = end + 1;
+        }
+        None
+    }
+
+    /// Checks execute triggers against an instruction address.
+    pub fn match_execute(&mut self, pc: u64, privilege: u8, virt: bool) -> bool {
+        self.fire(|t| {
+            is_address(t.tdata1)
+                && t.tdata1 & EXECUTE != 0
+                && mode_matches(t.tdata1, privilege, virt)
+                && address_matches(t.tdata1, t.tdata2, pc)
+        })
+        .is_some()
+    }
+
+    /// Checks load and store triggers against a data address. Returns
+    /// `Some(true)` for a trigger that fires after the access completes.
+    pub fn match_data(
+        &mut self,
+        addr: u64,
+        load: bool,
+        store: bool,
+        privilege: u8,
+        virt: bool,
+    ) -> Option<bool> {
+        self.fire(|t| {
+            is_address(t.tdata1)
+                && ((load && t.tdata1 & LOAD != 0) || (store && t.tdata1 & STORE != 0))
+                && mode_matches(t.tdata1, privilege, virt)
+                && address_matches(t.tdata1, t.tdata2, addr)
+        })
+        .map(|tdata1| trigger_type(tdata1) == TYPE_MCONTROL && tdata1 & MCONTROL_TIMING != 0)
+    }
+
+    /// Counts down icount triggers for an instruction executed in the given mode.
+    /// A trigger reaching zero becomes pending and fires before the next instruction.
+    pub fn count_instruction(&mut self, privilege: u8, virt: bool) {
+        for t in &mut self.triggers {
+            if trigger_type(t.tdata1) != TYPE_ICOUNT || !mode_matches(t.tdata1, privilege, virt) {
+                continue;
+            }
+            let count = (t.tdata1 & ICOUNT_COUNT) >> ICOUNT_COUNT_SHIFT;
+            if count == 0 {
+                continue;
+            }
+            t.tdata1 = (t.tdata1 & !ICOUNT_COUNT) | (count - 1) << ICOUNT_COUNT_SHIFT;
+            if count == 1 {
+                t.tdata1 |= ICOUNT_PENDING;
+            }
+        }
+    }
+
+    /// Whether an icount trigger is still counting down or about to fire.
+    pub fn counting(&self) -> bool {
+        self.triggers.iter().any(|t| {
+            trigger_type(t.tdata1) == TYPE_ICOUNT && t.tdata1 & (ICOUNT_COUNT | ICOUNT_PENDING) != 0
+        })
+    }
+
+    /// Takes the pending icount triggers, returning whether any fired.
+    pub fn take_pending(&mut self) -> bool {
+        let mut fired = false;
+        for t in &mut self.triggers {
+            if trigger_type(t.tdata1) == TYPE_ICOUNT && t.tdata1 & ICOUNT_PENDING != 0 {
+                t.tdata1 = (t.tdata1 & !ICOUNT_PENDING) | ICOUNT_HIT;
+                fired = true;
+            }
+        }
+        fired
+    }
+}
@@ -15,18 +15,6 @@ impl VirtAddr {
         self.0
     }
 
-    pub fn vpn0(&self) -> u64 {
-        (self.0 >> 12) & 0x1FF
-    }
-
-    pub fn vpn1(&self) -> u64 {
-        (self.0 >> 21) & 0x1FF
-    }
-
-    pub fn vpn2(&self) -> u64 {
-        (self.0 >> 30) & 0x1FF
-    }
-
     pub fn page_offset(&self) -> u64 {
         self.0 & 0xFFF
     }
@@ -51,6 +39,7 @@ pub enum AccessType {
     Write,
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Clone, Debug, PartialEq)]
 pub enum Trap {
     InstructionAddressMisaligned(u64),
@@ -63,16 +52,26 @@ pub enum Trap {
     StoreAccessFault(u64),
     EnvironmentCallFromUMode,
     EnvironmentCallFromSMode,
+    EnvironmentCallFromVSMode,
     EnvironmentCallFromMMode,
     InstructionPageFault(u64),
     LoadPageFault(u64),
     StorePageFault(u64),
+    /// G-stage faults carry the guest virtual and the guest physical address
+    InstructionGuestPageFault(u64, u64),
+    LoadGuestPageFault(u64, u64),
+    VirtualInstruction(u32),
+    StoreGuestPageFault(u64, u64),
     MachineTimerInterrupt,
-    UserSoftwareInterrupt,
     SupervisorSoftwareInterrupt,
     MachineSoftwareInterrupt,
     SupervisorTimerInterrupt,
-    ExternalInterrupt,
+    SupervisorExternalInterrupt,
+    MachineExternalInterrupt,
+    VirtualSupervisorSoftwareInterrupt,
+    VirtualSupervisorTimerInterrupt,
+    VirtualSupervisorExternalInterrupt,
+    LocalCounterOverflowInterrupt,
     RequestedTrap(u64),
 }
 
@@ -80,6 +79,8 @@ pub struct TranslationResult {
     pub paddr: PhysAddr,
     pub cycles: u64,
     pub trap: Option<Trap>,
+    /// The translation missed in the TLB and walked the page tables
+    pub tlb_miss: bool,
 }
 
 impl TranslationResult {
@@ -88,6 +89,7 @@ impl TranslationResult {
             paddr,
             cycles,
             trap: None,
+            tlb_miss: false,
         }
     }
 
@@ -96,6 +98,7 @@ impl TranslationResult {
             paddr: PhysAddr(0),
             cycles,
             trap: Some(trap),
+            tlb_miss: false,
         }
     }
 }
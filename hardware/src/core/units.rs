@@ -0,0 +1,307 @@
+use super::control::{AluOp, AtomicOp, CacheOp, ControlSignals};
+use super::vpu::VecOp;
+use crate::config::{LatencyConfig, PortConfig, UnitConfig};
+
+/// Functional-unit classes, each with its own latency and pool of units.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FuClass {
+    Alu,
+    Mul,
+    Div,
+    Crypto,
+    FpAdd,
+    FpMul,
+    FpFma,
+    FpDiv,
+    FpSqrt,
+    FpCvt,
+    FpMisc,
+}
+
+impl FuClass {
+    pub const COUNT: usize = 11;
+
+    pub const ALL: [Self; Self::COUNT] = [
+        Self::Alu,
+        Self::Mul,
+        Self::Div,
+        Self::Crypto,
+        Self::FpAdd,
+        Self::FpMul,
+        Self::FpFma,
+        Self::FpDiv,
+        Self::FpSqrt,
+        Self::FpCvt,
+        Self::FpMisc,
+    ];
+
+    /// The key naming the class in `[pipeline.latencies]` and the statistics.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Alu => "alu",
+            Self::Mul => "mul",
+            Self::Div => "div",
+            Self::Crypto => "crypto",
+            Self::FpAdd => "fp_add",
+            Self::FpMul => "fp_mul",
+            Self::FpFma => "fp_fma",
+            Self::FpDiv => "fp_div",
+            Self::FpSqrt => "fp_sqrt",
+            Self::FpCvt => "fp_cvt",
+            Self::FpMisc => "fp_misc",
+        }
+    }
+
+    /// The unit an instruction executes on, or `None` for memory, vector and
+    /// system instructions, which are timed elsewhere.
+    pub fn of(ctrl: &ControlSignals) -> Option<Self> {
+        if ctrl.mem_read
+            || ctrl.mem_write
+            || ctrl.atomic_op != AtomicOp::None
+            || ctrl.cache_op != CacheOp::None
+            || ctrl.vec_op != VecOp::None
+            || ctrl.is_system
+            || ctrl.is_fence
+            || ctrl.is_fence_i
+        {
+            return None;
+        }
+        let op = ctrl.alu;
+        Some(match op {
+            AluOp::Mul
+            | AluOp::Mulh
+            | AluOp::Mulhsu
+            | AluOp::Mulhu
+            | AluOp::Clmul
+            | AluOp::Clmulh
+            | AluOp::Clmulr => Self::Mul,
+            AluOp::Div | AluOp::Divu | AluOp::Rem | AluOp::Remu => Self::Div,
+            AluOp::FAdd | AluOp::FSub => Self::FpAdd,
+            AluOp::FMul => Self::FpMul,
+            AluOp::FMAdd | AluOp::FMSub | AluOp::FNMAdd | AluOp::FNMSub => Self::FpFma,
+            AluOp::FDiv => Self::FpDiv,
+            AluOp::FSqrt => Self::FpSqrt,
+            AluOp::FCvtWS
+            | AluOp::FCvtLS
+            | AluOp::FCvtSW
+            | AluOp::FCvtSL
+            | AluOp::FCvtWuS
+            | AluOp::FCvtLuS
+            | AluOp::FCvtSWu
+            | AluOp::FCvtSLu
+            | AluOp::FCvtSD
+            | AluOp::FCvtDS
+            | AluOp::FCvtSH
+            | AluOp::FCvtHS
+            | AluOp::FCvtDH
+            | AluOp::FCvtHD => Self::FpCvt,
+            AluOp::FMin
+            | AluOp::FMax
+            | AluOp::FSgnJ
+            | AluOp::FSgnJN
+            | AluOp::FSgnJX
+            | AluOp::FEq
+            | AluOp::FLt
+            | AluOp::FLe
+            | AluOp::FClass
+            | AluOp::FMvToX
+            | AluOp::FMvToF => Self::FpMisc,
+            _ if op.is_crypto() => Self::Crypto,
+            _ => Self::Alu,
+        })
+    }
+}
+
+struct Pool {
+    latency: u64,
+    pipelined: bool,
+    /// The cycle from which each unit accepts a new operation
+    free_at: Vec<u64>,
+}
+
+/// Busy tracking for every functional unit. A pipelined unit accepts a new
+/// operation each cycle; an iterative one only once its last has finished.
+/// The default has no units, and then nothing is timed.
+#[derive(Default)]
+pub struct FunctionalUnits {
+    pools: Vec<Pool>,
+}
+
+impl FunctionalUnits {
+    pub fn new(config: &LatencyConfig) -> Self {
+        let pool = |unit: &UnitConfig| Pool {
+            latency: unit.latency.max(1),
+            pipelined: unit.pipelined,
+            free_at: vec![0; unit.units.max(1)],
+        };
+        Self {
+            pools: FuClass::ALL
+                .iter()
+                .map(|&class| {
+                    pool(match class {
+                        FuClass::Alu => &config.alu,
+                        FuClass::Mul => &config.mul,
+                        FuClass::Div => &config.div,
+                        FuClass::Crypto => &config.crypto,
+                        FuClass::FpAdd => &config.fp_add,
+                        FuClass::FpMul => &config.fp_mul,
+                        FuClass::FpFma => &config.fp_fma,
+                        FuClass::FpDiv => &config.fp_div,
+                        FuClass::FpSqrt => &config.fp_sqrt,
+                        FuClass::FpCvt => &config.fp_cvt,
+                        FuClass::FpMisc => &config.fp_misc,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn latency(&self, class: FuClass) -> u64 {
+        self.pools.get(class as usize).map_or(1, |p| p.latency)
+    }
+
+    /// Whether a unit of `class` can accept an operation at `now`.
+    pub fn available(&self, class: FuClass, now: u64) -> bool {
+        self.pools
+            .get(class as usize)
+            .is_none_or(|p| p.free_at.iter().any(|&t| t <= now))
+    }
+
+    /// Starts an operation on the first unit of `class` free at or after
+    /// `now`. Returns the cycle it starts and the cycle its result is ready.
+    pub fn issue(&mut self, class: FuClass, now: u64) -> Option<(u64, u64)> {
+        let pool = self.pools.get_mut(class as usize)?;
+        let unit = pool.free_at.iter_mut().min()?;
+        let start = (*unit).max(now);
+        *unit = start + if pool.pipelined { 1 } else { pool.latency };
+        Some((start, start + pool.latency))
+    }
+}
+
+/// Issue ports, the slots a cycle offers to each kind of instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Port {
+    Alu,
+    Branch,
+    Load,
+    Store,
+    Fp,
+    MulDiv,
+}
+
+impl Port {
+    pub const COUNT: usize = 6;
+
+    pub const ALL: [Self; Self::COUNT] = [
+        Self::Alu,
+        Self::Branch,
+        Self::Load,
+        Self::Store,
+        Self::Fp,
+        Self::MulDiv,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Alu => "alu",
+            Self::Branch => "branch",
+            Self::Load => "load",
+            Self::Store => "store",
+            Self::Fp => "fp",
+            Self::MulDiv => "muldiv",
+        }
+    }
+
+    /// The ports an instruction occupies as it issues. Atomics take both a
+    /// load and a store port; vector instructions issue alone and take none.
+    pub fn needed(ctrl: &ControlSignals) -> Vec<Self> {
+        if ctrl.vec_op != VecOp::None {
+            return Vec::new();
+        }
+        if ctrl.mem_read || ctrl.mem_write || ctrl.cache_op != CacheOp::None {
+            let mut ports = Vec::with_capacity(2);
+            if ctrl.mem_read || ctrl.cache_op.is_prefetch() {
+                ports.push(Self::Load);
+            }
+            if ctrl.mem_write || (ctrl.cache_op != CacheOp::None && !ctrl.cache_op.is_prefetch()) {
+                ports.push(Self::Store);
+            }
+            return ports;
+        }
+        let port = if ctrl.branch || ctrl.jump {
+            Self::Branch
+        } else {
+            match FuClass::of(ctrl) {
+                Some(FuClass::Mul | FuClass::Div) => Self::MulDiv,
+                Some(
+                    FuClass::FpAdd
+                    | FuClass::FpMul
+                    | FuClass::FpFma
+                    | FuClass::FpDiv
+                    | FuClass::FpSqrt
+                    | FuClass::FpCvt
+                    | FuClass::FpMisc,
+                ) => Self::Fp,
+                _ => Self::Alu,
+            }
+        };
+        vec![port]
+    }
+}
+
+/// The issue ports of one cycle. The default has no ports, and then every
+/// instruction issues unconstrained and nothing is counted.
+#[derive(Default)]
+pub struct IssuePorts {
+    slots: Vec<usize>,
+    used: [usize; Port::COUNT],
+}
+
+impl IssuePorts {
+    pub fn new(config: &PortConfig) -> Self {
+        let slots = [
+            config.alu,
+            config.branch,
+            config.load,
+            config.store,
+            config.fp,
+            config.muldiv,
+        ];
+        Self {
+            slots: slots.iter().map(|&n| n.max(1)).collect(),
+            used: [0; Port::COUNT],
+        }
+    }
+
+    pub fn is_modelled(&self) -> bool {
+        !self.slots.is_empty()
+    }
+
+    /// Frees every port for a new cycle.
+    pub fn reset(&mut self) {
+        self.used = [0; Port::COUNT];
+    }
+
+    /// Takes the `needed` ports, or returns false and takes none if any of
+    /// them is already fully used this cycle.
+    pub fn claim(&mut self, needed: &[Port]) -> bool {
+        if self.slots.is_empty() {
+            return true;
+        }
+        if needed
+            .iter()
+            .any(|&p| self.used[p as usize] >= self.slots[p as usize])
+        {
+            return false;
+        }
+        for &p in needed {
+            self.used[p as usize] += 1;
+        }
+        true
+    }
+
+    /// Slots taken this cycle on each port.
+    pub fn used(&self) -> [usize; Port::COUNT] {
+        self.used
+    }
+}
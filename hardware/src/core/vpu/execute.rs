@@ -0,0 +1,367 @@
+use super::{VTYPE_VILL, VType, VecOp};
+use crate::core::Cpu;
+use crate::core::fpu::{FpFormat, RoundingMode, SoftFloat};
+use crate::core::types::Trap;
+use crate::isa::funct3;
+
+/// Register and mask fields shared by all OP-V arithmetic encodings.
+struct Fields {
+    vd: usize,
+    vs1: usize,
+    vs2: usize,
+    masked: bool,
+    funct3: u32,
+}
+
+impl Fields {
+    fn new(inst: u32) -> Self {
+        Self {
+            vd: ((inst >> 7) & 0x1f) as usize,
+            vs1: ((inst >> 15) & 0x1f) as usize,
+            vs2: ((inst >> 20) & 0x1f) as usize,
+            masked: (inst >> 25) & 1 == 0,
+            funct3: (inst >> 12) & 0x7,
+        }
+    }
+}
+
+fn elem_mask(bits: u32) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+fn sext(val: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((val << shift) as i64) >> shift
+}
+
+fn int_op(op: VecOp, x: u64, y: u64, d: u64, bits: u32) -> u64 {
+    let sx = sext(x, bits);
+    let sy = sext(y, bits);
+    let shamt = (y & (bits as u64 - 1)) as u32;
+    let res = match op {
+        VecOp::Add | VecOp::RedSum => x.wrapping_add(y),
+        VecOp::Sub => x.wrapping_sub(y),
+        VecOp::Rsub => y.wrapping_sub(x),
+        VecOp::Minu | VecOp::RedMinu => x.min(y),
+        VecOp::Min | VecOp::RedMin => sx.min(sy) as u64,
+        VecOp::Maxu | VecOp::RedMaxu => x.max(y),
+        VecOp::Max | VecOp::RedMax => sx.max(sy) as u64,
+        VecOp::And => x & y,
+        VecOp::Or => x | y,
+        VecOp::Xor => x ^ y,
+        VecOp::Sll => x << shamt,
+        VecOp::Srl => x >> shamt,
+        VecOp::Sra => (sx >> shamt) as u64,
+        VecOp::Mv => y,
+        VecOp::Mul => x.wrapping_mul(y),
+        VecOp::Mulh => ((sx as i128 * sy as i128) >> bits) as u64,
+        VecOp::Mulhu => ((x as u128 * y as u128) >> bits) as u64,
+        VecOp::Div => {
+            if sy == 0 {
+                u64::MAX
+            } else {
+                sx.wrapping_div(sy) as u64
+            }
+        }
+        VecOp::Divu => x.checked_div(y).unwrap_or(u64::MAX),
+        VecOp::Rem => {
+            if sy == 0 {
+                x
+            } else {
+                sx.wrapping_rem(sy) as u64
+            }
+        }
+        VecOp::Remu => x.checked_rem(y).unwrap_or(x),
+        VecOp::Macc => x.wrapping_mul(y).wrapping_add(d),
+        VecOp::Nmsac => d.wrapping_sub(x.wrapping_mul(y)),
+        VecOp::Madd => y.wrapping_mul(d).wrapping_add(x),
+        _ => 0,
+    };
+    res & elem_mask(bits)
+}
+
+fn int_cmp(op: VecOp, x: u64, y: u64, bits: u32) -> bool {
+    let sx = sext(x, bits);
+    let sy = sext(y, bits);
+    match op {
+        VecOp::Mseq => x == y,
+        VecOp::Msne => x != y,
+        VecOp::Msltu => x < y,
+        VecOp::Mslt => sx < sy,
+        VecOp::Msleu => x <= y,
+        VecOp::Msle => sx <= sy,
+        VecOp::Msgtu => x > y,
+        VecOp::Msgt => sx > sy,
+        _ => false,
+    }
+}
+
+fn fp_op(op: VecOp, x: u64, y: u64, d: u64, fmt: FpFormat, sf: &mut SoftFloat) -> u64 {
+    let (x, y, d) = (fmt.box_bits(x), fmt.box_bits(y), fmt.box_bits(d));
+    let res = match op {
+        VecOp::FAdd | VecOp::FRedUSum | VecOp::FRedOSum => sf.add(fmt, x, y),
+        VecOp::FSub => sf.sub(fmt, x, y),
+        VecOp::FMul => sf.mul(fmt, x, y),
+        VecOp::FDiv => sf.div(fmt, x, y),
+        VecOp::FMin => sf.min_max(fmt, x, y, false),
+        VecOp::FMax => sf.min_max(fmt, x, y, true),
+        VecOp::FSqrt => sf.sqrt(fmt, x),
+        VecOp::FMacc => sf.fma(fmt, y, x, d, false, false),
+        VecOp::FNmacc => sf.fma(fmt, y, x, d, true, true),
+        VecOp::FMsac => sf.fma(fmt, y, x, d, false, true),
+        VecOp::FNmsac => sf.fma(fmt, y, x, d, true, false),
+        VecOp::FMv => y,
+        _ => 0,
+    };
+    match fmt {
+        FpFormat::Single => res & 0xFFFF_FFFF,
+        _ => res,
+    }
+}
+
+fn fp_cmp(op: VecOp, x: u64, y: u64, fmt: FpFormat, sf: &mut SoftFloat) -> bool {
+    let (x, y) = (fmt.box_bits(x), fmt.box_bits(y));
+    let res = match op {
+        VecOp::MFeq => sf.eq(fmt, x, y),
+        VecOp::MFle => sf.le(fmt, x, y),
+        VecOp::MFlt => sf.lt(fmt, x, y),
+        _ => 0,
+    };
+    res != 0
+}
+
+/// Handles `vsetvli`, `vsetivli` and `vsetvl`, returning the new `vl`.
+fn set_vl(cpu: &mut Cpu, op: VecOp, inst: u32, a: u64, b: u64) -> u64 {
+    let rd = (inst >> 7) & 0x1f;
+    let rs1 = (inst >> 15) & 0x1f;
+    let vtype_bits = match op {
+        VecOp::Vsetvli => ((inst >> 20) & 0x7ff) as u64,
+        VecOp::Vsetivli => ((inst >> 20) & 0x3ff) as u64,
+        _ => b,
+    };
+
+    cpu.csrs.vstart = 0;
+    cpu.mark_vs_dirty();
+
+    let Some(vtype) = VType::decode(vtype_bits) else {
+        cpu.csrs.vtype = VTYPE_VILL;
+        cpu.csrs.vl = 0;
+        return 0;
+    };
+
+    let vlmax = vtype.vlmax(cpu.vregs.vlenb()) as u64;
+    let avl = match op {
+        VecOp::Vsetivli => rs1 as u64,
+        _ if rs1 != 0 => a,
+        _ if rd != 0 => u64::MAX,
+        _ => cpu.csrs.vl,
+    };
+
+    cpu.csrs.vtype = vtype_bits;
+    cpu.csrs.vl = avl.min(vlmax);
+    cpu.csrs.vl
+}
+
+/// Executes a vector configuration or arithmetic instruction. `a` is the
+/// scalar `rs1` operand (integer or FP) and `b` the integer `rs2` operand.
+/// Returns the value written to a scalar destination, if the instruction has one.
+pub fn execute(cpu: &mut Cpu, op: VecOp, inst: u32, a: u64, b: u64) -> Result<u64, Trap> {
+    if op.is_config() {
+        return Ok(set_vl(cpu, op, inst, a, b));
+    }
+
+    let illegal = Trap::IllegalInstruction(inst);
+    let vtype = VType::decode(cpu.csrs.vtype).ok_or(illegal.clone())?;
+    let f = Fields::new(inst);
+    let sew = vtype.sew;
+    let bits = (sew * 8) as u32;
+    let group = vtype.group_regs();
+    let vl = cpu.csrs.vl as usize;
+    let start = cpu.csrs.vstart as usize;
+
+    let fmt = match (op.is_fp(), sew) {
+        (false, _) => FpFormat::Double,
+        (true, 4) => FpFormat::Single,
+        (true, 8) => FpFormat::Double,
+        _ => return Err(illegal),
+    };
+    let mut sf = SoftFloat::new(if op.is_fp() {
+        RoundingMode::from_bits(cpu.csrs.frm).ok_or(illegal.clone())?
+    } else {
+        RoundingMode::default()
+    });
+
+    let is_shift = matches!(op, VecOp::Sll | VecOp::Srl | VecOp::Sra);
+    let scalar = match f.funct3 {
+        funct3::OPIVX | funct3::OPMVX => a & elem_mask(bits),
+        funct3::OPFVF => fmt.unbox(a),
+        funct3::OPIVI if is_shift => f.vs1 as u64,
+        funct3::OPIVI => sext(f.vs1 as u64, 5) as u64 & elem_mask(bits),
+        _ => 0,
+    };
+    let vector_src = matches!(f.funct3, funct3::OPIVV | funct3::OPMVV | funct3::OPFVV);
+    let src1 = |cpu: &Cpu, i: usize| {
+        if vector_src {
+            cpu.vregs.read(f.vs1, i, sew)
+        } else {
+            scalar
+        }
+    };
+    let active = |cpu: &Cpu, i: usize| !f.masked || cpu.vregs.mask_bit(0, i);
+    let aligned = |reg: usize| reg.is_multiple_of(group);
+
+    let mut scalar_result = 0;
+    match op {
+        VecOp::MvXS => {
+            scalar_result = sext(cpu.vregs.read(f.vs2, 0, sew), bits) as u64;
+        }
+        VecOp::FMvFS => {
+            scalar_result = fmt.box_bits(cpu.vregs.read(f.vs2, 0, sew));
+        }
+        VecOp::MvSX | VecOp::FMvSF => {
+            if start < vl {
+                cpu.vregs.write(f.vd, 0, sew, scalar);
+            }
+        }
+        VecOp::Mseq
+        | VecOp::Msne
+        | VecOp::Msltu
+        | VecOp::Mslt
+        | VecOp::Msleu
+        | VecOp::Msle
+        | VecOp::Msgtu
+        | VecOp::Msgt
+        | VecOp::MFeq
+        | VecOp::MFle
+        | VecOp::MFlt => {
+            if !aligned(f.vs2) || (vector_src && !aligned(f.vs1)) {
+                return Err(illegal);
+            }
+            // Sources are read in full before the mask is written, as vd may overlap them
+            let mut results = Vec::with_capacity(vl.saturating_sub(start));
+            for i in start..vl {
+                if !active(cpu, i) {
+                    continue;
+                }
+                let x = cpu.vregs.read(f.vs2, i, sew);
+                let y = src1(cpu, i);
+                let res = if op.is_fp() {
+                    fp_cmp(op, x, y, fmt, &mut sf)
+                } else {
+                    int_cmp(op, x, y, bits)
+                };
+                results.push((i, res));
+            }
+            for (i, res) in results {
+                cpu.vregs.set_mask_bit(f.vd, i, res);
+            }
+        }
+        VecOp::RedSum
+        | VecOp::RedMinu
+        | VecOp::RedMin
+        | VecOp::RedMaxu
+        | VecOp::RedMax
+        | VecOp::FRedUSum
+        | VecOp::FRedOSum => {
+            if start != 0 || !aligned(f.vs2) {
+                return Err(illegal);
+            }
+            if vl > 0 {
+                // Both FP sums are evaluated in element order, which is a valid unordered sum too
+                let mut acc = cpu.vregs.read(f.vs1, 0, sew);
+                for i in 0..vl {
+                    if !active(cpu, i) {
+                        continue;
+                    }
+                    let x = cpu.vregs.read(f.vs2, i, sew);
+                    acc = if op.is_fp() {
+                        fp_op(op, acc, x, 0, fmt, &mut sf)
+                    } else {
+                        int_op(op, acc, x, 0, bits)
+                    };
+                }
+                cpu.vregs.write(f.vd, 0, sew, acc);
+            }
+        }
+        VecOp::ZextVf2
+        | VecOp::ZextVf4
+        | VecOp::ZextVf8
+        | VecOp::SextVf2
+        | VecOp::SextVf4
+        | VecOp::SextVf8 => {
+            let factor = match op {
+                VecOp::ZextVf2 | VecOp::SextVf2 => 2,
+                VecOp::ZextVf4 | VecOp::SextVf4 => 4,
+                _ => 8,
+            };
+            let signed = matches!(op, VecOp::SextVf2 | VecOp::SextVf4 | VecOp::SextVf8);
+            let src_eew = sew / factor;
+            let src_group = (group / factor).max(1);
+            if src_eew == 0
+                || !aligned(f.vd)
+                || !f.vs2.is_multiple_of(src_group)
+                || (f.masked && f.vd == 0)
+            {
+                return Err(illegal);
+            }
+            let mut results = Vec::with_capacity(vl.saturating_sub(start));
+            for i in start..vl {
+                if !active(cpu, i) {
+                    continue;
+                }
+                let x = cpu.vregs.read(f.vs2, i, src_eew);
+                let res = if signed {
+                    sext(x, (src_eew * 8) as u32) as u64 & elem_mask(bits)
+                } else {
+                    x
+                };
+                results.push((i, res));
+            }
+            for (i, res) in results {
+                cpu.vregs.write(f.vd, i, sew, res);
+            }
+        }
+        _ => {
+            if !aligned(f.vd)
+                || !aligned(f.vs2)
+                || (vector_src && !aligned(f.vs1))
+                || (f.masked && f.vd == 0)
+            {
+                return Err(illegal);
+            }
+            let is_merge = matches!(op, VecOp::Merge | VecOp::FMerge);
+            for i in start..vl {
+                if !is_merge && !active(cpu, i) {
+                    continue;
+                }
+                let x = cpu.vregs.read(f.vs2, i, sew);
+                let y = src1(cpu, i);
+                let res = if is_merge {
+                    if cpu.vregs.mask_bit(0, i) { y } else { x }
+                } else {
+                    let d = cpu.vregs.read(f.vd, i, sew);
+                    if op.is_fp() {
+                        fp_op(op, x, y, d, fmt, &mut sf)
+                    } else {
+                        int_op(op, x, y, d, bits)
+                    }
+                };
+                cpu.vregs.write(f.vd, i, sew, res);
+            }
+        }
+    }
+
+    if sf.flags != 0 {
+        cpu.csrs.fflags |= sf.flags;
+        cpu.mark_fs_dirty();
+    }
+    cpu.csrs.vstart = 0;
+    if !matches!(op, VecOp::MvXS | VecOp::FMvFS) {
+        cpu.mark_vs_dirty();
+    }
+    Ok(scalar_result)
+}
@@ -0,0 +1,110 @@
+use super::{VType, VecOp};
+use crate::core::Cpu;
+use crate::core::control::MemWidth;
+use crate::core::pipeline::ExMemEntry;
+use crate::core::types::{AccessType, TranslationResult, Trap, VirtAddr};
+
+/// Performs a unit-stride or strided vector load/store one element at a time.
+/// `ex.alu` holds the base address, `ex.store_data` the byte stride and `ex.rd`
+/// the data register group. On a fault `vstart` records the failing element.
+pub fn access(cpu: &mut Cpu, ex: &ExMemEntry) -> Option<Trap> {
+    let illegal = Trap::IllegalInstruction(ex.inst);
+    let Some(vtype) = VType::decode(cpu.csrs.vtype) else {
+        return Some(illegal);
+    };
+
+    let eew: usize = match ex.ctrl.width {
+        MemWidth::Byte => 1,
+        MemWidth::Half => 2,
+        MemWidth::Word => 4,
+        _ => 8,
+    };
+
+    // EMUL = (EEW / SEW) * LMUL
+    let emul_log2 =
+        vtype.lmul_log2 + eew.trailing_zeros() as i32 - vtype.sew.trailing_zeros() as i32;
+    let masked = (ex.inst >> 25) & 1 == 0;
+    if !(-3..=3).contains(&emul_log2)
+        || !ex.rd.is_multiple_of(1 << emul_log2.max(0))
+        || (masked && ex.ctrl.mem_read && ex.rd == 0)
+    {
+        return Some(illegal);
+    }
+
+    let stride = if ex.ctrl.vec_op == VecOp::Strided {
+        ex.store_data
+    } else {
+        eew as u64
+    };
+    let access_type = if ex.ctrl.mem_write {
+        AccessType::Write
+    } else {
+        AccessType::Read
+    };
+    let line_bytes = cpu.l1_d_cache.line_bytes() as u64;
+    let mut last_line = None;
+
+    for i in cpu.csrs.vstart as usize..cpu.csrs.vl as usize {
+        if masked && !cpu.vregs.mask_bit(0, i) {
+            continue;
+        }
+
+        let vaddr = ex.alu.wrapping_add(stride.wrapping_mul(i as u64));
+        if vaddr % eew as u64 != 0 {
+            cpu.csrs.vstart = i as u64;
+            return Some(if ex.ctrl.mem_read {
+                Trap::LoadAddressMisaligned(vaddr)
+            } else {
+                Trap::StoreAddressMisaligned(vaddr)
+            });
+        }
+
+        let TranslationResult {
+            paddr,
+            cycles,
+            trap: fault,
+            ..
+        } = cpu.translate(VirtAddr::new(vaddr), access_type, eew as u64);
+        cpu.stall_cycles += cycles;
+        if let Some(t) = fault {
+            cpu.csrs.vstart = i as u64;
+            return Some(t);
+        }
+
+        // Consecutive elements in the same line are served by a single cache access
+        let raw_paddr = paddr.val();
+        let line = raw_paddr / line_bytes;
+        if raw_paddr < cpu.mmio_base && last_line != Some(line) {
+            let lat = cpu.simulate_memory_access(paddr, access_type);
+            cpu.stall_cycles += lat;
+            last_line = Some(line);
+        }
+
+        if ex.ctrl.mem_read {
+            let val = match eew {
+                1 => cpu.bus.bus.read_u8(raw_paddr) as u64,
+                2 => cpu.bus.bus.read_u16(raw_paddr) as u64,
+                4 => cpu.bus.bus.read_u32(raw_paddr) as u64,
+                _ => cpu.bus.bus.read_u64(raw_paddr),
+            };
+            cpu.vregs.write(ex.rd, i, eew, val);
+        } else {
+            let val = cpu.vregs.read(ex.rd, i, eew);
+            match eew {
+                1 => cpu.bus.bus.write_u8(raw_paddr, val as u8),
+                2 => cpu.bus.bus.write_u16(raw_paddr, val as u16),
+                4 => cpu.bus.bus.write_u32(raw_paddr, val as u32),
+                _ => cpu.bus.bus.write_u64(raw_paddr, val),
+            }
+            if cpu.load_reservation.is_some_and(|r| r / 8 == raw_paddr / 8) {
+                cpu.load_reservation = None;
+            }
+        }
+    }
+
+    cpu.csrs.vstart = 0;
+    if ex.ctrl.mem_read {
+        cpu.mark_vs_dirty();
+    }
+    None
+}
@@ -0,0 +1,212 @@
+pub mod execute;
+pub mod memory;
+
+/// Widest supported element, in bits.
+pub const ELEN: usize = 64;
+
+/// Set in `vtype` when the last `vsetvl{i}` requested an unsupported configuration.
+pub const VTYPE_VILL: u64 = 1 << 63;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VecOp {
+    #[default]
+    None,
+    Vsetvli,
+    Vsetivli,
+    Vsetvl,
+    UnitStride,
+    Strided,
+    Add,
+    Sub,
+    Rsub,
+    Minu,
+    Min,
+    Maxu,
+    Max,
+    And,
+    Or,
+    Xor,
+    Sll,
+    Srl,
+    Sra,
+    Mseq,
+    Msne,
+    Msltu,
+    Mslt,
+    Msleu,
+    Msle,
+    Msgtu,
+    Msgt,
+    Merge,
+    Mv,
+    Mul,
+    Mulh,
+    Mulhu,
+    Div,
+    Divu,
+    Rem,
+    Remu,
+    Macc,
+    Nmsac,
+    Madd,
+    ZextVf2,
+    ZextVf4,
+    ZextVf8,
+    SextVf2,
+    SextVf4,
+    SextVf8,
+    RedSum,
+    RedMinu,
+    RedMin,
+    RedMaxu,
+    RedMax,
+    MvXS,
+    MvSX,
+    FAdd,
+    FSub,
+    FMul,
+    FDiv,
+    FMin,
+    FMax,
+    FSqrt,
+    FMacc,
+    FNmacc,
+    FMsac,
+    FNmsac,
+    FRedUSum,
+    FRedOSum,
+    FMerge,
+    FMv,
+    FMvFS,
+    FMvSF,
+    MFeq,
+    MFle,
+    MFlt,
+}
+
+impl VecOp {
+    pub fn is_config(self) -> bool {
+        matches!(self, Self::Vsetvli | Self::Vsetivli | Self::Vsetvl)
+    }
+
+    pub fn is_memory(self) -> bool {
+        matches!(self, Self::UnitStride | Self::Strided)
+    }
+
+    pub fn is_fp(self) -> bool {
+        matches!(
+            self,
+            Self::FAdd
+                | Self::FSub
+                | Self::FMul
+                | Self::FDiv
+                | Self::FMin
+                | Self::FMax
+                | Self::FSqrt
+                | Self::FMacc
+                | Self::FNmacc
+                | Self::FMsac
+                | Self::FNmsac
+                | Self::FRedUSum
+                | Self::FRedOSum
+                | Self::FMerge
+                | Self::FMv
+                | Self::FMvFS
+                | Self::FMvSF
+                | Self::MFeq
+                | Self::MFle
+                | Self::MFlt
+        )
+    }
+}
+
+/// Decoded form of a legal `vtype` value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VType {
+    /// Selected element width in bytes.
+    pub sew: usize,
+    /// log2 of the register group multiplier, -3 (LMUL=1/8) to 3 (LMUL=8).
+    pub lmul_log2: i32,
+}
+
+impl VType {
+    /// Returns `None` for `vill`, reserved bits, or a SEW/LMUL pair this implementation cannot hold.
+    pub fn decode(bits: u64) -> Option<Self> {
+        // vta (bit 6) and vma (bit 7) are accepted; tails and masked-off elements are left undisturbed.
+        if bits >> 8 != 0 {
+            return None;
+        }
+        let vlmul = (bits & 0x7) as i32;
+        let vsew = (bits >> 3) & 0x7;
+        if vlmul == 4 || vsew > 3 {
+            return None;
+        }
+
+        let lmul_log2 = if vlmul > 4 { vlmul - 8 } else { vlmul };
+        let sew = 1usize << vsew;
+        if lmul_log2 < 0 && sew * 8 > ELEN >> -lmul_log2 {
+            return None;
+        }
+        Some(Self { sew, lmul_log2 })
+    }
+
+    pub fn vlmax(&self, vlenb: usize) -> usize {
+        if self.lmul_log2 >= 0 {
+            (vlenb << self.lmul_log2) / self.sew
+        } else {
+            (vlenb >> -self.lmul_log2) / self.sew
+        }
+    }
+
+    /// Number of registers in a group; fractional LMUL still occupies one.
+    pub fn group_regs(&self) -> usize {
+        1 << self.lmul_log2.max(0)
+    }
+}
+
+/// The 32 vector registers, stored back to back so a register group is a
+/// contiguous run of bytes.
+pub struct VectorRegisterFile {
+    vlenb: usize,
+    data: Vec<u8>,
+}
+
+impl VectorRegisterFile {
+    pub fn new(vlen: usize) -> Self {
+        let vlenb = vlen / 8;
+        Self {
+            vlenb,
+            data: vec![0; vlenb * 32],
+        }
+    }
+
+    pub fn vlenb(&self) -> usize {
+        self.vlenb
+    }
+
+    /// Reads element `idx` of `eew` bytes from the group starting at `base`.
+    pub fn read(&self, base: usize, idx: usize, eew: usize) -> u64 {
+        let off = base * self.vlenb + idx * eew;
+        let mut bytes = [0u8; 8];
+        bytes[..eew].copy_from_slice(&self.data[off..off + eew]);
+        u64::from_le_bytes(bytes)
+    }
+
+    pub fn write(&mut self, base: usize, idx: usize, eew: usize, val: u64) {
+        let off = base * self.vlenb + idx * eew;
+        self.data[off..off + eew].copy_from_slice(&val.to_le_bytes()[..eew]);
+    }
+
+    pub fn mask_bit(&self, reg: usize, idx: usize) -> bool {
+        (self.data[reg * self.vlenb + idx / 8] >> (idx % 8)) & 1 != 0
+    }
+
+    pub fn set_mask_bit(&mut self, reg: usize, idx: usize, val: bool) {
+        let byte = &mut self.data[reg * self.vlenb + idx / 8];
+        if val {
+            *byte |= 1 << (idx % 8);
+        } else {
+            *byte &= !(1 << (idx % 8));
+        }
+    }
+}
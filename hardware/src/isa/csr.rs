@@ -6,6 +6,20 @@ pub const MARCHID: u32 = 0xF12;
 pub const MIMPID: u32 = 0xF13;
 pub const MHARTID: u32 = 0xF14;
 
+// Floating-Point Control and Status
+pub const FFLAGS: u32 = 0x001;
+pub const FRM: u32 = 0x002;
+pub const FCSR: u32 = 0x003;
+
+// Vector Control and Status
+pub const VSTART: u32 = 0x008;
+pub const VXSAT: u32 = 0x009;
+pub const VXRM: u32 = 0x00A;
+pub const VCSR: u32 = 0x00F;
+pub const VL: u32 = 0xC20;
+pub const VTYPE: u32 = 0xC21;
+pub const VLENB: u32 = 0xC22;
+
 // Machine Trap Setup
 pub const MSTATUS: u32 = 0x300;
 pub const MISA: u32 = 0x301;
@@ -15,12 +29,54 @@ pub const MIE: u32 = 0x304;
 pub const MTVEC: u32 = 0x305;
 pub const MCOUNTEREN: u32 = 0x306;
 
+// Machine Configuration
+pub const MENVCFG: u32 = 0x30A;
+
 // Machine Trap Handling
 pub const MSCRATCH: u32 = 0x340;
 pub const MEPC: u32 = 0x341;
 pub const MCAUSE: u32 = 0x342;
 pub const MTVAL: u32 = 0x343;
 pub const MIP: u32 = 0x344;
+pub const MTINST: u32 = 0x34A;
+pub const MTVAL2: u32 = 0x34B;
+
+// Machine Memory Protection
+pub const PMPCFG0: u32 = 0x3A0;
+pub const PMPCFG15: u32 = 0x3AF;
+pub const PMPADDR0: u32 = 0x3B0;
+pub const PMPADDR63: u32 = 0x3EF;
+pub const PMPCFG_NAPOT_RWX: u64 = 0x1F; // A=NAPOT, X, W, R
+
+// Hypervisor Trap Setup and Handling
+pub const HSTATUS: u32 = 0x600;
+pub const HEDELEG: u32 = 0x602;
+pub const HIDELEG: u32 = 0x603;
+pub const HIE: u32 = 0x604;
+pub const HTIMEDELTA: u32 = 0x605;
+pub const HCOUNTEREN: u32 = 0x606;
+pub const HGEIE: u32 = 0x607;
+pub const HENVCFG: u32 = 0x60A;
+pub const HTVAL: u32 = 0x643;
+pub const HIP: u32 = 0x644;
+pub const HVIP: u32 = 0x645;
+pub const HTINST: u32 = 0x64A;
+pub const HGEIP: u32 = 0xE12;
+
+// Hypervisor Protection and Translation
+pub const HGATP: u32 = 0x680;
+
+// Virtual Supervisor Registers, which replace their S-mode counterparts while V=1
+pub const VSSTATUS: u32 = 0x200;
+pub const VSIE: u32 = 0x204;
+pub const VSTVEC: u32 = 0x205;
+pub const VSSCRATCH: u32 = 0x240;
+pub const VSEPC: u32 = 0x241;
+pub const VSCAUSE: u32 = 0x242;
+pub const VSTVAL: u32 = 0x243;
+pub const VSIP: u32 = 0x244;
+pub const VSATP: u32 = 0x280;
+pub const VSTIMECMP: u32 = 0x24D;
 
 // Supervisor Trap Setup
 pub const SSTATUS: u32 = 0x100;
@@ -28,12 +84,16 @@ pub const SIE: u32 = 0x104;
 pub const STVEC: u32 = 0x105;
 pub const SCOUNTEREN: u32 = 0x106;
 
+// Supervisor Configuration
+pub const SENVCFG: u32 = 0x10A;
+
 // Supervisor Trap Handling
 pub const SSCRATCH: u32 = 0x140;
 pub const SEPC: u32 = 0x141;
 pub const SCAUSE: u32 = 0x142;
 pub const STVAL: u32 = 0x143;
 pub const SIP: u32 = 0x144;
+pub const STIMECMP: u32 = 0x14D;
 
 // Supervisor Protection and Translation
 pub const SATP: u32 = 0x180;
@@ -44,8 +104,22 @@ pub const TIME: u32 = 0xC01;
 pub const INSTRET: u32 = 0xC02;
 pub const MCYCLE: u32 = 0xB00;
 pub const MINSTRET: u32 = 0xB02;
+pub const HPMCOUNTER3: u32 = 0xC03;
+pub const HPMCOUNTER31: u32 = 0xC1F;
+pub const MHPMCOUNTER3: u32 = 0xB03;
+pub const MHPMCOUNTER31: u32 = 0xB1F;
+pub const MCOUNTINHIBIT: u32 = 0x320;
+pub const MHPMEVENT3: u32 = 0x323;
+pub const MHPMEVENT31: u32 = 0x33F;
+pub const SCOUNTOVF: u32 = 0xDA0;
+
+// Debug triggers (Sdtrig)
+pub const TSELECT: u32 = 0x7A0;
+pub const TDATA1: u32 = 0x7A1;
+pub const TDATA2: u32 = 0x7A2;
+pub const TDATA3: u32 = 0x7A3;
+pub const TINFO: u32 = 0x7A4;
 
-pub const MSTATUS_UIE: u64 = 1 << 0;
 pub const MSTATUS_SIE: u64 = 1 << 1;
 pub const MSTATUS_MIE: u64 = 1 << 3;
 
@@ -69,16 +143,86 @@ pub const MIP_UEIP: u64 = 1 << 8;
 pub const MIP_SEIP: u64 = 1 << 9;
 pub const MIP_MEIP: u64 = 1 << 11;
 
+// Local counter-overflow interrupt (Sscofpmf)
+pub const MIP_LCOFIP: u64 = 1 << 13;
+
+// Virtual supervisor interrupts, injected by the hypervisor through hvip
+pub const MIP_VSSIP: u64 = 1 << 2;
+pub const MIP_VSTIP: u64 = 1 << 6;
+pub const MIP_VSEIP: u64 = 1 << 10;
+pub const MIP_VS_MASK: u64 = MIP_VSSIP | MIP_VSTIP | MIP_VSEIP;
+
+// Pending bits driven by devices (read-only in mip) and bits M-mode software may set
+pub const MIP_HW_MASK: u64 = MIP_MSIP | MIP_MTIP | MIP_MEIP;
+pub const MIP_WRITABLE: u64 = MIP_SSIP | MIP_STIP | MIP_SEIP | MIP_VSSIP | MIP_LCOFIP;
+pub const MIE_WRITABLE: u64 =
+    MIE_SSIP | MIE_MSIP | MIE_STIE | MIE_MTIE | MIE_SEIP | MIE_MEIP | MIP_VS_MASK | MIP_LCOFIP;
+
+// Counters exposed by mcounteren/scounteren/hcounteren: CY, TM, IR and HPM3-31
+pub const COUNTEREN_MASK: u64 = 0xFFFF_FFFF;
+pub const COUNTEREN_TM: u64 = 1 << 1;
+
+// menvcfg/henvcfg: STCE enables the Sstc timer compare CSRs
+pub const ENVCFG_STCE: u64 = 1 << 63;
+// menvcfg/henvcfg/senvcfg: CBIE, CBCFE and CBZE enable cbo.inval, cbo.clean/flush
+// and cbo.zero in less privileged modes; CBIE=01 makes cbo.inval a flush
+pub const ENVCFG_CBIE: u64 = 3 << 4;
+pub const ENVCFG_CBIE_FLUSH: u64 = 1 << 4;
+pub const ENVCFG_CBCFE: u64 = 1 << 6;
+pub const ENVCFG_CBZE: u64 = 1 << 7;
+pub const ENVCFG_CBO: u64 = ENVCFG_CBIE | ENVCFG_CBCFE | ENVCFG_CBZE;
+
+// Floating-Point Accrued Exceptions (fflags)
+pub const FFLAGS_NX: u64 = 1 << 0; // Inexact
+pub const FFLAGS_UF: u64 = 1 << 1; // Underflow
+pub const FFLAGS_OF: u64 = 1 << 2; // Overflow
+pub const FFLAGS_DZ: u64 = 1 << 3; // Divide by Zero
+pub const FFLAGS_NV: u64 = 1 << 4; // Invalid Operation
+pub const FFLAGS_MASK: u64 = 0x1F;
+pub const FRM_SHIFT: u64 = 5;
+pub const FRM_MASK: u64 = 0x7;
+
+// Vector fixed-point state (vcsr = vxrm:vxsat)
+pub const VXSAT_MASK: u64 = 0x1;
+pub const VXRM_MASK: u64 = 0x3;
+pub const VXRM_SHIFT: u64 = 1;
+
+// MISA Extension Bits
+pub const MISA_B: u64 = 1 << 1;
+pub const MISA_C: u64 = 1 << 2;
+pub const MISA_F: u64 = 1 << 5;
+pub const MISA_H: u64 = 1 << 7;
+pub const MISA_V: u64 = 1 << 21;
+
 // Custom debug CSR to trigger RequestedTrap
 pub const CSR_SIM_PANIC: u32 = 0x8FF;
 
+// Delegatable exceptions (ecall from M-mode cannot be delegated) and
+// supervisor-level interrupts; VS-level interrupts are always delegated
+pub const MEDELEG_MASK: u64 = 0xF0_B7FF;
+pub const MIDELEG_MASK: u64 = MIP_SSIP | MIP_STIP | MIP_SEIP | MIP_LCOFIP;
+
+// Exceptions HS-mode may delegate further to VS-mode; ecalls from VS/HS/M,
+// guest-page faults and virtual-instruction exceptions always stay in HS
+pub const HEDELEG_MASK: u64 = 0xB1FF;
+pub const HIDELEG_MASK: u64 = MIP_VS_MASK;
+
+// Trap vector modes (xtvec[1:0]); values 2 and 3 are reserved
+pub const TVEC_MODE_VECTORED: u64 = 1;
+pub const TVEC_MODE_RESERVED: u64 = 2;
+
+// Interrupt bit of mcause/scause
+pub const CAUSE_INTERRUPT: u64 = 1 << 63;
+
 // Previous Interrupt Enables
 pub const MSTATUS_SPIE: u64 = 1 << 5;
 pub const MSTATUS_MPIE: u64 = 1 << 7;
 
 // Previous Privilege Modes
 pub const MSTATUS_SPP: u64 = 1 << 8;
+pub const MSTATUS_SPP_SHIFT: u64 = 8;
 pub const MSTATUS_MPP: u64 = 3 << 11;
+pub const MSTATUS_MPP_SHIFT: u64 = 11;
 
 // Floating Point Status (FS)
 // 0 = Off, 1 = Initial, 2 = Clean, 3 = Dirty
@@ -88,12 +232,90 @@ pub const MSTATUS_FS_INIT: u64 = 1 << 13;
 pub const MSTATUS_FS_CLEAN: u64 = 2 << 13;
 pub const MSTATUS_FS_DIRTY: u64 = 3 << 13;
 
+// Vector Status (VS), same encoding as FS
+pub const MSTATUS_VS: u64 = 3 << 9;
+pub const MSTATUS_VS_INIT: u64 = 1 << 9;
+pub const MSTATUS_VS_DIRTY: u64 = 3 << 9;
+
+pub const MSTATUS_XS: u64 = 3 << 15;
+pub const MSTATUS_UXL: u64 = 3 << 32;
+// UXL and SXL are read-only: U- and S-mode are always 64-bit
+pub const MSTATUS_UXL_64: u64 = 2 << 32;
+pub const MSTATUS_SXL_64: u64 = 2 << 34;
+
+// Fields of mstatus visible through sstatus
+pub const SSTATUS_MASK: u64 = MSTATUS_SIE
+    | MSTATUS_SPIE
+    | MSTATUS_SPP
+    | MSTATUS_VS
+    | MSTATUS_FS
+    | MSTATUS_XS
+    | MSTATUS_SUM
+    | MSTATUS_MXR
+    | MSTATUS_UXL
+    | MSTATUS_SD;
+
+// State Dirty summary, read-only
+pub const MSTATUS_SD: u64 = 1 << 63;
+
 // Memory Privileges
+pub const MSTATUS_MPRV: u64 = 1 << 17; // Modify PRiVilege: M-mode loads and stores act as MPP
 pub const MSTATUS_SUM: u64 = 1 << 18; // Permit Supervisor User Memory access
 pub const MSTATUS_MXR: u64 = 1 << 19; // Make eXecutable Readable
+pub const MSTATUS_TVM: u64 = 1 << 20; // Trap Virtual Memory: satp and SFENCE.VMA are illegal in S
+pub const MSTATUS_TW: u64 = 1 << 21; // Timeout Wait: WFI is illegal below M-mode
+pub const MSTATUS_TSR: u64 = 1 << 22; // Trap SRET: SRET is illegal in S-mode
+
+// Hypervisor state in mstatus: previous virtualization mode, guest virtual address in mtval
+pub const MSTATUS_GVA: u64 = 1 << 38;
+pub const MSTATUS_MPV: u64 = 1 << 39;
+
+// Fields of mstatus and sstatus that software can write; the others are
+// read-only or not implemented
+pub const MSTATUS_WRITABLE: u64 = MSTATUS_SIE
+    | MSTATUS_MIE
+    | MSTATUS_SPIE
+    | MSTATUS_MPIE
+    | MSTATUS_SPP
+    | MSTATUS_VS
+    | MSTATUS_MPP
+    | MSTATUS_FS
+    | MSTATUS_MPRV
+    | MSTATUS_SUM
+    | MSTATUS_MXR
+    | MSTATUS_TVM
+    | MSTATUS_TW
+    | MSTATUS_TSR
+    | MSTATUS_GVA
+    | MSTATUS_MPV;
+pub const SSTATUS_WRITABLE: u64 =
+    MSTATUS_SIE | MSTATUS_SPIE | MSTATUS_SPP | MSTATUS_VS | MSTATUS_FS | MSTATUS_SUM | MSTATUS_MXR;
+
+// HSTATUS
+pub const HSTATUS_GVA: u64 = 1 << 6; // htval/stval hold a guest virtual address
+pub const HSTATUS_SPV: u64 = 1 << 7; // V before the last trap into HS-mode
+pub const HSTATUS_SPVP: u64 = 1 << 8; // Privilege for HLV/HSV, and before the last trap from V=1
+pub const HSTATUS_HU: u64 = 1 << 9; // Allow HLV/HSV in U-mode
+pub const HSTATUS_VTVM: u64 = 1 << 20;
+pub const HSTATUS_VTW: u64 = 1 << 21;
+pub const HSTATUS_VTSR: u64 = 1 << 22;
+pub const HSTATUS_VSXL_64: u64 = 2 << 32;
+pub const HSTATUS_WRITABLE: u64 = HSTATUS_GVA
+    | HSTATUS_SPV
+    | HSTATUS_SPVP
+    | HSTATUS_HU
+    | HSTATUS_VTVM
+    | HSTATUS_VTW
+    | HSTATUS_VTSR;
 
 // SATP (Supervisor Address Translation and Protection)
 pub const SATP_MODE_SHIFT: u64 = 60;
 pub const SATP_MODE_BARE: u64 = 0;
 pub const SATP_MODE_SV39: u64 = 8;
 pub const SATP_MODE_SV48: u64 = 9;
+pub const SATP_MODE_SV57: u64 = 10;
+pub const SATP_PPN_MASK: u64 = 0xFFF_FFFF_FFFF;
+
+// HGATP: G-stage translation; the x4 root table spans four pages, so PPN[1:0] is zero
+pub const HGATP_MODE_SV39X4: u64 = 8;
+pub const HGATP_PPN_MASK: u64 = 0xFFF_FFFF_FFFC;
@@ -11,6 +11,10 @@ pub const SH: u32 = 0b001;
 pub const SW: u32 = 0b010;
 pub const SD: u32 = 0b011;
 
+pub const FENCE: u32 = 0b000;
+pub const FENCE_I: u32 = 0b001;
+pub const CBO: u32 = 0b010;
+
 pub const BEQ: u32 = 0b000;
 pub const BNE: u32 = 0b001;
 pub const BLT: u32 = 0b100;
@@ -29,11 +33,27 @@ pub const AND: u32 = 0b111;
 
 pub const FMIN: u32 = 0b000;
 pub const FMAX: u32 = 0b001;
-pub const FEQ: u32 = 0b000;
+pub const FEQ: u32 = 0b010;
 pub const FLT: u32 = 0b001;
-pub const FLE: u32 = 0b010;
+pub const FLE: u32 = 0b000;
 pub const FCLASS: u32 = 0b001;
 pub const FMV_X_W: u32 = 0b000;
 pub const FSGNJ: u32 = 0b000;
 pub const FSGNJN: u32 = 0b001;
 pub const FSGNJX: u32 = 0b010;
+
+// Vector (OP-V) operand categories
+pub const OPIVV: u32 = 0b000;
+pub const OPFVV: u32 = 0b001;
+pub const OPMVV: u32 = 0b010;
+pub const OPIVI: u32 = 0b011;
+pub const OPIVX: u32 = 0b100;
+pub const OPFVF: u32 = 0b101;
+pub const OPMVX: u32 = 0b110;
+pub const OPCFG: u32 = 0b111;
+
+// Vector load/store element widths (LOAD-FP / STORE-FP)
+pub const VE8: u32 = 0b000;
+pub const VE16: u32 = 0b101;
+pub const VE32: u32 = 0b110;
+pub const VE64: u32 = 0b111;
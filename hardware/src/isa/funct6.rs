@@ -0,0 +1,72 @@
+// RV64 shift-immediate selectors (imm[11:6])
+pub const SLLI: u32 = 0b000000;
+pub const SRLI: u32 = 0b000000;
+pub const SRAI: u32 = 0b010000;
+pub const SLLI_UW: u32 = 0b000010;
+pub const RORI: u32 = 0b011000;
+pub const BCLRI_BEXTI: u32 = 0b010010;
+pub const BINVI: u32 = 0b011010;
+pub const BSETI: u32 = 0b001010;
+
+// Vector integer arithmetic (OPIVV / OPIVX / OPIVI)
+pub const VADD: u32 = 0b000000;
+pub const VSUB: u32 = 0b000010;
+pub const VRSUB: u32 = 0b000011;
+pub const VMINU: u32 = 0b000100;
+pub const VMIN: u32 = 0b000101;
+pub const VMAXU: u32 = 0b000110;
+pub const VMAX: u32 = 0b000111;
+pub const VAND: u32 = 0b001001;
+pub const VOR: u32 = 0b001010;
+pub const VXOR: u32 = 0b001011;
+pub const VMERGE_VMV: u32 = 0b010111;
+pub const VMSEQ: u32 = 0b011000;
+pub const VMSNE: u32 = 0b011001;
+pub const VMSLTU: u32 = 0b011010;
+pub const VMSLT: u32 = 0b011011;
+pub const VMSLEU: u32 = 0b011100;
+pub const VMSLE: u32 = 0b011101;
+pub const VMSGTU: u32 = 0b011110;
+pub const VMSGT: u32 = 0b011111;
+pub const VSLL: u32 = 0b100101;
+pub const VSRL: u32 = 0b101000;
+pub const VSRA: u32 = 0b101001;
+
+// Vector integer multiply/divide, reductions and moves (OPMVV / OPMVX)
+pub const VREDSUM: u32 = 0b000000;
+pub const VREDMINU: u32 = 0b000100;
+pub const VREDMIN: u32 = 0b000101;
+pub const VREDMAXU: u32 = 0b000110;
+pub const VREDMAX: u32 = 0b000111;
+pub const VWXUNARY0: u32 = 0b010000;
+pub const VXUNARY0: u32 = 0b010010;
+pub const VDIVU: u32 = 0b100000;
+pub const VDIV: u32 = 0b100001;
+pub const VREMU: u32 = 0b100010;
+pub const VREM: u32 = 0b100011;
+pub const VMULHU: u32 = 0b100100;
+pub const VMUL: u32 = 0b100101;
+pub const VMULH: u32 = 0b100111;
+pub const VMADD: u32 = 0b101001;
+pub const VMACC: u32 = 0b101101;
+pub const VNMSAC: u32 = 0b101111;
+
+// Vector floating point (OPFVV / OPFVF)
+pub const VFADD: u32 = 0b000000;
+pub const VFREDUSUM: u32 = 0b000001;
+pub const VFSUB: u32 = 0b000010;
+pub const VFREDOSUM: u32 = 0b000011;
+pub const VFMIN: u32 = 0b000100;
+pub const VFMAX: u32 = 0b000110;
+pub const VWFUNARY0: u32 = 0b010000;
+pub const VFUNARY1: u32 = 0b010011;
+pub const VFMERGE_VFMV: u32 = 0b010111;
+pub const VMFEQ: u32 = 0b011000;
+pub const VMFLE: u32 = 0b011001;
+pub const VMFLT: u32 = 0b011011;
+pub const VFDIV: u32 = 0b100000;
+pub const VFMUL: u32 = 0b100100;
+pub const VFMACC: u32 = 0b101100;
+pub const VFNMACC: u32 = 0b101101;
+pub const VFMSAC: u32 = 0b101110;
+pub const VFNMSAC: u32 = 0b101111;
@@ -16,3 +16,70 @@ pub const FCVT_W_F: u32 = 0b11000;
 pub const FCVT_F_W: u32 = 0b11010;
 pub const FMV_F_X: u32 = 0b11110;
 pub const FCVT_DS: u32 = 0b01000;
+
+// Zba/Zbb/Zbc/Zbs
+pub const SH_ADD: u32 = 0b0010000;
+pub const ADD_UW: u32 = 0b0000100;
+pub const ZEXT_H: u32 = 0b0000100;
+pub const ANDN_ORN_XNOR: u32 = 0b0100000;
+pub const MIN_MAX_CLMUL: u32 = 0b0000101;
+pub const ROTATE: u32 = 0b0110000;
+pub const BCLR_BEXT: u32 = 0b0100100;
+pub const BINV: u32 = 0b0110100;
+pub const BSET: u32 = 0b0010100;
+
+// Zbb unary ops share funct7 = ROTATE and are selected by the rs2 field
+pub const UNARY_CLZ: u32 = 0b00000;
+pub const UNARY_CTZ: u32 = 0b00001;
+pub const UNARY_CPOP: u32 = 0b00010;
+pub const UNARY_SEXT_B: u32 = 0b00100;
+pub const UNARY_SEXT_H: u32 = 0b00101;
+
+// Zbb full 12-bit immediates on funct3 = SRL_SRA
+pub const ORC_B_IMM: u32 = 0x287;
+pub const REV8_IMM: u32 = 0x6B8;
+
+// Zbkb / Zkne / Zknd on OP (pack and packw share funct7 with zext.h)
+pub const PACK: u32 = 0b0000100;
+pub const AES64ES: u32 = 0b0011001;
+pub const AES64ESM: u32 = 0b0011011;
+pub const AES64DS: u32 = 0b0011101;
+pub const AES64DSM: u32 = 0b0011111;
+pub const AES64KS2: u32 = 0b0111111;
+
+// Zkne/Zknd/Zknh full 12-bit immediates on OP-IMM funct3 = SLL
+pub const AES64IM_IMM: u32 = 0x300;
+pub const AES64KS1I_IMM: u32 = 0x310; // rnum in imm[3:0]
+pub const SHA256SUM0_IMM: u32 = 0x100;
+pub const SHA256SUM1_IMM: u32 = 0x101;
+pub const SHA256SIG0_IMM: u32 = 0x102;
+pub const SHA256SIG1_IMM: u32 = 0x103;
+pub const SHA512SUM0_IMM: u32 = 0x104;
+pub const SHA512SUM1_IMM: u32 = 0x105;
+pub const SHA512SIG0_IMM: u32 = 0x106;
+pub const SHA512SIG1_IMM: u32 = 0x107;
+
+// Zbkb full 12-bit immediate on OP-IMM funct3 = SRL_SRA
+pub const BREV8_IMM: u32 = 0x687;
+
+// Zicbom/Zicboz full 12-bit immediates on MISC-MEM funct3 = CBO
+pub const CBO_INVAL_IMM: u32 = 0x000;
+pub const CBO_CLEAN_IMM: u32 = 0x001;
+pub const CBO_FLUSH_IMM: u32 = 0x002;
+pub const CBO_ZERO_IMM: u32 = 0x004;
+
+// Zicbop hints are ORI with rd = x0, selected by the rs2 field (imm[4:0])
+pub const PREFETCH_I: u32 = 0b00000;
+pub const PREFETCH_R: u32 = 0b00001;
+pub const PREFETCH_W: u32 = 0b00011;
+
+// Hypervisor loads/stores on SYSTEM funct3 = HLSV; bit 0 selects HSV, and for
+// HLV the rs2 field picks signed, unsigned or execute-permission (HLVX) loads
+pub const HLV_B: u32 = 0b0110000;
+pub const HLV_H: u32 = 0b0110010;
+pub const HLV_W: u32 = 0b0110100;
+pub const HLV_D: u32 = 0b0110110;
+pub const HSV: u32 = 0b0000001;
+pub const HLV_SIGNED: usize = 0b00000;
+pub const HLV_UNSIGNED: usize = 0b00001;
+pub const HLVX: usize = 0b00011;
@@ -3,7 +3,9 @@ pub mod csr;
 pub mod decoder;
 pub mod funct3;
 pub mod funct5;
+pub mod funct6;
 pub mod funct7;
 pub mod instruction;
 pub mod opcodes;
+pub mod rvc;
 pub mod sys_ops;
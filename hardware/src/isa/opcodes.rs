@@ -1,5 +1,6 @@
 pub const OP_LOAD: u32 = 0b0000011;
 pub const OP_LOAD_FP: u32 = 0b0000111;
+pub const OP_MISC_MEM: u32 = 0b0001111;
 pub const OP_IMM: u32 = 0b0010011;
 pub const OP_AUIPC: u32 = 0b0010111;
 pub const OP_IMM_32: u32 = 0b0011011;
@@ -10,6 +11,7 @@ pub const OP_REG: u32 = 0b0110011;
 pub const OP_LUI: u32 = 0b0110111;
 pub const OP_REG_32: u32 = 0b0111011;
 pub const OP_FP: u32 = 0b1010011;
+pub const OP_V: u32 = 0b1010111;
 pub const OP_BRANCH: u32 = 0b1100011;
 pub const OP_JALR: u32 = 0b1100111;
 pub const OP_JAL: u32 = 0b1101111;
@@ -0,0 +1,320 @@
+use super::{funct3, opcodes};
+
+// Compressed Quadrants (inst[1:0])
+pub const QUADRANT_0: u16 = 0b00;
+pub const QUADRANT_1: u16 = 0b01;
+pub const QUADRANT_2: u16 = 0b10;
+
+/// Returns true if the low halfword starts a 16-bit (compressed) instruction.
+#[inline(always)]
+pub fn is_compressed(low_half: u16) -> bool {
+    (low_half & 0x3) != 0x3
+}
+
+fn r_type(funct7: u32, rs2: u32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn i_type(imm: i32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    (((imm as u32) & 0xFFF) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn s_type(imm: i32, rs2: u32, rs1: u32, funct3: u32, opcode: u32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 5) & 0x7F) << 25)
+        | (rs2 << 20)
+        | (rs1 << 15)
+        | (funct3 << 12)
+        | ((imm & 0x1F) << 7)
+        | opcode
+}
+
+fn b_type(imm: i32, rs2: u32, rs1: u32, funct3: u32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 12) & 1) << 31)
+        | (((imm >> 5) & 0x3F) << 25)
+        | (rs2 << 20)
+        | (rs1 << 15)
+        | (funct3 << 12)
+        | (((imm >> 1) & 0xF) << 8)
+        | (((imm >> 11) & 1) << 7)
+        | opcodes::OP_BRANCH
+}
+
+fn u_type(imm: i32, rd: u32, opcode: u32) -> u32 {
+    ((imm as u32) & 0xFFFF_F000) | (rd << 7) | opcode
+}
+
+fn j_type(imm: i32, rd: u32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 20) & 1) << 31)
+        | (((imm >> 1) & 0x3FF) << 21)
+        | (((imm >> 11) & 1) << 20)
+        | (((imm >> 12) & 0xFF) << 12)
+        | (rd << 7)
+        | opcodes::OP_JAL
+}
+
+/// Sign-extends the low `bits` bits of `val`.
+fn sext(val: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((val << shift) as i32) >> shift
+}
+
+/// Expands a 16-bit RV64C instruction into its 32-bit equivalent.
+///
+/// Returns `None` for reserved and illegal encodings (including the all-zero
+/// halfword), which the decoder reports as an illegal instruction.
+pub fn expand(inst: u16) -> Option<u32> {
+    let i = inst as u32;
+    let op = inst & 0x3;
+    let f3 = (i >> 13) & 0x7;
+
+    // Full-width register fields
+    let rd = (i >> 7) & 0x1F;
+    let rs2 = (i >> 2) & 0x1F;
+
+    // Compressed register fields (x8-x15 / f8-f15)
+    let rd_p = ((i >> 2) & 0x7) + 8;
+    let rs1_p = ((i >> 7) & 0x7) + 8;
+
+    // CI-format immediate: imm[5] = inst[12], imm[4:0] = inst[6:2]
+    let ci_imm = sext((((i >> 12) & 1) << 5) | ((i >> 2) & 0x1F), 6);
+
+    // CL/CS offsets for word and doubleword accesses
+    let lw_off = (((i >> 10) & 0x7) << 3) | (((i >> 6) & 1) << 2) | (((i >> 5) & 1) << 6);
+    let ld_off = (((i >> 10) & 0x7) << 3) | (((i >> 5) & 0x3) << 6);
+
+    match op {
+        QUADRANT_0 => match f3 {
+            // C.ADDI4SPN
+            0b000 => {
+                let imm = (((i >> 7) & 0xF) << 6)
+                    | (((i >> 11) & 0x3) << 4)
+                    | (((i >> 5) & 1) << 3)
+                    | (((i >> 6) & 1) << 2);
+                if imm == 0 {
+                    return None;
+                }
+                Some(i_type(imm as i32, 2, funct3::ADD_SUB, rd_p, opcodes::OP_IMM))
+            }
+            // C.FLD
+            0b001 => Some(i_type(
+                ld_off as i32,
+                rs1_p,
+                funct3::LD,
+                rd_p,
+                opcodes::OP_LOAD_FP,
+            )),
+            // C.LW
+            0b010 => Some(i_type(
+                lw_off as i32,
+                rs1_p,
+                funct3::LW,
+                rd_p,
+                opcodes::OP_LOAD,
+            )),
+            // C.LD
+            0b011 => Some(i_type(
+                ld_off as i32,
+                rs1_p,
+                funct3::LD,
+                rd_p,
+                opcodes::OP_LOAD,
+            )),
+            // C.FSD
+            0b101 => Some(s_type(
+                ld_off as i32,
+                rd_p,
+                rs1_p,
+                funct3::SD,
+                opcodes::OP_STORE_FP,
+            )),
+            // C.SW
+            0b110 => Some(s_type(
+                lw_off as i32,
+                rd_p,
+                rs1_p,
+                funct3::SW,
+                opcodes::OP_STORE,
+            )),
+            // C.SD
+            0b111 => Some(s_type(
+                ld_off as i32,
+                rd_p,
+                rs1_p,
+                funct3::SD,
+                opcodes::OP_STORE,
+            )),
+            _ => None,
+        },
+
+        QUADRANT_1 => match f3 {
+            // C.ADDI (C.NOP when rd == 0)
+            0b000 => Some(i_type(ci_imm, rd, funct3::ADD_SUB, rd, opcodes::OP_IMM)),
+            // C.ADDIW
+            0b001 => {
+                if rd == 0 {
+                    return None;
+                }
+                Some(i_type(ci_imm, rd, funct3::ADD_SUB, rd, opcodes::OP_IMM_32))
+            }
+            // C.LI
+            0b010 => Some(i_type(ci_imm, 0, funct3::ADD_SUB, rd, opcodes::OP_IMM)),
+            0b011 => {
+                if rd == 2 {
+                    // C.ADDI16SP
+                    let imm = sext(
+                        (((i >> 12) & 1) << 9)
+                            | (((i >> 3) & 0x3) << 7)
+                            | (((i >> 5) & 1) << 6)
+                            | (((i >> 2) & 1) << 5)
+                            | (((i >> 6) & 1) << 4),
+                        10,
+                    );
+                    if imm == 0 {
+                        return None;
+                    }
+                    Some(i_type(imm, 2, funct3::ADD_SUB, 2, opcodes::OP_IMM))
+                } else {
+                    // C.LUI
+                    if ci_imm == 0 {
+                        return None;
+                    }
+                    Some(u_type(ci_imm << 12, rd, opcodes::OP_LUI))
+                }
+            }
+            0b100 => {
+                let shamt = (((i >> 12) & 1) << 5) | ((i >> 2) & 0x1F);
+                match (i >> 10) & 0x3 {
+                    // C.SRLI
+                    0b00 => Some(i_type(
+                        shamt as i32,
+                        rs1_p,
+                        funct3::SRL_SRA,
+                        rs1_p,
+                        opcodes::OP_IMM,
+                    )),
+                    // C.SRAI
+                    0b01 => Some(i_type(
+                        (shamt | 0x400) as i32,
+                        rs1_p,
+                        funct3::SRL_SRA,
+                        rs1_p,
+                        opcodes::OP_IMM,
+                    )),
+                    // C.ANDI
+                    0b10 => Some(i_type(ci_imm, rs1_p, funct3::AND, rs1_p, opcodes::OP_IMM)),
+                    _ => {
+                        let rs2_p = rd_p;
+                        let is_word = (i >> 12) & 1 != 0;
+                        let (f7, f3, opcode) = match ((i >> 5) & 0x3, is_word) {
+                            (0b00, false) => (0b0100000, funct3::ADD_SUB, opcodes::OP_REG),
+                            (0b01, false) => (0, funct3::XOR, opcodes::OP_REG),
+                            (0b10, false) => (0, funct3::OR, opcodes::OP_REG),
+                            (0b11, false) => (0, funct3::AND, opcodes::OP_REG),
+                            (0b00, true) => (0b0100000, funct3::ADD_SUB, opcodes::OP_REG_32),
+                            (0b01, true) => (0, funct3::ADD_SUB, opcodes::OP_REG_32),
+                            _ => return None,
+                        };
+                        Some(r_type(f7, rs2_p, rs1_p, f3, rs1_p, opcode))
+                    }
+                }
+            }
+            // C.J
+            0b101 => {
+                let imm = sext(
+                    (((i >> 12) & 1) << 11)
+                        | (((i >> 8) & 1) << 10)
+                        | (((i >> 9) & 0x3) << 8)
+                        | (((i >> 6) & 1) << 7)
+                        | (((i >> 7) & 1) << 6)
+                        | (((i >> 2) & 1) << 5)
+                        | (((i >> 11) & 1) << 4)
+                        | (((i >> 3) & 0x7) << 1),
+                    12,
+                );
+                Some(j_type(imm, 0))
+            }
+            // C.BEQZ / C.BNEZ
+            _ => {
+                let imm = sext(
+                    (((i >> 12) & 1) << 8)
+                        | (((i >> 5) & 0x3) << 6)
+                        | (((i >> 2) & 1) << 5)
+                        | (((i >> 10) & 0x3) << 3)
+                        | (((i >> 3) & 0x3) << 1),
+                    9,
+                );
+                let f3 = if f3 == 0b110 {
+                    funct3::BEQ
+                } else {
+                    funct3::BNE
+                };
+                Some(b_type(imm, 0, rs1_p, f3))
+            }
+        },
+
+        QUADRANT_2 => match f3 {
+            // C.SLLI
+            0b000 => {
+                let shamt = (((i >> 12) & 1) << 5) | ((i >> 2) & 0x1F);
+                Some(i_type(shamt as i32, rd, funct3::SLL, rd, opcodes::OP_IMM))
+            }
+            // C.FLDSP
+            0b001 => {
+                let imm = (((i >> 12) & 1) << 5) | (((i >> 5) & 0x3) << 3) | (((i >> 2) & 0x7) << 6);
+                Some(i_type(imm as i32, 2, funct3::LD, rd, opcodes::OP_LOAD_FP))
+            }
+            // C.LWSP
+            0b010 => {
+                if rd == 0 {
+                    return None;
+                }
+                let imm = (((i >> 12) & 1) << 5) | (((i >> 4) & 0x7) << 2) | (((i >> 2) & 0x3) << 6);
+                Some(i_type(imm as i32, 2, funct3::LW, rd, opcodes::OP_LOAD))
+            }
+            // C.LDSP
+            0b011 => {
+                if rd == 0 {
+                    return None;
+                }
+                let imm = (((i >> 12) & 1) << 5) | (((i >> 5) & 0x3) << 3) | (((i >> 2) & 0x7) << 6);
+                Some(i_type(imm as i32, 2, funct3::LD, rd, opcodes::OP_LOAD))
+            }
+            0b100 => {
+                let bit12 = (i >> 12) & 1 != 0;
+                match (bit12, rd, rs2) {
+                    // C.JR
+                    (false, 0, 0) => None,
+                    (false, _, 0) => Some(i_type(0, rd, 0, 0, opcodes::OP_JALR)),
+                    // C.MV
+                    (false, _, _) => Some(r_type(0, rs2, 0, funct3::ADD_SUB, rd, opcodes::OP_REG)),
+                    // C.EBREAK
+                    (true, 0, 0) => Some(0x0010_0073),
+                    // C.JALR
+                    (true, _, 0) => Some(i_type(0, rd, 0, 1, opcodes::OP_JALR)),
+                    // C.ADD
+                    (true, _, _) => Some(r_type(0, rs2, rd, funct3::ADD_SUB, rd, opcodes::OP_REG)),
+                }
+            }
+            // C.FSDSP
+            0b101 => {
+                let imm = (((i >> 10) & 0x7) << 3) | (((i >> 7) & 0x7) << 6);
+                Some(s_type(imm as i32, rs2, 2, funct3::SD, opcodes::OP_STORE_FP))
+            }
+            // C.SWSP
+            0b110 => {
+                let imm = (((i >> 9) & 0xF) << 2) | (((i >> 7) & 0x3) << 6);
+                Some(s_type(imm as i32, rs2, 2, funct3::SW, opcodes::OP_STORE))
+            }
+            // C.SDSP
+            _ => {
+                let imm = (((i >> 10) & 0x7) << 3) | (((i >> 7) & 0x7) << 6);
+                Some(s_type(imm as i32, rs2, 2, funct3::SD, opcodes::OP_STORE))
+            }
+        },
+
+        _ => None,
+    }
+}
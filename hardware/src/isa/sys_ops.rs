@@ -7,6 +7,11 @@ pub const MRET: u32 = 0x3020_0073;
 pub const SRET: u32 = 0x1020_0073;
 pub const WFI: u32 = 0x1050_0073;
 pub const SFENCE_VMA: u32 = 0x1200_0073; // sfence.vma x0, x0
+pub const HFENCE_VVMA: u32 = 0x2200_0073; // hfence.vvma x0, x0
+pub const HFENCE_GVMA: u32 = 0x6200_0073; // hfence.gvma x0, x0
+
+// Clears the rs1/rs2 operands of the fences above, which are matched without them
+pub const FENCE_VMA_MASK: u32 = 0xFE00_7FFF;
 
 // CSR Funct3 Codes
 pub const CSRRW: u32 = 0b001;
@@ -16,6 +21,9 @@ pub const CSRRWI: u32 = 0b101;
 pub const CSRRSI: u32 = 0b110;
 pub const CSRRCI: u32 = 0b111;
 
+// Hypervisor virtual-machine loads and stores
+pub const HLSV: u32 = 0b100;
+
 // Trap Causes
 pub const CAUSE_MACHINE_TIMER: u64 = 0x8000_0000_0000_0007;
 pub const CAUSE_USER_ECALL: u64 = 8;
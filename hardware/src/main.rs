@@ -10,7 +10,7 @@ mod system;
 
 use crate::config::Config;
 use crate::core::Cpu;
-use crate::isa::abi;
+use crate::isa::{abi, csr};
 use crate::sim::loader;
 use crate::system::System;
 
@@ -36,6 +36,7 @@ fn main() {
 
     let system = System::new(&config, disk_path);
     let mut cpu = Cpu::new(system, &config);
+    println!("[*] ISA: {}", cpu.isa_string());
 
     if let Some(bin_path) = args.file {
         println!("[*] Direct Execution Mode");
@@ -50,6 +51,11 @@ fn main() {
 
         cpu.direct_mode = true;
         cpu.privilege = 0; // User mode
+        // No firmware or kernel runs here, so set up the user counters and
+        // cache-block operations directly
+        loader::setup_counters(&mut cpu);
+        cpu.csrs.menvcfg |= csr::ENVCFG_CBO;
+        cpu.csrs.senvcfg |= csr::ENVCFG_CBO;
     } else {
         println!("[*] Full System Mode");
         loader::setup_kernel_load(&mut cpu, &config, disk_path);
@@ -1,5 +1,5 @@
 use crate::config::Config;
-use crate::core::Cpu;
+use crate::core::{Cpu, hpm};
 use crate::isa::{abi, csr, sys_ops};
 use std::fs;
 use std::process;
@@ -11,6 +11,16 @@ pub fn load_binary(path: &str) -> Vec<u8> {
     })
 }
 
+/// Exposes the counters to S- and U-mode for the benchmarks, with
+/// hpmcounter3 onwards following the simulator events in order.
+pub fn setup_counters(cpu: &mut Cpu) {
+    for event in 1..=hpm::NUM_EVENTS {
+        cpu.csr_write(csr::MHPMEVENT3 + event as u32 - 1, event);
+    }
+    cpu.csr_write(csr::MCOUNTEREN, csr::COUNTEREN_MASK);
+    cpu.csr_write(csr::SCOUNTEREN, csr::COUNTEREN_MASK);
+}
+
 pub fn setup_kernel_load(cpu: &mut Cpu, config: &Config, disk_path: &str) {
     if disk_path.is_empty() {
         return;
@@ -37,11 +47,18 @@ pub fn setup_kernel_load(cpu: &mut Cpu, config: &Config, disk_path: &str) {
 
     cpu.csr_write(csr::MEPC, kernel_addr);
 
-    let mstatus_val = (1 << 11) | csr::MSTATUS_MPIE | csr::MSTATUS_FS_INIT;
+    let mstatus_val = (1 << 11) | csr::MSTATUS_MPIE | csr::MSTATUS_FS_INIT | csr::MSTATUS_VS_INIT;
     cpu.csr_write(csr::MSTATUS, mstatus_val);
     cpu.csr_write(csr::MEDELEG, 0xFFFF_FFFF_FFFF_FFFF);
     cpu.csr_write(csr::MIDELEG, 0xFFFF_FFFF_FFFF_FFFF);
     cpu.csr_write(csr::SATP, 0);
+    // Like boot firmware, open all of physical memory to S- and U-mode with one NAPOT entry
+    cpu.csr_write(csr::PMPADDR0, u64::MAX);
+    cpu.csr_write(csr::PMPCFG0, csr::PMPCFG_NAPOT_RWX);
+    setup_counters(cpu);
+    // Enable Sstc so the kernel can program its own timer through stimecmp,
+    // and let it use the cache-block operations
+    cpu.csr_write(csr::MENVCFG, csr::ENVCFG_STCE | csr::ENVCFG_CBO);
 
     cpu.regs.write(abi::REG_A0, 0);
     cpu.regs.write(abi::REG_A1, 0);
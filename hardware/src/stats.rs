@@ -1,5 +1,7 @@
 use std::time::Instant;
 
+use crate::core::units::{FuClass, Port};
+
 pub struct SimStats {
     start_time: Instant,
     pub cycles: u64,
@@ -16,6 +18,9 @@ pub struct SimStats {
     pub inst_fp_arith: u64,
     pub inst_fp_fma: u64,
     pub inst_fp_div_sqrt: u64,
+    pub inst_fp_half: u64,
+    pub inst_vector: u64,
+    pub inst_crypto: u64,
 
     pub branch_predictions: u64,
     pub branch_mispredictions: u64,
@@ -23,10 +28,21 @@ pub struct SimStats {
     pub cycles_user: u64,
     pub cycles_kernel: u64,
     pub cycles_machine: u64,
+    pub cycles_vs: u64,
+    pub cycles_vu: u64,
+    pub cycles_idle: u64,
 
     pub stalls_mem: u64,
     pub stalls_control: u64,
     pub stalls_data: u64,
+    /// Cycles spent waiting for a free unit of each class, indexed by `FuClass`
+    pub stalls_units: [u64; FuClass::COUNT],
+    /// Data-hazard stall cycles spent waiting for a result from each class
+    pub result_waits: [u64; FuClass::COUNT],
+    /// Issue slots used on each port, indexed by `Port`
+    pub ports_used: [u64; Port::COUNT],
+    /// Times an instruction could not issue because its port was taken
+    pub port_conflicts: u64,
 
     pub traps_taken: u64,
 
@@ -38,6 +54,17 @@ pub struct SimStats {
     pub l2_misses: u64,
     pub l3_hits: u64,
     pub l3_misses: u64,
+    pub itlb_misses: u64,
+    pub dtlb_misses: u64,
+
+    // Out-of-order back-end; rob_occupancy sums the ROB size over every cycle
+    pub rob_occupancy: u64,
+    pub stalls_rob_full: u64,
+    pub stalls_iq_full: u64,
+    pub stalls_lsq_full: u64,
+    pub stalls_regs_full: u64,
+    pub load_forwards: u64,
+    pub mem_order_violations: u64,
 }
 
 impl Default for SimStats {
@@ -56,14 +83,24 @@ impl Default for SimStats {
             inst_fp_arith: 0,
             inst_fp_fma: 0,
             inst_fp_div_sqrt: 0,
+            inst_fp_half: 0,
+            inst_vector: 0,
+            inst_crypto: 0,
             branch_predictions: 0,
             branch_mispredictions: 0,
             cycles_user: 0,
             cycles_kernel: 0,
             cycles_machine: 0,
+            cycles_vs: 0,
+            cycles_vu: 0,
+            cycles_idle: 0,
             stalls_mem: 0,
             stalls_control: 0,
             stalls_data: 0,
+            stalls_units: [0; FuClass::COUNT],
+            result_waits: [0; FuClass::COUNT],
+            ports_used: [0; Port::COUNT],
+            port_conflicts: 0,
             traps_taken: 0,
             icache_hits: 0,
             icache_misses: 0,
@@ -73,6 +110,15 @@ impl Default for SimStats {
             l2_misses: 0,
             l3_hits: 0,
             l3_misses: 0,
+            itlb_misses: 0,
+            dtlb_misses: 0,
+            rob_occupancy: 0,
+            stalls_rob_full: 0,
+            stalls_iq_full: 0,
+            stalls_lsq_full: 0,
+            stalls_regs_full: 0,
+            load_forwards: 0,
+            mem_order_violations: 0,
         }
     }
 }
@@ -121,6 +167,21 @@ impl SimStats {
             self.cycles_machine,
             (self.cycles_machine as f64 / cyc as f64) * 100.0
         );
+        println!(
+            "  cycles.vs              {} ({:.2}%)",
+            self.cycles_vs,
+            (self.cycles_vs as f64 / cyc as f64) * 100.0
+        );
+        println!(
+            "  cycles.vu              {} ({:.2}%)",
+            self.cycles_vu,
+            (self.cycles_vu as f64 / cyc as f64) * 100.0
+        );
+        println!(
+            "  cycles.idle            {} ({:.2}%)",
+            self.cycles_idle,
+            (self.cycles_idle as f64 / cyc as f64) * 100.0
+        );
         println!(
             "  stalls.memory          {} ({:.2}%)",
             self.stalls_mem,
@@ -169,6 +230,21 @@ impl SimStats {
             self.inst_fp_arith,
             (self.inst_fp_arith as f64 / total_inst) * 100.0
         );
+        println!(
+            "  op.fp_half             {} ({:.2}%)",
+            self.inst_fp_half,
+            (self.inst_fp_half as f64 / total_inst) * 100.0
+        );
+        println!(
+            "  op.vector              {} ({:.2}%)",
+            self.inst_vector,
+            (self.inst_vector as f64 / total_inst) * 100.0
+        );
+        println!(
+            "  op.crypto              {} ({:.2}%)",
+            self.inst_crypto,
+            (self.inst_crypto as f64 / total_inst) * 100.0
+        );
         println!("----------------------------------------------------------");
         println!("BRANCH PREDICTION");
         let bp_total = self.branch_predictions;
@@ -204,6 +280,65 @@ impl SimStats {
         print_cache("L1-D", self.dcache_hits, self.dcache_misses);
         print_cache("L2", self.l2_hits, self.l2_misses);
         print_cache("L3", self.l3_hits, self.l3_misses);
+        println!("  tlb.i_misses           {}", self.itlb_misses);
+        println!("  tlb.d_misses           {}", self.dtlb_misses);
+        if self.ports_used.iter().any(|&n| n > 0) {
+            println!("----------------------------------------------------------");
+            println!("ISSUE PORTS");
+            let slots: u64 = self.ports_used.iter().sum();
+            println!(
+                "  issue.slots_used       {} ({:.2} per cycle)",
+                slots,
+                slots as f64 / cyc as f64
+            );
+            for port in Port::ALL {
+                let used = self.ports_used[port as usize];
+                println!(
+                    "  {:<22} {} ({:.2} per cycle)",
+                    format!("ports.{}", port.name()),
+                    used,
+                    used as f64 / cyc as f64
+                );
+            }
+            println!("  ports.conflicts        {}", self.port_conflicts);
+        }
+        if self
+            .stalls_units
+            .iter()
+            .chain(&self.result_waits)
+            .any(|&n| n > 0)
+        {
+            println!("----------------------------------------------------------");
+            println!("FUNCTIONAL UNITS");
+            for class in FuClass::ALL {
+                println!(
+                    "  {:<22} {}",
+                    format!("stalls.{}", class.name()),
+                    self.stalls_units[class as usize]
+                );
+            }
+            for class in FuClass::ALL {
+                println!(
+                    "  {:<22} {}",
+                    format!("result_waits.{}", class.name()),
+                    self.result_waits[class as usize]
+                );
+            }
+        }
+        if self.rob_occupancy > 0 {
+            println!("----------------------------------------------------------");
+            println!("OUT-OF-ORDER CORE");
+            println!(
+                "  rob.avg_occupancy      {:.2}",
+                self.rob_occupancy as f64 / cyc as f64
+            );
+            println!("  stalls.rob_full        {}", self.stalls_rob_full);
+            println!("  stalls.iq_full         {}", self.stalls_iq_full);
+            println!("  stalls.lsq_full        {}", self.stalls_lsq_full);
+            println!("  stalls.regs_full       {}", self.stalls_regs_full);
+            println!("  lsq.forwards           {}", self.load_forwards);
+            println!("  lsq.order_violations   {}", self.mem_order_violations);
+        }
         println!("==========================================================");
     }
 }
@@ -31,7 +31,7 @@ impl Bus {
 
     /// Calculates the transit time for a packet of `bytes` size over the bus.
     pub fn calculate_transit_time(&self, bytes: usize) -> u64 {
-        let transfers = (bytes as u64 + self.width_bytes - 1) / self.width_bytes;
+        let transfers = (bytes as u64).div_ceil(self.width_bytes);
         self.latency_cycles + transfers
     }
 
@@ -59,14 +59,32 @@ impl Bus {
         false
     }
 
-    pub fn tick(&mut self) -> bool {
-        let mut interrupt_pending = false;
+    pub fn tick(&mut self) -> u64 {
+        let mut irq_lines = 0;
         for dev in &mut self.devices {
-            if dev.tick() {
-                interrupt_pending = true;
-            }
+            irq_lines |= dev.tick();
+        }
+        irq_lines
+    }
+
+    /// Ticks until the earliest device interrupt event, if any device has one scheduled.
+    pub fn next_event(&self) -> Option<u64> {
+        self.devices.iter().filter_map(|d| d.next_event()).min()
+    }
+
+    /// The platform timer's `mtime`, or 0 if there is no timer on the bus.
+    pub fn time(&self) -> u64 {
+        self.devices.iter().find_map(|d| d.time()).unwrap_or(0)
+    }
+
+    pub fn ticks_until(&self, target: u64) -> Option<u64> {
+        self.devices.iter().find_map(|d| d.ticks_until(target))
+    }
+
+    pub fn skip(&mut self, ticks: u64) {
+        for dev in &mut self.devices {
+            dev.skip(ticks);
         }
-        interrupt_pending
     }
 
     fn find_device(&mut self, paddr: u64) -> Option<(&mut Box<dyn Device>, u64)> {
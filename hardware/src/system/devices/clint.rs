@@ -1,3 +1,4 @@
+use crate::isa::csr;
 use crate::system::devices::Device;
 
 const MSIP_OFFSET: u64 = 0x0000;
@@ -94,13 +95,49 @@ impl Device for Clint {
         }
     }
 
-    fn tick(&mut self) -> bool {
+    fn tick(&mut self) -> u64 {
         self.counter += 1;
         if self.counter >= self.divider {
             self.mtime = self.mtime.wrapping_add(1);
             self.counter = 0;
         }
-        // Assert interrupt if mtime >= mtimecmp
-        self.mtime >= self.mtimecmp || (self.msip & 1) != 0
+
+        // MTIP while mtime >= mtimecmp, MSIP while msip is set
+        let mut lines = 0;
+        if self.mtime >= self.mtimecmp {
+            lines |= csr::MIP_MTIP;
+        }
+        if self.msip & 1 != 0 {
+            lines |= csr::MIP_MSIP;
+        }
+        lines
+    }
+
+    fn next_event(&self) -> Option<u64> {
+        // MTIP stays asserted once mtime has reached mtimecmp
+        self.ticks_until(self.mtimecmp)
+    }
+
+    fn time(&self) -> Option<u64> {
+        Some(self.mtime)
+    }
+
+    fn ticks_until(&self, target: u64) -> Option<u64> {
+        // An all-ones compare value is the conventional way to disable a timer
+        if self.mtime >= target || target == u64::MAX {
+            return None;
+        }
+        let increments = target - self.mtime;
+        (increments - 1)
+            .checked_mul(self.divider)?
+            .checked_add(self.divider - self.counter)
+    }
+
+    fn skip(&mut self, ticks: u64) {
+        let counter = self.counter + ticks % self.divider;
+        self.mtime = self
+            .mtime
+            .wrapping_add(ticks / self.divider + counter / self.divider);
+        self.counter = counter % self.divider;
     }
 }
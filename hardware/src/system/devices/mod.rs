@@ -32,7 +32,33 @@ pub trait Device {
         }
     }
 
-    fn tick(&mut self) -> bool {
-        false
+    /// Advances the device by one cycle and returns the interrupt lines it
+    /// currently asserts, as `mip` bits.
+    fn tick(&mut self) -> u64 {
+        0
+    }
+
+    /// Returns how many ticks remain until the device's interrupt lines change
+    /// on their own, or `None` if only a bus access can change them.
+    fn next_event(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns `mtime` if this device is the platform timer.
+    fn time(&self) -> Option<u64> {
+        None
+    }
+
+    /// For the platform timer, returns how many ticks remain until `mtime`
+    /// reaches `target`, or `None` if it already has or never will.
+    fn ticks_until(&self, _target: u64) -> Option<u64> {
+        None
+    }
+
+    /// Advances the device by `ticks` cycles at once.
+    fn skip(&mut self, ticks: u64) {
+        for _ in 0..ticks {
+            self.tick();
+        }
     }
 }
@@ -72,10 +72,31 @@ impl System {
         self.bus.load_binary_at(data, addr);
     }
 
-    pub fn tick(&mut self) -> bool {
+    pub fn tick(&mut self) -> u64 {
         self.bus.tick()
     }
 
+    /// Ticks until a device next changes its interrupt lines without CPU involvement.
+    pub fn next_event(&self) -> Option<u64> {
+        self.bus.next_event()
+    }
+
+    /// The current `mtime`, which the `time` CSR and the Sstc comparators read.
+    pub fn time(&self) -> u64 {
+        self.bus.time()
+    }
+
+    /// Ticks until `mtime` reaches `target`, if it has not already.
+    pub fn ticks_until(&self, target: u64) -> Option<u64> {
+        self.bus.ticks_until(target)
+    }
+
+    /// Advances every device by `ticks` cycles in one step, for use while the
+    /// core is idle and nothing else can happen in between.
+    pub fn fast_forward(&mut self, ticks: u64) {
+        self.bus.skip(ticks);
+    }
+
     pub fn check_exit(&self) -> Option<u64> {
         let val = self.exit_request.load(Ordering::Relaxed);
         if val != u64::MAX { Some(val) } else { None }
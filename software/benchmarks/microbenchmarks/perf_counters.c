@@ -3,16 +3,28 @@
 
 int main() {
   unsigned long start, end;
+  unsigned long l1d_start, l1d_end;
+  unsigned long bp_start, bp_end;
+  unsigned long lu_start, lu_end;
 
   // Workload
   volatile int k = 0;
 
   start = read_cycles();
+  l1d_start = read_hpmcounter(HPM_L1D_MISS);
+  bp_start = read_hpmcounter(HPM_BRANCH_MISPREDICT);
+  lu_start = read_hpmcounter(HPM_LOAD_USE_STALL);
   for (int i = 0; i < 10000; i++) {
     k += i;
   }
+  lu_end = read_hpmcounter(HPM_LOAD_USE_STALL);
+  bp_end = read_hpmcounter(HPM_BRANCH_MISPREDICT);
+  l1d_end = read_hpmcounter(HPM_L1D_MISS);
   end = read_cycles();
 
   printf("Benchmark Cycles: %lu\n", end - start);
+  printf("L1-D Misses: %lu\n", l1d_end - l1d_start);
+  printf("Branch Mispredicts: %lu\n", bp_end - bp_start);
+  printf("Load-Use Stalls: %lu\n", lu_end - lu_start);
   return 0;
 }
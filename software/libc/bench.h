@@ -15,4 +15,24 @@ static inline unsigned long read_instret(void) {
   return insts;
 }
 
+// Hardware performance counters programmed by the boot firmware
+#define HPM_L1I_MISS 3
+#define HPM_L1D_MISS 4
+#define HPM_L2_MISS 5
+#define HPM_L3_MISS 6
+#define HPM_BRANCH_MISPREDICT 7
+#define HPM_ITLB_MISS 8
+#define HPM_DTLB_MISS 9
+#define HPM_MEM_STALL 10
+#define HPM_LOAD_USE_STALL 11
+#define HPM_FP_OP 12
+
+// Reads hpmcounter<n> (CSR 0xC00 + n); n must be a constant
+#define read_hpmcounter(n)                                                     \
+  ({                                                                           \
+    unsigned long _val;                                                        \
+    asm volatile("csrr %0, %1" : "=r"(_val) : "i"(0xC00 + (n)));               \
+    _val;                                                                      \
+  })
+
 #endif